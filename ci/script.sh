@@ -13,6 +13,7 @@ main() {
 
     cross test --target $TARGET
     cross test --target $TARGET --release
+    cross test --target $TARGET --features log
 
     cross run --target $TARGET
     cross run --target $TARGET --release
@@ -0,0 +1,399 @@
+//! logger(1) replacement: sends messages from the command line or stdin
+//! through the slog-syslog-ng drains, so shell scripts log with the same
+//! headers, framing and structured data as the Rust services
+//!
+//! ```text
+//! slog-logger -p local3.notice -t backup -n logs.internal -T --rfc5424 \
+//!     --sd-id backup@32473 --sd-param 'volume="data"' "backup finished"
+//! ```
+
+#[macro_use]
+extern crate slog;
+extern crate slog_syslog_ng;
+
+use slog::{Drain, DrainExt, Level, Logger, OwnedKeyValueList, Record, RecordStatic};
+use slog::ser::Serialize;
+use slog_syslog_ng::*;
+use std::{env, process};
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+
+const USAGE: &'static str = "Usage: slog-logger [OPTIONS] [MESSAGE...]
+
+Logs MESSAGE, or each line of stdin when no message is given.
+
+Options:
+    -p, --priority PRI      Facility and severity, e.g. local3.notice, or a severity
+                            of the user facility; default user.notice
+    -t, --tag TAG           TAG, RFC5424 APP-NAME; default the process name
+    -i                      Log the pid of slog-logger as PROCID
+        --id ID             Log ID as PROCID
+    -n, --server HOST       Send to the remote syslog server, over UDP unless -T
+    -P, --port PORT         Port of the remote server; default 514
+    -T, --tcp               Use TCP
+    -d, --udp               Use UDP
+    -u, --socket PATH       Send to the unix domain socket, e.g. /dev/log
+        --rfc3164           RFC3164 message format, the default
+        --rfc5424           RFC5424 message format
+        --sd-id SD-ID       Add an SD-ELEMENT, with the following --sd-param
+        --sd-param NAME=\"VALUE\"
+                            Add a parameter to the last --sd-id element
+    -s, --stderr            Print messages to stderr as well
+    -h, --help              Print this help";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Protocol {
+    Default,
+    UDP,
+    TCP,
+}
+
+#[derive(Debug)]
+struct Args {
+    facility: Facility,
+    severity: Severity,
+    tag: Option<String>,
+    proc_id: ProcId,
+    server: Option<String>,
+    port: u16,
+    protocol: Protocol,
+    socket: Option<String>,
+    mode: FormatMode,
+    // SD-ID and the (name, value) parameters of each element
+    structured_data: Vec<(String, Vec<(String, String)>)>,
+    stderr: bool,
+    message: Vec<String>,
+}
+
+impl Default for Args {
+    fn default() -> Args {
+        Args {
+            facility: Facility::LOG_USER,
+            severity: Severity::LOG_NOTICE,
+            tag: None,
+            proc_id: ProcId::Omit,
+            server: None,
+            port: SYSLOG_DEFAULT_PORT,
+            protocol: Protocol::Default,
+            socket: None,
+            mode: FormatMode::RFC3164,
+            structured_data: Vec::new(),
+            stderr: false,
+            message: Vec::new(),
+        }
+    }
+}
+
+fn invalid<T>(message: String) -> SyslogResult<T> {
+    Err(SyslogError::ConfigInvalid(message))
+}
+
+fn value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> SyslogResult<String> {
+    args.next().ok_or_else(|| SyslogError::ConfigInvalid(format!("{} requires a value", flag)))
+}
+
+// `facility.severity`, or a severity of the user facility
+fn parse_priority(priority: &str) -> SyslogResult<(Facility, Severity)> {
+    let mut parts = priority.splitn(2, '.');
+    match (parts.next(), parts.next()) {
+        (Some(facility), Some(severity)) => Ok((facility.parse()?, severity.parse()?)),
+        _ => Ok((Facility::LOG_USER, priority.parse()?)),
+    }
+}
+
+// `name="value"`, quotes are optional
+fn parse_sd_param(param: &str) -> SyslogResult<(String, String)> {
+    let mut parts = param.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(name), Some(value)) if !name.is_empty() => {
+            let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                &value[1..value.len() - 1]
+            } else {
+                value
+            };
+            Ok((name.to_string(), value.to_string()))
+        }
+        _ => invalid(format!("Invalid --sd-param, expected NAME=\"VALUE\": {}", param)),
+    }
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> SyslogResult<Args> {
+    let mut parsed = Args::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "-p" | "--priority" => {
+                let (facility, severity) = parse_priority(&value(&mut args, &flag)?)?;
+                parsed.facility = facility;
+                parsed.severity = severity;
+            }
+            "-t" | "--tag" => parsed.tag = Some(value(&mut args, &flag)?),
+            "-i" => parsed.proc_id = ProcId::Pid,
+            "--id" => parsed.proc_id = ProcId::Custom(value(&mut args, &flag)?),
+            "-n" | "--server" => parsed.server = Some(value(&mut args, &flag)?),
+            "-P" | "--port" => {
+                let port = value(&mut args, &flag)?;
+                parsed.port = port.parse()
+                    .or_else(|_| invalid(format!("Invalid port: {}", port)))?;
+            }
+            "-T" | "--tcp" => parsed.protocol = Protocol::TCP,
+            "-d" | "--udp" => parsed.protocol = Protocol::UDP,
+            "-u" | "--socket" => parsed.socket = Some(value(&mut args, &flag)?),
+            "--rfc3164" => parsed.mode = FormatMode::RFC3164,
+            "--rfc5424" => parsed.mode = FormatMode::RFC5424,
+            "--sd-id" => parsed.structured_data.push((value(&mut args, &flag)?, Vec::new())),
+            "--sd-param" => {
+                let param = parse_sd_param(&value(&mut args, &flag)?)?;
+                match parsed.structured_data.last_mut() {
+                    Some(&mut (_, ref mut params)) => params.push(param),
+                    None => return invalid("--sd-param requires a preceding --sd-id".to_string()),
+                }
+            }
+            "-s" | "--stderr" => parsed.stderr = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            "--" => parsed.message.extend(args.by_ref()),
+            _ if flag.starts_with('-') && flag.len() > 1 => {
+                return invalid(format!("Unknown option: {}", flag))
+            }
+            _ => parsed.message.push(flag),
+        }
+    }
+
+    if !parsed.structured_data.is_empty() && parsed.mode != FormatMode::RFC5424 {
+        return invalid("--sd-id requires --rfc5424".to_string());
+    }
+    if parsed.socket.is_some() &&
+       (parsed.server.is_some() || parsed.protocol != Protocol::Default) {
+        return invalid("--socket can't be combined with --server, --tcp or --udp".to_string());
+    }
+    Ok(parsed)
+}
+
+// Settings shared by all the transports
+fn configure<T>(config: SyslogConfig<T>, args: &Args) -> SyslogConfig<T> {
+    let mut config = config.mode(args.mode.clone())
+        .facility(args.facility)
+        .severity(args.severity)
+        .proc_id(args.proc_id.clone());
+    if let Some(ref tag) = args.tag {
+        config = config.app_name(tag.as_str());
+    }
+    for &(ref sd_id, ref params) in &args.structured_data {
+        let mut element = SdElement::new(sd_id.as_str());
+        for &(ref name, _) in params {
+            element = element.param(sd_key(sd_id, name), name.as_str());
+        }
+        config = config.sd_element(element);
+    }
+    config
+}
+
+// Record key of an SD-PARAM, unique across the elements
+fn sd_key(sd_id: &str, name: &str) -> String {
+    format!("{}/{}", sd_id, name)
+}
+
+fn connect(args: &Args) -> SyslogResult<ConfiguredDrain> {
+    let config = configure(syslog(), args);
+    if let Some(ref socket) = args.socket {
+        return config.uds().socket(socket.as_str()).connect();
+    }
+    let server = (args.server.clone().unwrap_or_else(|| "localhost".to_string()), args.port);
+    match (args.protocol, args.server.is_some()) {
+        (Protocol::TCP, _) => config.tcp().server(server).connect(),
+        (Protocol::UDP, _) |
+        (Protocol::Default, true) => config.udp().server(server).connect(),
+        (Protocol::Default, false) => config.connect(),
+    }
+}
+
+// Level closest to the severity, the configured severity is sent as it is
+fn level_of(severity: Severity) -> Level {
+    match severity {
+        Severity::LOG_EMERG | Severity::LOG_ALERT | Severity::LOG_CRIT => Level::Critical,
+        Severity::LOG_ERR => Level::Error,
+        Severity::LOG_WARN => Level::Warning,
+        Severity::LOG_NOTICE | Severity::LOG_INFO => Level::Info,
+        Severity::LOG_DEBUG => Level::Debug,
+    }
+}
+
+// Keeps the first error of the drain, to report it and exit with 1
+// instead of the panic of a fused drain
+struct CheckedDrain {
+    drain: Arc<ConfiguredDrain>,
+    error: Arc<Mutex<Option<SyslogError>>>,
+}
+
+impl Drain for CheckedDrain {
+    type Error = SyslogError;
+
+    fn log(&self, record: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<()> {
+        if let Err(err) = self.drain.log(record, logger_values) {
+            if let Ok(mut error) = self.error.lock() {
+                if error.is_none() {
+                    *error = Some(err);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Drain errors are converted, to be reported along with the ones of stdin
+fn run(args: Args) -> io::Result<()> {
+    let error = Arc::new(Mutex::new(None));
+    let configured = Arc::new(connect(&args)?);
+    let drain = CheckedDrain {
+        drain: configured.clone(),
+        error: error.clone(),
+    };
+    let logger = Logger::root(drain.fuse(), o!());
+
+    // Record keys are `&'static str`, the few keys of the command line
+    // are leaked for the lifetime of the process
+    let params: Vec<(&'static str, &String)> = args.structured_data
+        .iter()
+        .flat_map(|&(ref sd_id, ref params)| {
+            params.iter().map(move |&(ref name, ref value)| {
+                let key: &'static str = Box::leak(sd_key(sd_id, name).into_boxed_str());
+                (key, value)
+            })
+        })
+        .collect();
+    let values: Vec<(&'static str, &Serialize)> =
+        params.iter().map(|&(key, value)| (key, value as &Serialize)).collect();
+
+    let record_static = RecordStatic {
+        level: level_of(args.severity),
+        file: file!(),
+        line: line!(),
+        column: column!(),
+        function: "",
+        module: module_path!(),
+        target: module_path!(),
+    };
+    {
+        let log = |message: &str| {
+            if args.stderr {
+                let _ = writeln!(io::stderr(), "{}", message);
+            }
+            logger.log(&Record::new(&record_static, format_args!("{}", message), &values));
+        };
+
+        if args.message.is_empty() {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                let line = line?;
+                if !line.is_empty() {
+                    log(&line);
+                }
+            }
+        } else {
+            log(&args.message.join(" "));
+        }
+    }
+
+    // Pending "last message repeated" summary is sent explicitly,
+    // a failure is reported unless an earlier message failed already
+    drop(logger);
+    let flushed = configured.flush();
+    let error = error.lock().ok().and_then(|mut error| error.take());
+    match (error, flushed) {
+        (Some(err), _) |
+        (None, Err(err)) => Err(err.into()),
+        (None, Ok(())) => Ok(()),
+    }
+}
+
+fn main() {
+    let result = parse_args(env::args().skip(1)).map_err(io::Error::from).and_then(run);
+    if let Err(err) = result {
+        let _ = writeln!(io::stderr(), "slog-logger: {}\n\n{}", err, USAGE);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> SyslogResult<Args> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    // Message of the `ConfigInvalid` error of the arguments
+    fn invalid_message(args: &[&str]) -> String {
+        match parse(args) {
+            Err(SyslogError::ConfigInvalid(message)) => message,
+            other => panic!("Expected ConfigInvalid, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn priority_with_facility_or_bare_severity() {
+        assert_eq!(parse_priority("local3.notice").unwrap(),
+                   (Facility::LOG_LOCAL3, Severity::LOG_NOTICE));
+        assert_eq!(parse_priority("err").unwrap(), (Facility::LOG_USER, Severity::LOG_ERR));
+        assert!(parse_priority("local3.loud").is_err());
+        assert!(parse_priority("local9.notice").is_err());
+
+        let args = parse(&["-p", "local3.notice", "backup done"]).unwrap();
+        assert_eq!(args.facility, Facility::LOG_LOCAL3);
+        assert_eq!(args.severity, Severity::LOG_NOTICE);
+        assert_eq!(args.message, vec!["backup done".to_string()]);
+    }
+
+    #[test]
+    fn unknown_option_is_rejected() {
+        assert_eq!(invalid_message(&["--verbose", "text"]), "Unknown option: --verbose");
+        assert_eq!(invalid_message(&["-p"]), "-p requires a value");
+    }
+
+    #[test]
+    fn sd_param_values_quoted_or_not() {
+        assert_eq!(parse_sd_param("volume=\"data\"").unwrap(),
+                   ("volume".to_string(), "data".to_string()));
+        assert_eq!(parse_sd_param("volume=data").unwrap(),
+                   ("volume".to_string(), "data".to_string()));
+        assert_eq!(parse_sd_param("note=\"a=b\"").unwrap(),
+                   ("note".to_string(), "a=b".to_string()));
+        assert!(parse_sd_param("=data").is_err());
+        assert!(parse_sd_param("volume").is_err());
+
+        let args = parse(&["--rfc5424", "--sd-id", "backup@32473", "--sd-param", "volume=\"data\"",
+                           "--sd-param", "files=12", "done"])
+            .unwrap();
+        assert_eq!(args.structured_data,
+                   vec![("backup@32473".to_string(),
+                         vec![("volume".to_string(), "data".to_string()),
+                              ("files".to_string(), "12".to_string())])]);
+    }
+
+    #[test]
+    fn structured_data_requires_sd_id_and_rfc5424() {
+        assert_eq!(invalid_message(&["--rfc5424", "--sd-param", "volume=data"]),
+                   "--sd-param requires a preceding --sd-id");
+        assert_eq!(invalid_message(&["--sd-id", "backup@32473", "--sd-param", "volume=data"]),
+                   "--sd-id requires --rfc5424");
+    }
+
+    #[test]
+    fn socket_excludes_server_and_protocol() {
+        assert_eq!(invalid_message(&["-u", "/dev/log", "-n", "logs.internal"]),
+                   "--socket can't be combined with --server, --tcp or --udp");
+        assert!(parse(&["-u", "/dev/log", "-T"]).is_err());
+        assert_eq!(parse(&["-u", "/dev/log"]).unwrap().socket, Some("/dev/log".to_string()));
+    }
+
+    #[test]
+    fn arguments_after_double_dash_are_message() {
+        let args = parse(&["-s", "--", "-p", "--not-an-option", "text"]).unwrap();
+        assert!(args.stderr);
+        assert_eq!(args.severity, Severity::LOG_NOTICE);
+        assert_eq!(args.message,
+                   vec!["-p".to_string(), "--not-an-option".to_string(), "text".to_string()]);
+    }
+}
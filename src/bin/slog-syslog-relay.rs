@@ -0,0 +1,152 @@
+//! Syslog relay: receives RFC3164 and RFC5424 messages over UDP, TCP, TLS or
+//! a unix domain socket, and forwards them through a slog-syslog-ng drain,
+//! with its reconnects, failover and spooling
+//!
+//! ```text
+//! slog-syslog-relay --udp 0.0.0.0:514 --uds /dev/log --convert rfc5424 \
+//!     --forward 'tcp://logs.internal:514?mode=rfc5424&spool=/var/spool/relay'
+//! ```
+
+extern crate slog_syslog_ng;
+
+use slog_syslog_ng::*;
+use std::{env, fs, process};
+use std::io::{self, Write};
+use std::net::{TcpListener, UdpSocket};
+use std::os::unix::net::UnixDatagram;
+use std::sync::Arc;
+use std::time::Duration;
+
+const USAGE: &'static str = "Usage: slog-syslog-relay [OPTIONS] --forward URL
+
+Listeners, each can be repeated:
+    --udp ADDR          Receive datagrams on the UDP address, e.g. 0.0.0.0:514
+    --tcp ADDR          Accept TCP connections on the address
+    --tls ADDR          Accept TLS connections on the address, requires --cert and --key
+    --uds PATH          Receive datagrams on the unix domain socket, e.g. /dev/log
+
+Options:
+    --forward URL       Destination, as SYSLOG_URL, e.g. tcp://logs:514?mode=rfc5424
+    --cert FILE         PEM certificate chain of the TLS listeners
+    --key FILE          PEM private key of the TLS listeners
+    --convert MODE      Convert messages to rfc3164 or rfc5424, forwarded as received otherwise
+    --ksv-sd-id SD-ID   Move key=value pairs of RFC3164 messages converted to RFC5424
+                        to an SD-ELEMENT, e.g. msg@32473
+    --read-timeout SECS Close TCP and TLS connections idle for SECS, 0 keeps them open
+                        [default: 60]
+    --max-connections N Maximal number of open TCP and TLS connections [default: 256]
+    -h, --help          Print this help";
+
+#[derive(Debug, Default)]
+struct Args {
+    udp: Vec<String>,
+    tcp: Vec<String>,
+    tls: Vec<String>,
+    uds: Vec<String>,
+    forward: Option<String>,
+    cert: Option<String>,
+    key: Option<String>,
+    config: RelayConfig,
+}
+
+fn value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> SyslogResult<String> {
+    args.next().ok_or_else(|| SyslogError::ConfigInvalid(format!("{} requires a value", flag)))
+}
+
+fn number<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> SyslogResult<usize> {
+    let number = value(args, flag)?;
+    number.parse::<usize>()
+        .map_err(|_| SyslogError::ConfigInvalid(format!("Invalid {} value: {}", flag, number)))
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> SyslogResult<Args> {
+    let mut parsed = Args::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--udp" => parsed.udp.push(value(&mut args, &flag)?),
+            "--tcp" => parsed.tcp.push(value(&mut args, &flag)?),
+            "--tls" => parsed.tls.push(value(&mut args, &flag)?),
+            "--uds" => parsed.uds.push(value(&mut args, &flag)?),
+            "--forward" => parsed.forward = Some(value(&mut args, &flag)?),
+            "--cert" => parsed.cert = Some(value(&mut args, &flag)?),
+            "--key" => parsed.key = Some(value(&mut args, &flag)?),
+            "--convert" => {
+                let mode = value(&mut args, &flag)?.parse::<FormatMode>()?;
+                parsed.config = parsed.config.convert(mode);
+            }
+            "--ksv-sd-id" => parsed.config = parsed.config.ksv_sd_id(value(&mut args, &flag)?),
+            "--read-timeout" => {
+                let timeout = match number(&mut args, &flag)? {
+                    0 => None,
+                    secs => Some(Duration::from_secs(secs as u64)),
+                };
+                parsed.config = parsed.config.read_timeout(timeout);
+            }
+            "--max-connections" => {
+                parsed.config = parsed.config.max_connections(number(&mut args, &flag)?)
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(SyslogError::ConfigInvalid(format!("Unknown option: {}", flag))),
+        }
+    }
+    Ok(parsed)
+}
+
+fn run(args: Args) -> SyslogResult<()> {
+    let forward = args.forward
+        .ok_or_else(|| SyslogError::ConfigInvalid("--forward is required".to_string()))?;
+    let drain = forward.parse::<SyslogSettings>()?.into_config()?.connect()?;
+    let relay = Arc::new(Relay::new(drain, args.config));
+
+    let mut listeners = Vec::new();
+    for addr in &args.udp {
+        let socket = UdpSocket::bind(addr.as_str())
+            .map_err(|err| SyslogError::connect(Transport::UDP, addr, err))?;
+        listeners.push(listen_udp(relay.clone(), socket));
+    }
+    for addr in &args.tcp {
+        let listener = TcpListener::bind(addr.as_str())
+            .map_err(|err| SyslogError::connect(Transport::TCP, addr, err))?;
+        listeners.push(listen_tcp(relay.clone(), listener));
+    }
+    if !args.tls.is_empty() {
+        let (cert, key) = match (args.cert.as_ref(), args.key.as_ref()) {
+            (Some(cert), Some(key)) => (cert, key),
+            _ => {
+                return Err(SyslogError::ConfigInvalid("--tls requires --cert and --key"
+                                                          .to_string()))
+            }
+        };
+        for addr in &args.tls {
+            let listener = TcpListener::bind(addr.as_str())
+                .map_err(|err| SyslogError::connect(Transport::TLS, addr, err))?;
+            listeners.push(listen_tls(relay.clone(), listener, cert, key)?);
+        }
+    }
+    for path in &args.uds {
+        // Socket file left by a previous run
+        let _ = fs::remove_file(path);
+        let socket = UnixDatagram::bind(path)
+            .map_err(|err| SyslogError::connect(Transport::UDS, path, err))?;
+        listeners.push(listen_uds(relay.clone(), socket));
+    }
+    if listeners.is_empty() {
+        return Err(SyslogError::ConfigInvalid("No listener configured".to_string()));
+    }
+
+    for listener in listeners {
+        let _ = listener.join();
+    }
+    Ok(())
+}
+
+fn main() {
+    let result = parse_args(env::args().skip(1)).and_then(run);
+    if let Err(err) = result {
+        let _ = writeln!(io::stderr(), "slog-syslog-relay: {}\n\n{}", err, USAGE);
+        process::exit(1);
+    }
+}
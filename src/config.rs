@@ -1,6 +1,30 @@
-use std::net::{ToSocketAddrs, SocketAddr};
+use drain::{BoxedSyslogDrain, ConfiguredDrain};
+use error::{SyslogError, SyslogResult};
+use failover_drain::{DEFAULT_PROBE_INTERVAL, FailoverDrain};
+use fields::FieldTransforms;
+use format::{FormatOptions, HeaderFields, HostnameRefresh, ProcId, Rfc3164KsvTs3164Local,
+             Rfc3164KsvTs3164Utc, Rfc3164KsvTsIsoLocal, Rfc3164KsvTsIsoUtc, Rfc3164ShortKsv,
+             Rfc5424KsvTsIsoLocal, Rfc5424KsvTsIsoUtc, Rfc5424NativeTsIsoLocal,
+             Rfc5424NativeTsIsoUtc};
+use posix::{HostnameSource, ProcessNameSource, get_host_name_from, get_pid,
+            get_process_name_from, locate_default_uds_socket};
+use rate_limit::RateLimitConfig;
+use redaction::RedactionPolicy;
+use slog_stream::Format as StreamFormat;
+use spool::{SpoolConfig, SpoolDrain};
+use structured_data::{AutoStructuredData, SdElement, SourceLocation};
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, ToSocketAddrs, SocketAddr};
 use std::path::PathBuf;
-use syslog::Facility;
+use std::str::FromStr;
+use std::time::Duration;
+use syslog::{Facility, HOSTNAME_MAX_LEN, SYSLOG_DEFAULT_PORT, SYSLOG_DEFAULT_TLS_PORT, Severity};
+use tcp_drain::{Framing, StreamTimeouts, TCPDisconnected, TCPDrain, TCPDrainDelimited,
+                TCPDrainFramed};
+use tls_drain::{TLSConfig, TLSDisconnected, TLSDrain, TLSDrainDelimited, TLSDrainFramed};
+use udp_drain::UDPDrain;
+use uds_drain::{UDSDrain, UDSRetryConfig, UDSSocketType};
 
 #[derive(Debug, PartialEq, Clone)]
 /// Syslog message format
@@ -28,6 +52,18 @@ impl Default for FormatMode {
     }
 }
 
+impl FromStr for FormatMode {
+    type Err = SyslogError;
+    fn from_str(s: &str) -> Result<FormatMode, SyslogError> {
+        let result = match &s.to_lowercase()[..] {
+            "rfc3164" | "3164" | "bsd" => FormatMode::RFC3164,
+            "rfc5424" | "5424" | "ietf" => FormatMode::RFC5424,
+            _ => return Err(SyslogError::ConfigInvalid(format!("Unknown format mode: {}", s))),
+        };
+        Ok(result)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 ///  Structured data serialization format
 ///
@@ -62,6 +98,22 @@ impl Default for SerializationFormat {
     }
 }
 
+impl FromStr for SerializationFormat {
+    type Err = SyslogError;
+    fn from_str(s: &str) -> Result<SerializationFormat, SyslogError> {
+        let result = match &s.to_lowercase()[..] {
+            "ksv" | "kv" => SerializationFormat::KSV,
+            "cee" | "json" => SerializationFormat::CEE,
+            "native" => SerializationFormat::Native,
+            _ => {
+                return Err(SyslogError::ConfigInvalid(format!("Unknown serialization format: {}",
+                                                              s)))
+            }
+        };
+        Ok(result)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 /// Timestamp timezone
 ///
@@ -82,6 +134,18 @@ impl Default for TimestampTZ {
     }
 }
 
+impl FromStr for TimestampTZ {
+    type Err = SyslogError;
+    fn from_str(s: &str) -> Result<TimestampTZ, SyslogError> {
+        let result = match &s.to_lowercase()[..] {
+            "local" => TimestampTZ::Local,
+            "utc" | "z" => TimestampTZ::UTC,
+            _ => return Err(SyslogError::ConfigInvalid(format!("Unknown timezone: {}", s))),
+        };
+        Ok(result)
+    }
+}
+
 
 /// Timestamp format
 ///
@@ -102,24 +166,181 @@ impl Default for TimestampFormat {
     }
 }
 
+impl FromStr for TimestampFormat {
+    type Err = SyslogError;
+    fn from_str(s: &str) -> Result<TimestampFormat, SyslogError> {
+        let result = match &s.to_lowercase()[..] {
+            "rfc3164" | "3164" => TimestampFormat::RFC3164,
+            "iso8601" | "rfc3339" => TimestampFormat::ISO8601,
+            _ => return Err(SyslogError::ConfigInvalid(format!("Unknown timestamp format: {}", s))),
+        };
+        Ok(result)
+    }
+}
+
 /// Empty configuration
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct DefaultConfig;
 
+/// RFC3164 TAG length limit
+pub const RFC3164_TAG_MAX_LEN: usize = 32;
+
+/// RFC5424 APP-NAME length limit
+pub const RFC5424_APP_NAME_MAX_LEN: usize = 48;
+
+/// RFC5424 PROCID length limit
+pub const RFC5424_PROCID_MAX_LEN: usize = 128;
+
+/// Questionable configuration, which works, but likely not the way it's expected to
+///
+/// Reported by `validate`, `connect` doesn't fail on warnings.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigWarning {
+    /// `Native` serialization falls back to key=value for RFC3164
+    NativeSerializationFallback,
+    /// Hostname is not sent over unix domain socket, local daemon fills it in
+    HostnameIgnored,
+    /// ISO8601 timestamps in RFC3164 messages may be rejected by older daemons
+    IsoTimestampInRfc3164,
+    /// RFC5424 recommends UTC timestamps
+    LocalTimezoneInRfc5424,
+    /// Detected process name doesn't fit APP-NAME (TAG) limits of the format
+    InvalidProcessName(String),
+    /// SD-ELEMENTs are not used by the configured format: key mappings
+    /// need RFC5424 native serialization, automatic elements need RFC5424
+    StructuredDataIgnored,
+}
+
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigWarning::NativeSerializationFallback => {
+                write!(f, "Native serialization falls back to key=value for RFC3164")
+            }
+            ConfigWarning::HostnameIgnored => {
+                write!(f, "Hostname is ignored for unix domain socket")
+            }
+            ConfigWarning::IsoTimestampInRfc3164 => {
+                write!(f, "ISO8601 timestamps in RFC3164 may be rejected by older daemons")
+            }
+            ConfigWarning::LocalTimezoneInRfc5424 => {
+                write!(f, "Local timezone is discouraged for RFC5424, use UTC")
+            }
+            ConfigWarning::InvalidProcessName(ref reason) => {
+                write!(f, "Process name can't be used as APP-NAME: {}", reason)
+            }
+            ConfigWarning::StructuredDataIgnored => {
+                write!(f, "SD-ELEMENTs are ignored by the configured format")
+            }
+        }
+    }
+}
+
+// PRINTUSASCII: %d33-126
+fn is_print_us_ascii(value: &str) -> bool {
+    value.bytes().all(|b| b >= 33 && b <= 126)
+}
+
+// Message of an invalid setting, for the list of all the invalid ones
+fn invalid_message(err: SyslogError) -> String {
+    match err {
+        SyslogError::ConfigInvalid(message) => message,
+        err => err.to_string(),
+    }
+}
+
+/// Check hostname against RFC5424 HOSTNAME limits: 1-255 printable US-ASCII characters
+pub fn validate_hostname(hostname: &str) -> Result<(), String> {
+    if hostname.is_empty() {
+        return Err("hostname is empty".to_string());
+    }
+    if hostname.len() > HOSTNAME_MAX_LEN {
+        return Err(format!("hostname is longer than {} characters", HOSTNAME_MAX_LEN));
+    }
+    if !is_print_us_ascii(hostname) {
+        return Err(format!("hostname {:?} contains non-printable, non-ASCII or space characters",
+                           hostname));
+    }
+    Ok(())
+}
+
+/// Check APP-NAME against the format limits
+///
+/// RFC3164 TAG: up to 32 characters, `:` and `[` would be taken for TAG delimiters.
+/// RFC5424 APP-NAME: up to 48 printable US-ASCII characters.
+pub fn validate_app_name(app_name: &str, mode: &FormatMode) -> Result<(), String> {
+    let limit = match *mode {
+        FormatMode::RFC3164 => RFC3164_TAG_MAX_LEN,
+        FormatMode::RFC5424 => RFC5424_APP_NAME_MAX_LEN,
+    };
+    if app_name.is_empty() {
+        return Err("APP-NAME is empty".to_string());
+    }
+    if app_name.len() > limit {
+        return Err(format!("APP-NAME {:?} is longer than {} characters", app_name, limit));
+    }
+    if !is_print_us_ascii(app_name) {
+        return Err(format!("APP-NAME {:?} contains non-printable, non-ASCII or space characters",
+                           app_name));
+    }
+    if *mode == FormatMode::RFC3164 && app_name.contains(|c| c == ':' || c == '[') {
+        return Err(format!("APP-NAME {:?} contains TAG delimiters ':' or '['", app_name));
+    }
+    Ok(())
+}
+
+/// Check custom PROCID against the format limits
+///
+/// RFC5424 PROCID: up to 128 printable US-ASCII characters.
+/// RFC3164 `[pid]` part of TAG, which `]` would terminate, has to fit the TAG limit.
+pub fn validate_proc_id(proc_id: &str, mode: &FormatMode) -> Result<(), String> {
+    let limit = match *mode {
+        FormatMode::RFC3164 => RFC3164_TAG_MAX_LEN,
+        FormatMode::RFC5424 => RFC5424_PROCID_MAX_LEN,
+    };
+    if proc_id.is_empty() {
+        return Err("PROCID is empty".to_string());
+    }
+    if proc_id.len() > limit {
+        return Err(format!("PROCID {:?} is longer than {} characters", proc_id, limit));
+    }
+    if !is_print_us_ascii(proc_id) {
+        return Err(format!("PROCID {:?} contains non-printable, non-ASCII or space characters",
+                           proc_id));
+    }
+    if *mode == FormatMode::RFC3164 && proc_id.contains(']') {
+        return Err(format!("PROCID {:?} contains TAG delimiter ']'", proc_id));
+    }
+    Ok(())
+}
+
 
 /// Unix domain socket specific configuration
 #[derive(Debug, Clone, PartialEq)]
 pub struct UDSConfig {
-    /// Path to syslog socket.
+    /// Path to syslog socket, paths starting with `@` are
+    /// Linux abstract namespace addresses.
     ///
     /// Default: `None`, will try to connect to
     /// `/dev/log` on Linux and `/var/run/syslog` on MacOS.
     pub socket: Option<PathBuf>,
+    /// Socket type [UDSSocketType](enum.UDSSocketType.html).
+    ///
+    /// Default: `Auto`, datagram socket with fallback to stream socket
+    pub socket_type: UDSSocketType,
+    /// Handling of failed sends [UDSRetryConfig](struct.UDSRetryConfig.html).
+    ///
+    /// Default: 3 retries, 10ms apart, blocking socket
+    pub retry: UDSRetryConfig,
 }
 
 impl Default for UDSConfig {
     fn default() -> UDSConfig {
-        UDSConfig { socket: None }
+        UDSConfig {
+            socket: None,
+            socket_type: UDSSocketType::default(),
+            retry: UDSRetryConfig::default(),
+        }
     }
 }
 
@@ -128,24 +349,33 @@ impl Default for UDSConfig {
 pub struct UDPConfig<S>
     where S: ToSocketAddrs
 {
-    /// Syslog server host - should convert to
+    /// Syslog server hosts in the order of preference - should convert to
     /// [ToSocketAddrs](https://doc.rust-lang.org/std/net/trait.ToSocketAddrs.html).
+    /// When more than one server is specified, messages are sent to the first healthy one
+    /// [FailoverDrain](struct.FailoverDrain.html).
     ///
-    /// Default: None. will try to connect to default ports on localhost
-    pub server: Option<S>,
+    /// Default: empty, will try to connect to default ports on localhost
+    pub servers: Vec<S>,
+    /// Interval between attempts to use server marked as unhealthy
+    ///
+    /// Default: 30 seconds
+    pub probe_interval: Duration,
 }
 
 impl<S: ToSocketAddrs> UDPConfig<S>
     where S: ToSocketAddrs
 {
-    fn new(server: S) -> Self {
-        UDPConfig { server: Some(server) }
+    fn new(servers: Vec<S>, probe_interval: Duration) -> Self {
+        UDPConfig {
+            servers: servers,
+            probe_interval: probe_interval,
+        }
     }
 }
 
 impl Default for UDPConfig<SocketAddr> {
     fn default() -> Self {
-        UDPConfig { server: None }
+        UDPConfig::new(Vec::new(), Duration::from_secs(DEFAULT_PROBE_INTERVAL))
     }
 }
 
@@ -154,24 +384,53 @@ impl Default for UDPConfig<SocketAddr> {
 pub struct TCPConfig<S>
     where S: ToSocketAddrs
 {
-    /// Syslog server host - should convert to
+    /// Syslog server hosts in the order of preference - should convert to
     /// [ToSocketAddrs](https://doc.rust-lang.org/std/net/trait.ToSocketAddrs.html).
+    /// When more than one server is specified, messages are sent to the first healthy one
+    /// [FailoverDrain](struct.FailoverDrain.html).
+    ///
+    /// Default: empty, will try to connect to default ports on localhost
+    pub servers: Vec<S>,
+    /// Interval between attempts to use server marked as unhealthy
+    ///
+    /// Default: 30 seconds
+    pub probe_interval: Duration,
+    /// Message framing [Framing](enum.Framing.html).
+    ///
+    /// Default: `None`, octet counting for RFC5424, LF delimited for RFC3164
+    pub framing: Option<Framing>,
+    /// TLS session configuration [TLSConfig](struct.TLSConfig.html).
     ///
-    /// Default: None. will try to connect to default ports on localhost
-    pub server: Option<S>,
+    /// Default: `None`, plain TCP
+    pub tls: Option<TLSConfig>,
+    /// Defer connecting until the first message is sent
+    ///
+    /// Default: false, connect fails if the servers are unreachable
+    pub lazy_connect: bool,
+    /// Timeouts of connecting and writing [StreamTimeouts](struct.StreamTimeouts.html).
+    ///
+    /// Default: OS defaults
+    pub timeouts: StreamTimeouts,
 }
 
 impl<S: ToSocketAddrs> TCPConfig<S>
     where S: ToSocketAddrs
 {
-    fn new(server: S) -> Self {
-        TCPConfig { server: Some(server) }
+    fn new(servers: Vec<S>, probe_interval: Duration) -> Self {
+        TCPConfig {
+            servers: servers,
+            probe_interval: probe_interval,
+            framing: None,
+            tls: None,
+            lazy_connect: false,
+            timeouts: StreamTimeouts::default(),
+        }
     }
 }
 
 impl Default for TCPConfig<SocketAddr> {
     fn default() -> Self {
-        TCPConfig { server: None }
+        TCPConfig::new(Vec::new(), Duration::from_secs(DEFAULT_PROBE_INTERVAL))
     }
 }
 
@@ -204,11 +463,77 @@ pub struct SyslogConfig<T> {
     ///
     /// Default: `LOG_USER`.
     pub facility: Facility,
+    /// Syslog severity of every message [Severity](enum.Severity.html).
+    ///
+    /// Default: `None`, mapped from the record level
+    pub severity: Option<Severity>,
     /// Hostname
     ///
     /// Default: `None` will be omitted for unix domain socket drain,
     /// autodetected in case of UDP or TCP drains
     pub hostname: Option<String>,
+    /// Source of the autodetected hostname [HostnameSource](enum.HostnameSource.html),
+    /// the primary address is used when the name is unavailable
+    ///
+    /// Default: `System`
+    pub hostname_source: HostnameSource,
+    /// When the autodetected hostname is looked up again
+    /// [HostnameRefresh](enum.HostnameRefresh.html), doesn't apply to the configured one
+    ///
+    /// Default: `OnDemand`, after `refresh_hostname()` is called
+    pub hostname_refresh: HostnameRefresh,
+    /// RFC3164 TAG, RFC5424 APP-NAME
+    ///
+    /// Default: `None`, process name from `process_name_source`
+    pub app_name: Option<String>,
+    /// Source of the process name [ProcessNameSource](enum.ProcessNameSource.html).
+    ///
+    /// Default: `Executable`
+    pub process_name_source: ProcessNameSource,
+    /// RFC5424 PROCID, `[pid]` part of RFC3164 TAG [ProcId](enum.ProcId.html).
+    ///
+    /// Default: `Pid`
+    pub proc_id: ProcId,
+    /// On-disk spool for messages which couldn't be delivered
+    /// [SpoolConfig](struct.SpoolConfig.html).
+    ///
+    /// Default: `None`, undeliverable messages are reported as errors
+    pub spool: Option<SpoolConfig>,
+    /// Rate limiting and duplicate suppression [RateLimitConfig](struct.RateLimitConfig.html).
+    ///
+    /// Default: `None`, all records are sent
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Redaction of sensitive values [RedactionPolicy](struct.RedactionPolicy.html).
+    ///
+    /// Default: `None`, values are sent as they are
+    pub redaction: Option<RedactionPolicy>,
+    /// Key prefix, renames, dropped keys, deduplication and ordering of values
+    /// [FieldTransforms](struct.FieldTransforms.html).
+    ///
+    /// Default: `None`, all values are sent with their keys
+    pub fields: Option<FieldTransforms>,
+    /// RFC5424 SD-ELEMENTs for groups of keys [SdElement](struct.SdElement.html).
+    ///
+    /// Default: empty, record and logger values go to the `record_sd_id` and
+    /// `logger_sd_id` elements
+    pub sd_elements: Vec<SdElement>,
+    /// Registered RFC5424 SD-ELEMENTs added to every message
+    /// [AutoStructuredData](struct.AutoStructuredData.html).
+    ///
+    /// Default: `None`
+    pub auto_sd: Option<AutoStructuredData>,
+    /// Source location of records [SourceLocation](struct.SourceLocation.html).
+    ///
+    /// Default: `None`
+    pub source_location: Option<SourceLocation>,
+    /// SD-ID of the RFC5424 SD-ELEMENT with record values.
+    ///
+    /// Default: `None`, `msg@32473`
+    pub record_sd_id: Option<String>,
+    /// SD-ID of the RFC5424 SD-ELEMENT with logger values.
+    ///
+    /// Default: `None`, `logger@32473`
+    pub logger_sd_id: Option<String>,
 }
 
 /// General syslog config, applies to all connection types
@@ -263,6 +588,15 @@ impl<T> SyslogConfig<T> {
         self
     }
 
+    /// Syslog severity of every message [Severity](enum.Severity.html), for levels
+    /// slog doesn't have, e.g. `LOG_NOTICE` or `LOG_ALERT`
+    ///
+    /// Default: `None`, mapped from the record level
+    pub fn severity<VALUE: Into<Severity>>(mut self, value: VALUE) -> Self {
+        self.severity = Some(value.into());
+        self
+    }
+
     /// Hostname
     ///
     /// Default: `None` will be omitted for unix domain socket drain,
@@ -272,6 +606,342 @@ impl<T> SyslogConfig<T> {
         self
     }
 
+    /// Source of the autodetected hostname [HostnameSource](enum.HostnameSource.html):
+    /// short name, FQDN or primary address
+    ///
+    /// Default: `System`, as returned by `gethostname`
+    pub fn hostname_source<VALUE: Into<HostnameSource>>(mut self, value: VALUE) -> Self {
+        self.hostname_source = value.into();
+        self
+    }
+
+    /// When the autodetected hostname is looked up again
+    /// [HostnameRefresh](enum.HostnameRefresh.html), e.g. every minute after container renames
+    ///
+    /// Default: `OnDemand`, after `refresh_hostname()` is called
+    pub fn hostname_refresh<VALUE: Into<HostnameRefresh>>(mut self, value: VALUE) -> Self {
+        self.hostname_refresh = value.into();
+        self
+    }
+
+    /// RFC3164 TAG, RFC5424 APP-NAME, up to 32 and 48 characters
+    ///
+    /// Default: `None`, process name from `process_name_source`
+    pub fn app_name<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.app_name = Some(value.into());
+        self
+    }
+
+    /// Source of the process name [ProcessNameSource](enum.ProcessNameSource.html).
+    ///
+    /// Default: `Executable`
+    pub fn process_name_source<VALUE: Into<ProcessNameSource>>(mut self, value: VALUE) -> Self {
+        self.process_name_source = value.into();
+        self
+    }
+
+    /// RFC5424 PROCID, `[pid]` part of RFC3164 TAG [ProcId](enum.ProcId.html).
+    ///
+    /// Default: `Pid`
+    pub fn proc_id<VALUE: Into<ProcId>>(mut self, value: VALUE) -> Self {
+        self.proc_id = value.into();
+        self
+    }
+
+    /// On-disk spool for messages which couldn't be delivered
+    /// [SpoolConfig](struct.SpoolConfig.html).
+    ///
+    /// Default: `None`, undeliverable messages are reported as errors
+    pub fn spool<VALUE: Into<SpoolConfig>>(mut self, value: VALUE) -> Self {
+        self.spool = Some(value.into());
+        self
+    }
+
+    /// Rate limiting and duplicate suppression [RateLimitConfig](struct.RateLimitConfig.html).
+    ///
+    /// Default: `None`, all records are sent
+    pub fn rate_limit<VALUE: Into<RateLimitConfig>>(mut self, value: VALUE) -> Self {
+        self.rate_limit = Some(value.into());
+        self
+    }
+
+    /// Redaction of sensitive values [RedactionPolicy](struct.RedactionPolicy.html).
+    ///
+    /// Default: `None`, values are sent as they are
+    pub fn redaction<VALUE: Into<RedactionPolicy>>(mut self, value: VALUE) -> Self {
+        self.redaction = Some(value.into());
+        self
+    }
+
+    /// Key prefix, renames, dropped keys, deduplication and ordering of values
+    /// [FieldTransforms](struct.FieldTransforms.html).
+    ///
+    /// Default: `None`, all values are sent with their keys
+    pub fn fields<VALUE: Into<FieldTransforms>>(mut self, value: VALUE) -> Self {
+        self.fields = Some(value.into());
+        self
+    }
+
+    /// RFC5424 SD-ELEMENT for a group of keys [SdElement](struct.SdElement.html),
+    /// e.g. `SdElement::origin()` for `origin.` keys.
+    ///
+    /// Default: empty, record and logger values go to the `record_sd_id` and
+    /// `logger_sd_id` elements
+    pub fn sd_element<VALUE: Into<SdElement>>(mut self, value: VALUE) -> Self {
+        self.sd_elements.push(value.into());
+        self
+    }
+
+    /// Registered RFC5424 SD-ELEMENTs added to every message
+    /// [AutoStructuredData](struct.AutoStructuredData.html),
+    /// e.g. `AutoStructuredData::all()`.
+    ///
+    /// Default: `None`
+    pub fn auto_sd<VALUE: Into<AutoStructuredData>>(mut self, value: VALUE) -> Self {
+        self.auto_sd = Some(value.into());
+        self
+    }
+
+    /// Source location of records: `source.` keys, or the RFC5424 SD-ELEMENT
+    /// [SourceLocation](struct.SourceLocation.html), e.g. `SourceLocation::new("source@32473")`
+    ///
+    /// Default: `None`
+    pub fn source_location<VALUE: Into<SourceLocation>>(mut self, value: VALUE) -> Self {
+        self.source_location = Some(value.into());
+        self
+    }
+
+    /// SD-ID of the RFC5424 SD-ELEMENT with record values, e.g. `app@<your PEN>`
+    ///
+    /// Default: `msg@32473`
+    pub fn record_sd_id<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.record_sd_id = Some(value.into());
+        self
+    }
+
+    /// SD-ID of the RFC5424 SD-ELEMENT with logger values, e.g. `ctx@<your PEN>`
+    ///
+    /// Default: `logger@32473`
+    pub fn logger_sd_id<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.logger_sd_id = Some(value.into());
+        self
+    }
+
+    // Checks common to all connection types, `sends_hostname` is false
+    // for transports where the local daemon fills the hostname in
+    fn validate_with(&self, sends_hostname: bool) -> SyslogResult<Vec<ConfigWarning>> {
+        let mut errors = Vec::<String>::new();
+        let mut warnings = Vec::<ConfigWarning>::new();
+
+        if let Some(ref hostname) = self.hostname {
+            if let Err(err) = validate_hostname(hostname) {
+                errors.push(err);
+            }
+            if !sends_hostname {
+                warnings.push(ConfigWarning::HostnameIgnored);
+            }
+        }
+
+        if let Some(ref spool) = self.spool {
+            if spool.segment_size == 0 {
+                errors.push("spool segment size is 0".to_string());
+            }
+            if spool.segment_size > spool.max_size {
+                errors.push(format!("spool segment size {} exceeds spool size cap {}",
+                                    spool.segment_size,
+                                    spool.max_size));
+            }
+        }
+
+        for element in &self.sd_elements {
+            if let Err(err) = element.validate() {
+                errors.push(invalid_message(err));
+            }
+        }
+        if let Some(ref auto_sd) = self.auto_sd {
+            if let Err(err) = auto_sd.validate() {
+                errors.push(invalid_message(err));
+            }
+        }
+        if let Some(ref source_location) = self.source_location {
+            if let Err(err) = source_location.validate() {
+                errors.push(invalid_message(err));
+            }
+        }
+        for sd_id in self.record_sd_id.iter().chain(self.logger_sd_id.iter()) {
+            if let Err(err) = SdElement::new(sd_id.as_str()).validate() {
+                errors.push(invalid_message(err));
+            }
+        }
+        let native = self.mode == FormatMode::RFC5424 &&
+                     self.serialization == SerializationFormat::Native;
+        let auto_sd = self.auto_sd.as_ref().map(|a| !a.is_empty()).unwrap_or(false);
+        if (!self.sd_elements.is_empty() && !native) ||
+           (auto_sd && self.mode != FormatMode::RFC5424) {
+            warnings.push(ConfigWarning::StructuredDataIgnored);
+        }
+
+        match self.mode {
+            FormatMode::RFC3164 => {
+                if self.serialization == SerializationFormat::Native {
+                    warnings.push(ConfigWarning::NativeSerializationFallback);
+                }
+                if self.timestamp == TimestampFormat::ISO8601 {
+                    warnings.push(ConfigWarning::IsoTimestampInRfc3164);
+                }
+            }
+            FormatMode::RFC5424 => {
+                if self.timezone == TimestampTZ::Local {
+                    warnings.push(ConfigWarning::LocalTimezoneInRfc5424);
+                }
+            }
+        }
+
+        match self.app_name {
+            Some(ref app_name) => {
+                if let Err(err) = validate_app_name(app_name, &self.mode) {
+                    errors.push(err);
+                }
+            }
+            None => {
+                if let Some(process_name) = get_process_name_from(self.process_name_source) {
+                    if let Err(err) = validate_app_name(&process_name, &self.mode) {
+                        warnings.push(ConfigWarning::InvalidProcessName(err));
+                    }
+                }
+            }
+        }
+
+        if let ProcId::Custom(ref proc_id) = self.proc_id {
+            if let Err(err) = validate_proc_id(proc_id, &self.mode) {
+                errors.push(err);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(warnings)
+        } else {
+            Err(SyslogError::ConfigInvalid(errors.join("; ")))
+        }
+    }
+
+    // Configured hostname, or the local one for network transports
+    fn detected_hostname(&self) -> Option<String> {
+        self.hostname.clone().or_else(|| get_host_name_from(self.hostname_source))
+    }
+
+    // Build drain with the formatter picked by format mode, serialization
+    // and timestamp settings. `short` omits RFC3164 timestamp and hostname
+    // for unix domain socket, where the local daemon fills them in.
+    fn build_drain<B>(&self,
+                      hostname: Option<String>,
+                      short: bool,
+                      builder: &B)
+                      -> SyslogResult<BoxedSyslogDrain>
+        where B: BuildDrain
+    {
+        let process_name = self.app_name
+            .clone()
+            .or_else(|| get_process_name_from(self.process_name_source));
+        let pid = get_pid();
+        let facility = self.facility;
+        let options = self.format_options();
+        // Only the autodetected hostname of network transports is refreshed
+        let hostname_refresh = if short || self.hostname.is_some() {
+            HostnameRefresh::Never
+        } else {
+            self.hostname_refresh
+        };
+
+        let header_fields = || {
+            let fields = HeaderFields::new(hostname.clone(), process_name.clone(), pid, facility)
+                .proc_id(self.proc_id.clone())
+                .hostname_refresh(hostname_refresh)
+                .hostname_source(self.hostname_source);
+            match self.severity {
+                Some(severity) => fields.severity(severity),
+                None => fields,
+            }
+        };
+
+        macro_rules! build {
+            ($format:ty) => (builder.build(|| {
+                <$format>::from_fields(header_fields()).options(options.clone())
+            }))
+        }
+
+        match (&self.mode, &self.serialization, &self.timestamp, &self.timezone) {
+            (_, &SerializationFormat::CEE, _, _) => {
+                Err(SyslogError::ConfigInvalid("CEE serialization is not supported yet"
+                                                   .to_string()))
+            }
+            (&FormatMode::RFC3164, _, _, _) if short => build!(Rfc3164ShortKsv),
+            (&FormatMode::RFC3164, _, &TimestampFormat::RFC3164, &TimestampTZ::Local) => {
+                build!(Rfc3164KsvTs3164Local)
+            }
+            (&FormatMode::RFC3164, _, &TimestampFormat::RFC3164, &TimestampTZ::UTC) => {
+                build!(Rfc3164KsvTs3164Utc)
+            }
+            (&FormatMode::RFC3164, _, &TimestampFormat::ISO8601, &TimestampTZ::Local) => {
+                build!(Rfc3164KsvTsIsoLocal)
+            }
+            (&FormatMode::RFC3164, _, &TimestampFormat::ISO8601, &TimestampTZ::UTC) => {
+                build!(Rfc3164KsvTsIsoUtc)
+            }
+            // RFC5424 always uses RFC3339 timestamps
+            (&FormatMode::RFC5424, &SerializationFormat::KSV, _, &TimestampTZ::Local) => {
+                build!(Rfc5424KsvTsIsoLocal)
+            }
+            (&FormatMode::RFC5424, &SerializationFormat::KSV, _, &TimestampTZ::UTC) => {
+                build!(Rfc5424KsvTsIsoUtc)
+            }
+            (&FormatMode::RFC5424, &SerializationFormat::Native, _, &TimestampTZ::Local) => {
+                build!(Rfc5424NativeTsIsoLocal)
+            }
+            (&FormatMode::RFC5424, &SerializationFormat::Native, _, &TimestampTZ::UTC) => {
+                build!(Rfc5424NativeTsIsoUtc)
+            }
+        }
+    }
+
+    fn format_options(&self) -> FormatOptions {
+        let mut options = FormatOptions::default();
+        if let Some(ref redaction) = self.redaction {
+            options = options.redaction(redaction.clone());
+        }
+        if let Some(ref fields) = self.fields {
+            options = options.fields(fields.clone());
+        }
+        for element in &self.sd_elements {
+            options = options.sd_element(element.clone());
+        }
+        if let Some(ref auto_sd) = self.auto_sd {
+            options = options.auto_sd(auto_sd.clone());
+        }
+        if let Some(ref source_location) = self.source_location {
+            options = options.source_location(source_location.clone());
+        }
+        if let Some(ref record_sd_id) = self.record_sd_id {
+            options = options.record_sd_id(record_sd_id.clone());
+        }
+        if let Some(ref logger_sd_id) = self.logger_sd_id {
+            options = options.logger_sd_id(logger_sd_id.clone());
+        }
+        options
+    }
+
+    // Wrap drain with the spool and rate limiting, if configured
+    fn wrapped(&self, drain: BoxedSyslogDrain) -> SyslogResult<ConfiguredDrain> {
+        let drain: BoxedSyslogDrain = match self.spool {
+            Some(ref spool) => Box::new(SpoolDrain::new(drain, spool.clone())?),
+            None => drain,
+        };
+        Ok(match self.rate_limit {
+               Some(ref rate_limit) => ConfiguredDrain::new(drain).rate_limit(rate_limit.clone()),
+               None => ConfiguredDrain::new(drain),
+           })
+    }
+
     fn connection_config<C>(self, connection_config: C) -> SyslogConfig<C> {
         SyslogConfig {
             connection_config: connection_config,
@@ -281,7 +951,22 @@ impl<T> SyslogConfig<T> {
             timezone: self.timezone,
             serialization: self.serialization,
             facility: self.facility,
+            severity: self.severity,
             hostname: self.hostname,
+            hostname_source: self.hostname_source,
+            hostname_refresh: self.hostname_refresh,
+            app_name: self.app_name,
+            process_name_source: self.process_name_source,
+            proc_id: self.proc_id,
+            spool: self.spool,
+            rate_limit: self.rate_limit,
+            redaction: self.redaction,
+            fields: self.fields,
+            sd_elements: self.sd_elements,
+            auto_sd: self.auto_sd,
+            source_location: self.source_location,
+            record_sd_id: self.record_sd_id,
+            logger_sd_id: self.logger_sd_id,
         }
     }
 }
@@ -296,7 +981,22 @@ impl Default for SyslogConfig<DefaultConfig> {
             timezone: TimestampTZ::default(),
             serialization: SerializationFormat::default(),
             facility: Facility::default(),
+            severity: None,
             hostname: None,
+            hostname_source: HostnameSource::default(),
+            hostname_refresh: HostnameRefresh::OnDemand,
+            app_name: None,
+            process_name_source: ProcessNameSource::default(),
+            proc_id: ProcId::default(),
+            spool: None,
+            rate_limit: None,
+            redaction: None,
+            fields: None,
+            sd_elements: Vec::new(),
+            auto_sd: None,
+            source_location: None,
+            record_sd_id: None,
+            logger_sd_id: None,
         }
     }
 }
@@ -321,16 +1021,37 @@ impl SyslogConfig<DefaultConfig> {
     /// Try to connect without further configuration.
     ///
     /// It will attempt to connect unix domain socket,
-    /// then try to fall back on UDP and then TCP
-    /// By default will use the first working detected socket on the system,
-    /// and in case of UDP and TCP standart ports (514, 6514)
+    /// then fall back on UDP to the standard port (514) on localhost.
+    /// By default will use the first working detected socket on the system.
     ///
     /// Defaults:
     /// RFC3164 message format,
     /// key=value serialiation and a timestamp in RFC3164 format
     /// in a local timezone
-    pub fn connect(self) -> Result<bool, String> {
-        Ok(true)
+    pub fn connect(self) -> SyslogResult<ConfiguredDrain> {
+        self.validate()?;
+        let drain = match UDSBuilder::new(&UDSConfig::default()) {
+            Ok(uds) => self.build_drain(None, true, &uds),
+            Err(err) => Err(err),
+        };
+        let drain = match drain {
+            Ok(drain) => drain,
+            Err(_) => {
+                let udp = UDPBuilder {
+                    servers: resolve_servers::<SocketAddr>(&[], SYSLOG_DEFAULT_PORT)?,
+                    probe_interval: Duration::from_secs(DEFAULT_PROBE_INTERVAL),
+                };
+                self.build_drain(self.detected_hostname(), false, &udp)?
+            }
+        };
+        self.wrapped(drain)
+    }
+
+    /// Check configuration, returning warnings for questionable settings
+    ///
+    /// Fails with `ConfigInvalid` listing all the invalid settings.
+    pub fn validate(&self) -> SyslogResult<Vec<ConfigWarning>> {
+        self.validate_with(true)
     }
 }
 
@@ -343,9 +1064,35 @@ impl SyslogConfig<UDSConfig> {
         self
     }
 
+    /// Socket type [UDSSocketType](enum.UDSSocketType.html).
+    ///
+    /// Default: `Auto`, datagram socket with fallback to stream socket
+    pub fn socket_type<VALUE: Into<UDSSocketType>>(mut self, value: VALUE) -> Self {
+        self.connection_config.socket_type = value.into();
+        self
+    }
+
+    /// Handling of failed sends [UDSRetryConfig](struct.UDSRetryConfig.html).
+    ///
+    /// Default: 3 retries, 10ms apart, blocking socket
+    pub fn retry<VALUE: Into<UDSRetryConfig>>(mut self, value: VALUE) -> Self {
+        self.connection_config.retry = value.into();
+        self
+    }
+
+    /// Check configuration, returning warnings for questionable settings
+    ///
+    /// Fails with `ConfigInvalid` listing all the invalid settings.
+    pub fn validate(&self) -> SyslogResult<Vec<ConfigWarning>> {
+        self.validate_with(false)
+    }
+
     /// Connect unix domain socket drain
-    pub fn connect(self) -> Result<bool, String> {
-        Ok(true)
+    pub fn connect(self) -> SyslogResult<ConfiguredDrain> {
+        self.validate()?;
+        let uds = UDSBuilder::new(&self.connection_config)?;
+        let drain = self.build_drain(None, true, &uds)?;
+        self.wrapped(drain)
     }
 }
 
@@ -357,12 +1104,59 @@ impl SyslogConfig<UDPConfig<SocketAddr>> {
     pub fn server<S>(self, server: S) -> SyslogConfig<UDPConfig<S>>
         where S: ToSocketAddrs
     {
-        self.connection_config(UDPConfig::new(server))
+        self.servers(vec![server])
     }
 
-    /// Connect UDP drain
-    pub fn connect(self) -> Result<bool, String> {
-        Ok(true)
+    /// Syslog server hosts in the order of preference, primary first.
+    ///
+    /// Default: empty, will try to connect to default ports on localhost
+    pub fn servers<S, I>(self, servers: I) -> SyslogConfig<UDPConfig<S>>
+        where S: ToSocketAddrs,
+              I: IntoIterator<Item = S>
+    {
+        let probe_interval = self.connection_config.probe_interval;
+        self.connection_config(UDPConfig::new(servers.into_iter().collect(), probe_interval))
+    }
+
+}
+
+impl<S> SyslogConfig<UDPConfig<S>>
+    where S: ToSocketAddrs
+{
+    /// Check configuration, returning warnings for questionable settings
+    ///
+    /// Fails with `ConfigInvalid` listing all the invalid settings.
+    pub fn validate(&self) -> SyslogResult<Vec<ConfigWarning>> {
+        self.validate_with(true)
+    }
+
+    /// Connect UDP drain, failing over across servers if there are more than one
+    ///
+    /// Servers which don't resolve are skipped, fails only if none of them does.
+    pub fn connect(self) -> SyslogResult<ConfiguredDrain>
+        where S: fmt::Display
+    {
+        self.validate()?;
+        let udp = UDPBuilder {
+            servers: resolve_servers(&self.connection_config.servers, SYSLOG_DEFAULT_PORT)?,
+            probe_interval: self.connection_config.probe_interval,
+        };
+        let drain = self.build_drain(self.detected_hostname(), false, &udp)?;
+        self.wrapped(drain)
+    }
+
+    /// Add failover server, used when the servers before it are unhealthy
+    pub fn failover_server(mut self, server: S) -> Self {
+        self.connection_config.servers.push(server);
+        self
+    }
+
+    /// Interval between attempts to use server marked as unhealthy
+    ///
+    /// Default: 30 seconds
+    pub fn probe_interval(mut self, value: Duration) -> Self {
+        self.connection_config.probe_interval = value;
+        self
     }
 }
 
@@ -374,11 +1168,373 @@ impl SyslogConfig<TCPConfig<SocketAddr>> {
     pub fn server<S>(self, server: S) -> SyslogConfig<TCPConfig<S>>
         where S: ToSocketAddrs
     {
-        self.connection_config(TCPConfig::new(server))
+        self.servers(vec![server])
+    }
+
+    /// Syslog server hosts in the order of preference, primary first.
+    ///
+    /// Default: empty, will try to connect to default ports on localhost
+    pub fn servers<S, I>(self, servers: I) -> SyslogConfig<TCPConfig<S>>
+        where S: ToSocketAddrs,
+              I: IntoIterator<Item = S>
+    {
+        let mut connection_config = TCPConfig::new(servers.into_iter().collect(),
+                                                   self.connection_config.probe_interval);
+        connection_config.framing = self.connection_config.framing;
+        connection_config.tls = self.connection_config.tls.clone();
+        connection_config.lazy_connect = self.connection_config.lazy_connect;
+        connection_config.timeouts = self.connection_config.timeouts;
+        self.connection_config(connection_config)
+    }
+
+}
+
+impl<S> SyslogConfig<TCPConfig<S>>
+    where S: ToSocketAddrs
+{
+    /// Check configuration, returning warnings for questionable settings
+    ///
+    /// Fails with `ConfigInvalid` listing all the invalid settings.
+    pub fn validate(&self) -> SyslogResult<Vec<ConfigWarning>> {
+        self.validate_with(true)
+    }
+
+    /// Message framing [Framing](enum.Framing.html).
+    ///
+    /// Default: octet counting for RFC5424, LF delimited for RFC3164
+    pub fn framing<VALUE: Into<Framing>>(mut self, value: VALUE) -> Self {
+        self.connection_config.framing = Some(value.into());
+        self
+    }
+
+    /// Upgrade connection to TLS [TLSConfig](struct.TLSConfig.html).
+    ///
+    /// Default servers port becomes 6514.
+    pub fn tls<VALUE: Into<TLSConfig>>(mut self, value: VALUE) -> Self {
+        self.connection_config.tls = Some(value.into());
+        self
+    }
+
+    /// Defer connecting until the first message is sent, like `openlog`
+    /// without `LOG_NDELAY`
+    ///
+    /// Default: false
+    pub fn lazy_connect(mut self, value: bool) -> Self {
+        self.connection_config.lazy_connect = value;
+        self
+    }
+
+    /// Timeout of establishing the connection, TLS handshake has its own
+    /// [TLSConfig::handshake_timeout](struct.TLSConfig.html#method.handshake_timeout).
+    ///
+    /// Default: `None`, OS default
+    pub fn connect_timeout(mut self, value: Duration) -> Self {
+        self.connection_config.timeouts.connect = Some(value);
+        self
+    }
+
+    /// Timeout of writing a message
+    ///
+    /// Default: `None`, writes block until the server accepts the message
+    pub fn write_timeout(mut self, value: Duration) -> Self {
+        self.connection_config.timeouts.write = Some(value);
+        self
+    }
+
+    /// Connect TCP or TLS drain, failing over across servers if there are more than one
+    ///
+    /// Drain type is picked by framing: `TCPDrainFramed` (`TLSDrainFramed`)
+    /// for octet counting, `TCPDrainDelimited` (`TLSDrainDelimited`) otherwise.
+    ///
+    /// Servers which don't resolve are skipped, unreachable ones connect lazily and
+    /// start as unhealthy failover destinations. Fails only if none of them connects.
+    pub fn connect(self) -> SyslogResult<ConfiguredDrain>
+        where S: fmt::Display
+    {
+        self.validate()?;
+        let default_port = match self.connection_config.tls {
+            Some(_) => SYSLOG_DEFAULT_TLS_PORT,
+            None => SYSLOG_DEFAULT_PORT,
+        };
+        let tcp = TCPBuilder {
+            servers: resolve_servers(&self.connection_config.servers, default_port)?,
+            probe_interval: self.connection_config.probe_interval,
+            framing: self.connection_config.framing.unwrap_or(Framing::for_mode(&self.mode)),
+            tls: self.connection_config.tls.clone(),
+            lazy_connect: self.connection_config.lazy_connect,
+            timeouts: self.connection_config.timeouts,
+        };
+        let drain = self.build_drain(self.detected_hostname(), false, &tcp)?;
+        self.wrapped(drain)
     }
 
-    /// Connect TCP drain
-    pub fn connect(self) -> Result<bool, String> {
-        Ok(true)
+    /// Add failover server, used when the servers before it are unhealthy
+    pub fn failover_server(mut self, server: S) -> Self {
+        self.connection_config.servers.push(server);
+        self
+    }
+
+    /// Interval between attempts to use server marked as unhealthy
+    ///
+    /// Default: 30 seconds
+    pub fn probe_interval(mut self, value: Duration) -> Self {
+        self.connection_config.probe_interval = value;
+        self
+    }
+}
+
+// Connects drain for a formatter type picked at runtime from the config,
+// `new_formatter` is called for every destination
+trait BuildDrain {
+    fn build<F, N>(&self, new_formatter: N) -> SyslogResult<BoxedSyslogDrain>
+        where F: StreamFormat + Send + Sync + 'static,
+              N: Fn() -> F;
+}
+
+// Resolved server address, with the host part for TLS server name
+struct Server {
+    addr: SocketAddr,
+    host: String,
+}
+
+// Host part of `host:port`, `[ipv6]:port`
+fn host_part(server: &str) -> &str {
+    if server.starts_with('[') {
+        return match server.find(']') {
+                   Some(index) => &server[1..index],
+                   None => server,
+               };
+    }
+    match server.rfind(':') {
+        // Bare IPv6 address has more than one colon
+        Some(index) if server[..index].find(':').is_none() => &server[..index],
+        _ => server,
+    }
+}
+
+// Resolve servers, using localhost if none are configured
+fn resolve_servers<S>(servers: &[S], default_port: u16) -> SyslogResult<Vec<Server>>
+    where S: ToSocketAddrs + fmt::Display
+{
+    if servers.is_empty() {
+        return Ok(vec![Server {
+                           addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                                                 default_port),
+                           host: "localhost".to_string(),
+                       }]);
+    }
+
+    // Servers which don't resolve are skipped, unless none of them does
+    let mut resolved = Vec::<Server>::with_capacity(servers.len());
+    let mut first_error = None;
+    for server in servers {
+        let destination = server.to_string();
+        let resolve_failed = |cause: io::Error| {
+            SyslogError::ResolveFailed {
+                destination: destination.clone(),
+                cause: cause,
+            }
+        };
+        let addr = server.to_socket_addrs()
+            .map_err(&resolve_failed)
+            .and_then(|mut addrs| {
+                addrs.next()
+                    .ok_or_else(|| {
+                                    resolve_failed(io::Error::new(io::ErrorKind::NotFound,
+                                                                  "no address"))
+                                })
+            });
+        match addr {
+            Ok(addr) => {
+                resolved.push(Server {
+                                  addr: addr,
+                                  host: host_part(&destination).to_string(),
+                              })
+            }
+            Err(err) => {
+                first_error = first_error.or(Some(err));
+            }
+        }
+    }
+    match first_error {
+        Some(err) if resolved.is_empty() => Err(err),
+        _ => Ok(resolved),
+    }
+}
+
+// Single drain, or failover across multiple drains
+//
+// `connect` is called with `lazy` set for servers which couldn't connect,
+// they become unhealthy destinations, probed later, or are skipped
+// if that fails too. Fails only if none of the servers connects.
+fn connect_servers<C>(servers: &[Server],
+                      probe_interval: Duration,
+                      mut connect: C)
+                      -> SyslogResult<BoxedSyslogDrain>
+    where C: FnMut(&Server, bool) -> SyslogResult<BoxedSyslogDrain>
+{
+    let mut drains = Vec::<BoxedSyslogDrain>::with_capacity(servers.len());
+    let mut unhealthy = Vec::new();
+    let mut first_error = None;
+    for server in servers {
+        match connect(server, false) {
+            Ok(drain) => drains.push(drain),
+            Err(err) => {
+                if servers.len() > 1 {
+                    if let Ok(drain) = connect(server, true) {
+                        unhealthy.push(drains.len());
+                        drains.push(drain);
+                    }
+                }
+                first_error = first_error.or(Some(err));
+            }
+        }
+    }
+
+    if unhealthy.len() == drains.len() {
+        return Err(first_error.unwrap_or(SyslogError::NoDestination));
+    }
+    if drains.len() == 1 {
+        return Ok(drains.remove(0));
+    }
+    let failover = unhealthy.into_iter()
+        .fold(FailoverDrain::new(drains).probe_interval(probe_interval),
+              |failover, index| failover.unhealthy(index));
+    Ok(Box::new(failover))
+}
+
+struct UDSBuilder {
+    socket: PathBuf,
+    socket_type: UDSSocketType,
+    retry: UDSRetryConfig,
+}
+
+impl UDSBuilder {
+    fn new(config: &UDSConfig) -> SyslogResult<UDSBuilder> {
+        let socket = match config.socket {
+            Some(ref socket) => socket.clone(),
+            None => locate_default_uds_socket()?,
+        };
+        Ok(UDSBuilder {
+               socket: socket,
+               socket_type: config.socket_type,
+               retry: config.retry.clone(),
+           })
+    }
+}
+
+impl BuildDrain for UDSBuilder {
+    fn build<F, N>(&self, new_formatter: N) -> SyslogResult<BoxedSyslogDrain>
+        where F: StreamFormat + Send + Sync + 'static,
+              N: Fn() -> F
+    {
+        let drain = UDSDrain::new(self.socket.clone(), new_formatter())
+            .socket_type(self.socket_type)
+            .retry(self.retry.clone())
+            .connect()?;
+        Ok(Box::new(drain))
+    }
+}
+
+struct UDPBuilder {
+    servers: Vec<Server>,
+    probe_interval: Duration,
+}
+
+impl BuildDrain for UDPBuilder {
+    fn build<F, N>(&self, new_formatter: N) -> SyslogResult<BoxedSyslogDrain>
+        where F: StreamFormat + Send + Sync + 'static,
+              N: Fn() -> F
+    {
+        // UDP socket isn't connected, there is nothing to defer
+        connect_servers(&self.servers, self.probe_interval, |server, _| {
+            let drain = UDPDrain::new(server.addr, new_formatter()).connect()?;
+            Ok(Box::new(drain) as BoxedSyslogDrain)
+        })
+    }
+}
+
+struct TCPBuilder {
+    servers: Vec<Server>,
+    probe_interval: Duration,
+    framing: Framing,
+    tls: Option<TLSConfig>,
+    lazy_connect: bool,
+    timeouts: StreamTimeouts,
+}
+
+impl TCPBuilder {
+    fn configure_tcp<T, F>(&self,
+                           drain: TCPDrain<T, TCPDisconnected, F>,
+                           lazy: bool)
+                           -> TCPDrain<T, TCPDisconnected, F>
+        where F: StreamFormat
+    {
+        let mut drain = drain.lazy_connect(self.lazy_connect || lazy);
+        if let Some(timeout) = self.timeouts.connect {
+            drain = drain.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.timeouts.write {
+            drain = drain.write_timeout(timeout);
+        }
+        drain
+    }
+
+    fn configure_tls<T, F>(&self,
+                           drain: TLSDrain<T, TLSDisconnected, F>,
+                           lazy: bool)
+                           -> TLSDrain<T, TLSDisconnected, F>
+        where F: StreamFormat
+    {
+        let mut drain = drain.lazy_connect(self.lazy_connect || lazy);
+        if let Some(timeout) = self.timeouts.connect {
+            drain = drain.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.timeouts.write {
+            drain = drain.write_timeout(timeout);
+        }
+        drain
+    }
+}
+
+impl BuildDrain for TCPBuilder {
+    fn build<F, N>(&self, new_formatter: N) -> SyslogResult<BoxedSyslogDrain>
+        where F: StreamFormat + Send + Sync + 'static,
+              N: Fn() -> F
+    {
+        connect_servers(&self.servers, self.probe_interval, |server, lazy| {
+            let addr = server.addr;
+            let drain: BoxedSyslogDrain = match (&self.tls, self.framing) {
+                (&None, Framing::Octet) => {
+                    let drain = TCPDrainFramed::new(addr, new_formatter());
+                    Box::new(self.configure_tcp(drain, lazy).connect()?)
+                }
+                (&None, Framing::NonTransparent(delimiter)) => {
+                    let drain = TCPDrainDelimited::new(addr, new_formatter()).delimiter(delimiter);
+                    Box::new(self.configure_tcp(drain, lazy).connect()?)
+                }
+                (&Some(ref tls), framing) => {
+                    // Verify the certificate against the configured server host by default
+                    let mut tls = tls.clone();
+                    if tls.server_name.is_none() {
+                        tls.server_name = Some(server.host.clone());
+                    }
+                    match framing {
+                        Framing::Octet => {
+                            let drain = TLSDrainFramed::new(addr, new_formatter())
+                                .tls_config(tls);
+                            Box::new(self.configure_tls(drain, lazy).connect()?)
+                        }
+                        Framing::NonTransparent(delimiter) => {
+                            let drain = TLSDrainDelimited::new(addr, new_formatter())
+                                .delimiter(delimiter)
+                                .tls_config(tls);
+                            Box::new(self.configure_tls(drain, lazy).connect()?)
+                        }
+                    }
+                }
+            };
+            Ok(drain)
+        })
     }
 }
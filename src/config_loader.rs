@@ -0,0 +1,476 @@
+use config::{DefaultConfig, FormatMode, SerializationFormat, SyslogConfig, TCPConfig,
+             TimestampFormat, TimestampTZ, UDPConfig, UDSConfig, ConfigWarning};
+use drain::ConfiguredDrain;
+use error::{SyslogError, SyslogResult, Transport};
+use posix::HostnameSource;
+use serde::de::{self, Deserialize, Deserializer, MapVisitor, SeqVisitor, Visitor};
+use rate_limit::{RateLimit, RateLimitConfig};
+use spool::SpoolConfig;
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use syslog::{Facility, SYSLOG_DEFAULT_PORT, SYSLOG_DEFAULT_TLS_PORT};
+use tcp_drain::Framing;
+use tls_drain::TLSConfig;
+use uds_drain::UDSSocketType;
+
+/// Prefix of the environment variables read by `SyslogSettings::from_env`
+pub const SYSLOG_ENV_PREFIX: &'static str = "SYSLOG_";
+
+/// Syslog configuration in a connection agnostic form
+///
+/// Read from a config file section (via serde), `SYSLOG_*` environment
+/// variables, or a URL like `tcp://logs.internal:514?mode=rfc5424&facility=local3&tz=utc`,
+/// then turned into the typed builder with `into_config`.
+///
+/// Keys are the same for all the sources: `transport`, `server` (or `servers`),
+/// `socket`, `socket_type`, `mode`, `timestamp`, `tz` (or `timezone`),
+/// `serialization`, `facility`, `hostname`, `hostname_source` (`system`, `short`, `fqdn`,
+/// `ip`), `async`, `spool`, `probe_interval` (seconds),
+/// `rate_limit` (messages per second of each severity), `suppress_duplicates` (seconds),
+/// `framing` (`octet`, `lf`, `nul`), `lazy_connect`, `connect_timeout`, `write_timeout`
+/// (seconds), `ca_file`, `server_name` and `handshake_timeout` (seconds) for TLS.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SyslogSettings {
+    /// Transport: `uds` (`unix`), `udp`, `tcp` or `tls` (`tcp+tls`)
+    ///
+    /// Default: `None`, detect like `syslog().connect()`
+    pub transport: Option<Transport>,
+    /// Syslog server hosts, default port is added if missing
+    pub servers: Vec<String>,
+    /// Path to syslog socket
+    pub socket: Option<PathBuf>,
+    /// Unix domain socket type
+    pub socket_type: UDSSocketType,
+    /// Formatting mode
+    pub mode: FormatMode,
+    /// Timestamp format
+    pub timestamp: TimestampFormat,
+    /// Timezone format
+    pub timezone: TimestampTZ,
+    /// Serialization format
+    pub serialization: SerializationFormat,
+    /// Syslog facility
+    pub facility: Facility,
+    /// Hostname
+    pub hostname: Option<String>,
+    /// Source of the autodetected hostname
+    pub hostname_source: HostnameSource,
+    /// Whether streamer should be synchronous or asynchronous
+    pub async: bool,
+    /// Spool directory
+    pub spool: Option<PathBuf>,
+    /// Messages per second of each severity from `LOG_ERR` to `LOG_DEBUG`, every
+    /// one has its own limit, `LOG_CRIT` and more severe are not limited
+    pub rate_limit: Option<u32>,
+    /// Interval of "last message repeated N times" reports, for collapsed duplicates
+    pub suppress_duplicates: Option<Duration>,
+    /// Interval between attempts to use server marked as unhealthy
+    pub probe_interval: Option<Duration>,
+    /// TCP and TLS message framing
+    pub framing: Option<Framing>,
+    /// Defer connecting until the first message is sent
+    pub lazy_connect: bool,
+    /// TCP and TLS connect timeout
+    pub connect_timeout: Option<Duration>,
+    /// TCP and TLS write timeout
+    pub write_timeout: Option<Duration>,
+    /// TLS CA certificates file
+    pub ca_file: Option<String>,
+    /// TLS server name to verify the certificate against
+    pub server_name: Option<String>,
+    /// TLS handshake timeout
+    pub handshake_timeout: Option<Duration>,
+}
+
+fn invalid<T: fmt::Display>(message: T) -> SyslogError {
+    SyslogError::ConfigInvalid(message.to_string())
+}
+
+fn parse_transport(value: &str) -> SyslogResult<Transport> {
+    let result = match &value.to_lowercase()[..] {
+        "uds" | "unix" => Transport::UDS,
+        "udp" => Transport::UDP,
+        "tcp" => Transport::TCP,
+        "tls" | "tcp+tls" => Transport::TLS,
+        _ => return Err(invalid(format!("Unknown transport: {}", value))),
+    };
+    Ok(result)
+}
+
+fn parse_bool(key: &str, value: &str) -> SyslogResult<bool> {
+    match &value.to_lowercase()[..] {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(invalid(format!("Invalid boolean for {}: {}", key, value))),
+    }
+}
+
+fn parse_seconds(key: &str, value: &str) -> SyslogResult<Duration> {
+    value.parse::<u64>()
+        .map(Duration::from_secs)
+        .map_err(|_| invalid(format!("Invalid number of seconds for {}: {}", key, value)))
+}
+
+// Append default port of the transport, unless the server has one,
+// bare IPv6 addresses are put in brackets
+fn with_default_port(server: &str, transport: Transport) -> String {
+    let port = match transport {
+        Transport::TLS => SYSLOG_DEFAULT_TLS_PORT,
+        _ => SYSLOG_DEFAULT_PORT,
+    };
+    if server.starts_with('[') {
+        if server.contains("]:") {
+            return server.to_string();
+        }
+        return format!("{}:{}", server, port);
+    }
+    match server.matches(':').count() {
+        0 => format!("{}:{}", server, port),
+        1 => server.to_string(),
+        _ => format!("[{}]:{}", server, port),
+    }
+}
+
+impl SyslogSettings {
+    /// Set a single setting by its key
+    pub fn set(&mut self, key: &str, value: &str) -> SyslogResult<()> {
+        if !self.set_known(key, value)? {
+            return Err(invalid(format!("Unknown setting: {}", key)));
+        }
+        Ok(())
+    }
+
+    // Set a setting, returning false if there is no such key
+    fn set_known(&mut self, key: &str, value: &str) -> SyslogResult<bool> {
+        match &key.to_lowercase()[..] {
+            "transport" => self.transport = Some(parse_transport(value)?),
+            "server" | "servers" => {
+                self.servers = value.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect()
+            }
+            "socket" => self.socket = Some(PathBuf::from(value)),
+            "socket_type" => self.socket_type = value.parse()?,
+            "mode" => self.mode = value.parse()?,
+            "timestamp" => self.timestamp = value.parse()?,
+            "tz" | "timezone" => self.timezone = value.parse()?,
+            "serialization" => self.serialization = value.parse()?,
+            "facility" => self.facility = value.parse()?,
+            "hostname" => self.hostname = Some(value.to_string()),
+            "hostname_source" => self.hostname_source = value.parse()?,
+            "async" => self.async = parse_bool(key, value)?,
+            "spool" => self.spool = Some(PathBuf::from(value)),
+            "rate_limit" => {
+                let rate = value.parse::<u32>()
+                    .map_err(|_| invalid(format!("Invalid rate limit: {}", value)))?;
+                self.rate_limit = Some(rate);
+            }
+            "suppress_duplicates" => {
+                self.suppress_duplicates = Some(parse_seconds(key, value)?)
+            }
+            "probe_interval" => self.probe_interval = Some(parse_seconds(key, value)?),
+            "framing" => self.framing = Some(value.parse()?),
+            "lazy_connect" => self.lazy_connect = parse_bool(key, value)?,
+            "connect_timeout" => self.connect_timeout = Some(parse_seconds(key, value)?),
+            "write_timeout" => self.write_timeout = Some(parse_seconds(key, value)?),
+            "handshake_timeout" => self.handshake_timeout = Some(parse_seconds(key, value)?),
+            "ca_file" => self.ca_file = Some(value.to_string()),
+            "server_name" => self.server_name = Some(value.to_string()),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Settings from `SYSLOG_*` environment variables
+    ///
+    /// `SYSLOG_URL` is parsed first, the rest of the variables
+    /// (`SYSLOG_MODE`, `SYSLOG_FACILITY`, ...) override it.
+    pub fn from_env() -> SyslogResult<SyslogSettings> {
+        SyslogSettings::from_vars(env::vars())
+    }
+
+    /// Settings from `SYSLOG_*` variables, other variables are ignored
+    ///
+    /// So are `SYSLOG_*` variables which aren't settings, like `SYSLOG_NG_CONF`
+    /// of other software, while invalid values of the known ones are errors.
+    pub fn from_vars<I>(vars: I) -> SyslogResult<SyslogSettings>
+        where I: IntoIterator<Item = (String, String)>
+    {
+        let vars = vars.into_iter()
+            .filter(|&(ref name, _)| name.starts_with(SYSLOG_ENV_PREFIX))
+            .map(|(name, value)| (name[SYSLOG_ENV_PREFIX.len()..].to_lowercase(), value))
+            .collect::<Vec<_>>();
+
+        let mut settings = match vars.iter().find(|&&(ref key, _)| key == "url") {
+            Some(&(_, ref url)) => url.parse()?,
+            None => SyslogSettings::default(),
+        };
+
+        for &(ref key, ref value) in vars.iter().filter(|&&(ref key, _)| key != "url") {
+            settings.set_known(key, value)?;
+        }
+        Ok(settings)
+    }
+
+    /// Typed builder for the configured transport
+    pub fn into_config(self) -> SyslogResult<AnySyslogConfig> {
+        let transport = self.transport;
+        if transport.is_none() && !self.servers.is_empty() {
+            return Err(invalid("Syslog servers require transport to be set"));
+        }
+
+        let mut config = SyslogConfig::<DefaultConfig>::new()
+            .mode(self.mode)
+            .timestamp(self.timestamp)
+            .timezone(self.timezone)
+            .serialization(self.serialization)
+            .facility(self.facility)
+            .hostname_source(self.hostname_source)
+            .async(self.async);
+        if let Some(hostname) = self.hostname {
+            config = config.hostname(hostname);
+        }
+        if let Some(spool) = self.spool {
+            config = config.spool(SpoolConfig::new(spool));
+        }
+        if self.rate_limit.is_some() || self.suppress_duplicates.is_some() {
+            let mut rate_limit = RateLimitConfig::new();
+            if let Some(rate) = self.rate_limit {
+                rate_limit = rate_limit.limit(RateLimit::new(rate, Duration::from_secs(1)));
+            }
+            if let Some(interval) = self.suppress_duplicates {
+                rate_limit = rate_limit.suppress_duplicates(interval);
+            }
+            config = config.rate_limit(rate_limit);
+        }
+
+        let servers = match transport {
+            Some(transport) => {
+                self.servers
+                    .iter()
+                    .map(|server| with_default_port(server, transport))
+                    .collect::<Vec<_>>()
+            }
+            None => Vec::new(),
+        };
+
+        let result = match transport {
+            None => AnySyslogConfig::Default(config),
+            Some(Transport::UDS) => {
+                let mut config = config.uds().socket_type(self.socket_type);
+                if let Some(socket) = self.socket {
+                    config = config.socket(socket);
+                }
+                AnySyslogConfig::UDS(config)
+            }
+            Some(Transport::UDP) => {
+                let mut config = config.udp().servers(servers);
+                if let Some(probe_interval) = self.probe_interval {
+                    config = config.probe_interval(probe_interval);
+                }
+                AnySyslogConfig::UDP(config)
+            }
+            Some(Transport::TCP) |
+            Some(Transport::TLS) => {
+                let mut config = config.tcp().servers(servers);
+                if let Some(probe_interval) = self.probe_interval {
+                    config = config.probe_interval(probe_interval);
+                }
+                if let Some(framing) = self.framing {
+                    config = config.framing(framing);
+                }
+                config = config.lazy_connect(self.lazy_connect);
+                if let Some(timeout) = self.connect_timeout {
+                    config = config.connect_timeout(timeout);
+                }
+                if let Some(timeout) = self.write_timeout {
+                    config = config.write_timeout(timeout);
+                }
+                if transport == Some(Transport::TLS) {
+                    let mut tls = TLSConfig::new();
+                    tls.ca_file = self.ca_file;
+                    tls.server_name = self.server_name;
+                    tls.handshake_timeout = self.handshake_timeout;
+                    config = config.tls(tls);
+                }
+                AnySyslogConfig::TCP(config)
+            }
+            Some(other) => return Err(invalid(format!("Unsupported transport: {}", other))),
+        };
+        Ok(result)
+    }
+}
+
+/// Parse syslog URL
+///
+/// `scheme://location?key=value&...`, where scheme is a transport
+/// and location is either a comma separated list of servers,
+/// or a socket path for `unix`: `unix:///dev/log`, `unix://@syslog`.
+/// Values are taken as is, without percent-decoding.
+impl FromStr for SyslogSettings {
+    type Err = SyslogError;
+    fn from_str(url: &str) -> Result<SyslogSettings, SyslogError> {
+        let (scheme, rest) = match url.find("://") {
+            Some(index) => (&url[..index], &url[index + 3..]),
+            None => return Err(invalid(format!("Invalid syslog URL: {}", url))),
+        };
+        let (location, query) = match rest.find('?') {
+            Some(index) => (&rest[..index], &rest[index + 1..]),
+            None => (rest, ""),
+        };
+
+        let mut settings = SyslogSettings::default();
+        settings.set("transport", scheme)?;
+        if settings.transport == Some(Transport::UDS) {
+            if !location.is_empty() {
+                settings.set("socket", location)?;
+            }
+        } else {
+            settings.set("servers", location.trim_right_matches('/'))?;
+        }
+
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let mut parts = pair.splitn(2, '=');
+            let key = parts.next().unwrap_or("");
+            let value = parts.next()
+                .ok_or_else(|| invalid(format!("Missing value for {} in {}", key, url)))?;
+            settings.set(key, value)?;
+        }
+        Ok(settings)
+    }
+}
+
+// Setting value in a config file: scalar or a list of servers
+enum SettingValue {
+    Single(String),
+    List(Vec<String>),
+}
+
+struct SettingValueVisitor;
+
+impl Visitor for SettingValueVisitor {
+    type Value = SettingValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a string, number, boolean or a list of strings")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<SettingValue, E> {
+        Ok(SettingValue::Single(value.to_string()))
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<SettingValue, E> {
+        Ok(SettingValue::Single(value.to_string()))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<SettingValue, E> {
+        Ok(SettingValue::Single(value.to_string()))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<SettingValue, E> {
+        Ok(SettingValue::Single(value.to_string()))
+    }
+
+    fn visit_seq<V: SeqVisitor>(self, mut visitor: V) -> Result<SettingValue, V::Error> {
+        let mut values = Vec::<String>::new();
+        while let Some(value) = visitor.visit::<String>()? {
+            values.push(value);
+        }
+        Ok(SettingValue::List(values))
+    }
+}
+
+impl Deserialize for SettingValue {
+    fn deserialize<D: Deserializer>(deserializer: D) -> Result<SettingValue, D::Error> {
+        deserializer.deserialize(SettingValueVisitor)
+    }
+}
+
+struct SyslogSettingsVisitor;
+
+impl Visitor for SyslogSettingsVisitor {
+    type Value = SyslogSettings;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of syslog settings")
+    }
+
+    fn visit_map<V: MapVisitor>(self, mut visitor: V) -> Result<SyslogSettings, V::Error> {
+        let mut settings = SyslogSettings::default();
+        while let Some((key, value)) = visitor.visit::<String, SettingValue>()? {
+            let value = match value {
+                SettingValue::Single(value) => value,
+                SettingValue::List(values) => values.join(","),
+            };
+            settings.set(&key, &value).map_err(|err| de::Error::custom(err.to_string()))?;
+        }
+        Ok(settings)
+    }
+}
+
+/// Deserialize from a map with the same keys as the URL query
+/// (`transport`, `servers`, `mode`, ...), `servers` may be a list
+impl Deserialize for SyslogSettings {
+    fn deserialize<D: Deserializer>(deserializer: D) -> Result<SyslogSettings, D::Error> {
+        deserializer.deserialize_map(SyslogSettingsVisitor)
+    }
+}
+
+/// Syslog config for any of the transports, built from
+/// [SyslogSettings](struct.SyslogSettings.html)
+#[derive(Debug)]
+pub enum AnySyslogConfig {
+    /// Transport detected on connect
+    Default(SyslogConfig<DefaultConfig>),
+    /// Unix domain socket
+    UDS(SyslogConfig<UDSConfig>),
+    /// UDP
+    UDP(SyslogConfig<UDPConfig<String>>),
+    /// TCP or TLS
+    TCP(SyslogConfig<TCPConfig<String>>),
+}
+
+impl AnySyslogConfig {
+    /// Check configuration, returning warnings for questionable settings
+    pub fn validate(&self) -> SyslogResult<Vec<ConfigWarning>> {
+        match *self {
+            AnySyslogConfig::Default(ref config) => config.validate(),
+            AnySyslogConfig::UDS(ref config) => config.validate(),
+            AnySyslogConfig::UDP(ref config) => config.validate(),
+            AnySyslogConfig::TCP(ref config) => config.validate(),
+        }
+    }
+
+    /// Connect drain for the configured transport
+    pub fn connect(self) -> SyslogResult<ConfiguredDrain> {
+        match self {
+            AnySyslogConfig::Default(config) => config.connect(),
+            AnySyslogConfig::UDS(config) => config.connect(),
+            AnySyslogConfig::UDP(config) => config.connect(),
+            AnySyslogConfig::TCP(config) => config.connect(),
+        }
+    }
+}
+
+/// Deserialize from a map of settings straight into the typed builder,
+/// e.g. a `[syslog]` section of a config file
+impl Deserialize for AnySyslogConfig {
+    fn deserialize<D: Deserializer>(deserializer: D) -> Result<AnySyslogConfig, D::Error> {
+        SyslogSettings::deserialize(deserializer)?
+            .into_config()
+            .map_err(|err| de::Error::custom(err.to_string()))
+    }
+}
+
+/// Parse syslog URL straight into the typed builder
+impl FromStr for AnySyslogConfig {
+    type Err = SyslogError;
+    fn from_str(url: &str) -> Result<AnySyslogConfig, SyslogError> {
+        url.parse::<SyslogSettings>()?.into_config()
+    }
+}
@@ -0,0 +1,160 @@
+use error::{SyslogError, SyslogResult};
+use metrics::DrainStats;
+use rate_limit::{RateLimitConfig, RateLimiter};
+use slog::{Drain, OwnedKeyValueList, Record};
+use std::fmt;
+use std::sync::Arc;
+
+/// Connected syslog drain, able to send already formatted messages
+///
+/// Formatting and sending are split, so wrapping drains
+/// (spooling, failover, ...) can format a record once, and then
+/// store, resend or replay the resulting syslog message.
+pub trait SyslogDrain {
+    /// Format record into a complete syslog message
+    fn format(&self, record: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>>;
+
+    /// Send formatted syslog message
+    fn send(&self, message: &[u8]) -> SyslogResult<()>;
+
+    /// Re-establish underlying connection
+    ///
+    /// Connectionless transports have nothing to do here.
+    fn reconnect(&self) -> SyslogResult<()> {
+        Ok(())
+    }
+
+    /// Delivery metrics snapshot, `None` if the drain doesn't keep any
+    fn stats(&self) -> Option<DrainStats> {
+        None
+    }
+}
+
+/// Boxed connected drain, for combining different drain types
+pub type BoxedSyslogDrain = Box<SyslogDrain + Send + Sync>;
+
+impl<D> SyslogDrain for Box<D>
+    where D: SyslogDrain + ?Sized
+{
+    fn format(&self, record: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        (**self).format(record, logger_values)
+    }
+
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        (**self).send(message)
+    }
+
+    fn reconnect(&self) -> SyslogResult<()> {
+        (**self).reconnect()
+    }
+
+    fn stats(&self) -> Option<DrainStats> {
+        (**self).stats()
+    }
+}
+
+impl<D> SyslogDrain for Arc<D>
+    where D: SyslogDrain + ?Sized
+{
+    fn format(&self, record: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        (**self).format(record, logger_values)
+    }
+
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        (**self).send(message)
+    }
+
+    fn reconnect(&self) -> SyslogResult<()> {
+        (**self).reconnect()
+    }
+
+    fn stats(&self) -> Option<DrainStats> {
+        (**self).stats()
+    }
+}
+
+/// slog drain over a boxed syslog drain
+///
+/// Returned by `SyslogConfig::connect`, where the actual drain type
+/// depends on the configuration. With duplicate suppression, pending repeat
+/// summary is sent once the interval elapses, `flush` sends it right away,
+/// otherwise it's lost when the drain is dropped.
+pub struct ConfiguredDrain {
+    drain: Arc<BoxedSyslogDrain>,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl ConfiguredDrain {
+    /// Wrap connected syslog drain
+    pub fn new(drain: BoxedSyslogDrain) -> ConfiguredDrain {
+        ConfiguredDrain {
+            drain: Arc::new(drain),
+            limiter: None,
+        }
+    }
+
+    /// Rate limit records [RateLimitConfig](struct.RateLimitConfig.html)
+    pub fn rate_limit(mut self, config: RateLimitConfig) -> Self {
+        self.limiter = Some(RateLimiter::with_timer(config, &self.drain));
+        self
+    }
+
+    /// Rate limiter, if records are rate limited
+    pub fn limiter(&self) -> Option<&RateLimiter> {
+        self.limiter.as_ref().map(|limiter| &**limiter)
+    }
+
+    /// Send pending repeat summary, if any
+    pub fn flush(&self) -> SyslogResult<()> {
+        match self.limiter {
+            Some(ref limiter) => limiter.flush(&self.drain),
+            None => Ok(()),
+        }
+    }
+
+    /// Underlying syslog drain, pending repeat summary is sent first
+    pub fn into_inner(self) -> BoxedSyslogDrain {
+        let _ = self.flush();
+        match Arc::try_unwrap(self.drain) {
+            Ok(drain) => drain,
+            // Repeat timer is sending through the drain right now
+            Err(drain) => Box::new(drain),
+        }
+    }
+}
+
+impl fmt::Debug for ConfiguredDrain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ConfiguredDrain")
+    }
+}
+
+impl SyslogDrain for ConfiguredDrain {
+    fn format(&self, record: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        self.drain.format(record, logger_values)
+    }
+
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        self.drain.send(message)
+    }
+
+    fn reconnect(&self) -> SyslogResult<()> {
+        self.drain.reconnect()
+    }
+
+    fn stats(&self) -> Option<DrainStats> {
+        self.drain.stats()
+    }
+}
+
+impl Drain for ConfiguredDrain {
+    type Error = SyslogError;
+
+    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<()> {
+        if let Some(ref limiter) = self.limiter {
+            return limiter.log(&self.drain, info, logger_values);
+        }
+        let buf = self.drain.format(info, logger_values)?;
+        self.drain.send(buf.as_slice())
+    }
+}
@@ -0,0 +1,261 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::result;
+
+/// Transport, the error has occurred on
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Transport {
+    /// Unix domain socket
+    UDS,
+    /// UDP socket
+    UDP,
+    /// TCP stream
+    TCP,
+    /// TLS stream
+    TLS,
+    /// Platform `syslog(3)`
+    Libc,
+    /// On-disk spool
+    Spool,
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Transport::UDS => "uds",
+            Transport::UDP => "udp",
+            Transport::TCP => "tcp",
+            Transport::TLS => "tls",
+            Transport::Libc => "libc",
+            Transport::Spool => "spool",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Syslog error
+#[derive(Debug)]
+pub enum SyslogError {
+    /// Invalid configuration or configuration value
+    ConfigInvalid(String),
+    /// None of the syslog sockets could be found
+    SocketNotFound(Vec<PathBuf>),
+    /// Couldn't resolve address or name
+    ResolveFailed {
+        /// Name being resolved
+        destination: String,
+        /// Underlying error
+        cause: io::Error,
+    },
+    /// Couldn't connect to the destination
+    ConnectFailed {
+        /// Transport
+        transport: Transport,
+        /// Syslog server address or socket path
+        destination: String,
+        /// Underlying error
+        cause: io::Error,
+    },
+    /// TLS configuration or session error
+    Tls {
+        /// Syslog server address, if connected
+        destination: String,
+        /// Error description
+        message: String,
+    },
+    /// Couldn't format record
+    FormatFailed(io::Error),
+    /// Couldn't send message to the destination
+    SendFailed {
+        /// Transport
+        transport: Transport,
+        /// Syslog server address or socket path
+        destination: String,
+        /// Underlying error
+        cause: io::Error,
+    },
+    /// Message exceeds the size limit of the transport
+    MessageTooLarge {
+        /// Transport
+        transport: Transport,
+        /// Syslog server address or socket path
+        destination: String,
+        /// Message size
+        size: usize,
+        /// Size limit, if known
+        limit: Option<usize>,
+    },
+    /// Lock guarding the connection was poisoned by a panicking thread
+    PoisonedLock {
+        /// Transport
+        transport: Transport,
+        /// Syslog server address or socket path
+        destination: String,
+    },
+    /// No destination is available to send the message to
+    NoDestination,
+    /// Received message isn't valid RFC3164 or RFC5424
+    ParseFailed(String),
+}
+
+/// Syslog result
+pub type SyslogResult<T> = result::Result<T, SyslogError>;
+
+impl SyslogError {
+    /// Connect error
+    pub fn connect<D: fmt::Display>(transport: Transport,
+                                    destination: D,
+                                    cause: io::Error)
+                                    -> Self {
+        SyslogError::ConnectFailed {
+            transport: transport,
+            destination: destination.to_string(),
+            cause: cause,
+        }
+    }
+
+    /// Send error
+    pub fn send<D: fmt::Display>(transport: Transport, destination: D, cause: io::Error) -> Self {
+        SyslogError::SendFailed {
+            transport: transport,
+            destination: destination.to_string(),
+            cause: cause,
+        }
+    }
+
+    /// Message too large error
+    pub fn too_large<D: fmt::Display>(transport: Transport,
+                                      destination: D,
+                                      size: usize,
+                                      limit: Option<usize>)
+                                      -> Self {
+        SyslogError::MessageTooLarge {
+            transport: transport,
+            destination: destination.to_string(),
+            size: size,
+            limit: limit,
+        }
+    }
+
+    /// Poisoned lock error
+    pub fn poisoned<D: fmt::Display>(transport: Transport, destination: D) -> Self {
+        SyslogError::PoisonedLock {
+            transport: transport,
+            destination: destination.to_string(),
+        }
+    }
+
+    /// Transport, the error has occurred on
+    pub fn transport(&self) -> Option<Transport> {
+        match *self {
+            SyslogError::ConnectFailed { transport, .. } |
+            SyslogError::SendFailed { transport, .. } |
+            SyslogError::MessageTooLarge { transport, .. } |
+            SyslogError::PoisonedLock { transport, .. } => Some(transport),
+            SyslogError::Tls { .. } => Some(Transport::TLS),
+            _ => None,
+        }
+    }
+
+    /// Destination, the error has occurred on
+    pub fn destination(&self) -> Option<&str> {
+        match *self {
+            SyslogError::ResolveFailed { ref destination, .. } |
+            SyslogError::ConnectFailed { ref destination, .. } |
+            SyslogError::Tls { ref destination, .. } |
+            SyslogError::SendFailed { ref destination, .. } |
+            SyslogError::MessageTooLarge { ref destination, .. } |
+            SyslogError::PoisonedLock { ref destination, .. } => Some(destination.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Underlying I/O error
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match *self {
+            SyslogError::ResolveFailed { ref cause, .. } |
+            SyslogError::ConnectFailed { ref cause, .. } |
+            SyslogError::SendFailed { ref cause, .. } => Some(cause),
+            SyslogError::FormatFailed(ref cause) => Some(cause),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SyslogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SyslogError::ConfigInvalid(ref message) => {
+                write!(f, "Invalid syslog configuration: {}", message)
+            }
+            SyslogError::SocketNotFound(ref paths) => {
+                write!(f, "Couldn't find socket file (tried {:?})", paths)
+            }
+            SyslogError::ResolveFailed { ref destination, ref cause } => {
+                write!(f, "Couldn't resolve {}: {}", destination, cause)
+            }
+            SyslogError::ConnectFailed { transport, ref destination, ref cause } => {
+                write!(f, "Couldn't connect to {} {}: {}", transport, destination, cause)
+            }
+            SyslogError::Tls { ref destination, ref message } => {
+                write!(f, "TLS error with {}: {}", destination, message)
+            }
+            SyslogError::FormatFailed(ref cause) => write!(f, "Couldn't format record: {}", cause),
+            SyslogError::SendFailed { transport, ref destination, ref cause } => {
+                write!(f, "Couldn't send to {} {}: {}", transport, destination, cause)
+            }
+            SyslogError::MessageTooLarge { transport, ref destination, size, limit } => {
+                write!(f, "Message of {} bytes exceeds ", size)?;
+                if let Some(limit) = limit {
+                    write!(f, "{} bytes ", limit)?;
+                }
+                write!(f, "limit of {} {}", transport, destination)
+            }
+            SyslogError::PoisonedLock { transport, ref destination } => {
+                write!(f, "Couldn't acquire lock for {} {}", transport, destination)
+            }
+            SyslogError::NoDestination => write!(f, "No syslog destination available"),
+            SyslogError::ParseFailed(ref message) => {
+                write!(f, "Couldn't parse syslog message: {}", message)
+            }
+        }
+    }
+}
+
+impl Error for SyslogError {
+    fn description(&self) -> &str {
+        match *self {
+            SyslogError::ConfigInvalid(_) => "invalid syslog configuration",
+            SyslogError::SocketNotFound(_) => "syslog socket not found",
+            SyslogError::ResolveFailed { .. } => "couldn't resolve address",
+            SyslogError::ConnectFailed { .. } => "couldn't connect to syslog",
+            SyslogError::Tls { .. } => "TLS error",
+            SyslogError::FormatFailed(_) => "couldn't format record",
+            SyslogError::SendFailed { .. } => "couldn't send to syslog",
+            SyslogError::MessageTooLarge { .. } => "message too large",
+            SyslogError::PoisonedLock { .. } => "poisoned lock",
+            SyslogError::NoDestination => "no syslog destination available",
+            SyslogError::ParseFailed(_) => "couldn't parse syslog message",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        self.io_error().map(|err| err as &Error)
+    }
+}
+
+impl From<SyslogError> for io::Error {
+    fn from(err: SyslogError) -> io::Error {
+        let kind = match err {
+            SyslogError::ConfigInvalid(_) |
+            SyslogError::MessageTooLarge { .. } => io::ErrorKind::InvalidInput,
+            SyslogError::ParseFailed(_) => io::ErrorKind::InvalidData,
+            SyslogError::SocketNotFound(_) => io::ErrorKind::NotFound,
+            SyslogError::NoDestination => io::ErrorKind::NotConnected,
+            _ => err.io_error().map(io::Error::kind).unwrap_or(io::ErrorKind::Other),
+        };
+        io::Error::new(kind, err)
+    }
+}
@@ -0,0 +1,201 @@
+use drain::{BoxedSyslogDrain, SyslogDrain};
+use error::{SyslogError, SyslogResult};
+use metrics::{DrainStats, merged_stats};
+use slog::{Drain, OwnedKeyValueList, Record};
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Default interval between attempts to use destination marked as unhealthy
+pub const DEFAULT_PROBE_INTERVAL: u64 = 30;
+
+struct Destination {
+    drain: BoxedSyslogDrain,
+    healthy: AtomicBool,
+    failed_at: Mutex<Option<Instant>>,
+}
+
+impl Destination {
+    // Unhealthy destination is probed again after probe interval has elapsed
+    fn is_due(&self, probe_interval: Duration) -> bool {
+        if self.healthy.load(Ordering::Relaxed) {
+            return true;
+        }
+        match self.failed_at.lock() {
+            Ok(failed_at) => failed_at.map(|t| t.elapsed() >= probe_interval).unwrap_or(true),
+            Err(_) => false,
+        }
+    }
+
+    fn mark_healthy(&self) {
+        self.healthy.store(true, Ordering::Relaxed);
+    }
+
+    fn mark_unhealthy(&self) {
+        self.healthy.store(false, Ordering::Relaxed);
+        if let Ok(mut failed_at) = self.failed_at.lock() {
+            *failed_at = Some(Instant::now());
+        }
+    }
+}
+
+/// Drain failing over across multiple syslog destinations
+///
+/// Destinations are tried in order, message is sent to the first healthy one.
+/// Destination is marked unhealthy when sending fails, and is skipped until
+/// the probe interval elapses, then it's reconnected and tried again.
+/// Since destinations are always tried in order, the drain
+/// fails back to the primary as soon as it recovers.
+pub struct FailoverDrain {
+    destinations: Vec<Destination>,
+    probe_interval: Duration,
+    active: AtomicUsize,
+}
+
+impl FailoverDrain {
+    /// FailoverDrain constructor, destinations in the order of preference
+    pub fn new(destinations: Vec<BoxedSyslogDrain>) -> FailoverDrain {
+        FailoverDrain {
+            destinations: destinations.into_iter()
+                .map(|drain| {
+                         Destination {
+                             drain: drain,
+                             healthy: AtomicBool::new(true),
+                             failed_at: Mutex::new(None),
+                         }
+                     })
+                .collect(),
+            probe_interval: Duration::from_secs(DEFAULT_PROBE_INTERVAL),
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// Interval between attempts to use destination marked as unhealthy
+    ///
+    /// Default: 30 seconds
+    pub fn probe_interval(mut self, value: Duration) -> Self {
+        self.probe_interval = value;
+        self
+    }
+
+    /// Mark destination unhealthy, e.g. one which couldn't connect,
+    /// it's skipped until the probe interval elapses
+    pub fn unhealthy(self, index: usize) -> Self {
+        if let Some(destination) = self.destinations.get(index) {
+            destination.mark_unhealthy();
+        }
+        self
+    }
+
+    /// Index of the destination which accepted the last message
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Health status of every destination, in order
+    pub fn health(&self) -> Vec<bool> {
+        self.destinations
+            .iter()
+            .map(|d| d.healthy.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    // Try destinations in order, until one of them accepts the message
+    fn failover<S>(&self, mut send: S) -> SyslogResult<()>
+        where S: FnMut(&BoxedSyslogDrain) -> SyslogResult<()>
+    {
+        let mut last_error = None;
+
+        for (index, destination) in self.destinations.iter().enumerate() {
+            if !destination.is_due(self.probe_interval) {
+                continue;
+            }
+
+            if !destination.healthy.load(Ordering::Relaxed) {
+                if let Err(err) = destination.drain.reconnect() {
+                    destination.mark_unhealthy();
+                    last_error = Some(err);
+                    continue;
+                }
+            }
+
+            match send(&destination.drain) {
+                Ok(()) => {
+                    destination.mark_healthy();
+                    self.active.store(index, Ordering::Relaxed);
+                    return Ok(());
+                }
+                Err(err) => {
+                    destination.mark_unhealthy();
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or(SyslogError::NoDestination))
+    }
+}
+
+impl fmt::Debug for FailoverDrain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "FailoverDrain {{ health: {:?}, active: {}, probe_interval: {:?} }}",
+               self.health(),
+               self.active(),
+               self.probe_interval)
+    }
+}
+
+impl SyslogDrain for FailoverDrain {
+    // Destinations are expected to share the format, primary one is used
+    fn format(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        match self.destinations.first() {
+            Some(destination) => destination.drain.format(info, logger_values),
+            None => Err(SyslogError::NoDestination),
+        }
+    }
+
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        self.failover(|drain| drain.send(message))
+    }
+
+    // Succeeds when at least one destination reconnects
+    fn reconnect(&self) -> SyslogResult<()> {
+        let mut reconnected = false;
+        let mut last_error = None;
+        for destination in &self.destinations {
+            match destination.drain.reconnect() {
+                Ok(()) => {
+                    destination.mark_healthy();
+                    reconnected = true;
+                }
+                Err(err) => {
+                    destination.mark_unhealthy();
+                    last_error = Some(err);
+                }
+            }
+        }
+        if reconnected {
+            return Ok(());
+        }
+        Err(last_error.unwrap_or(SyslogError::NoDestination))
+    }
+
+    // Metrics of all the destinations added up
+    fn stats(&self) -> Option<DrainStats> {
+        merged_stats(self.destinations.iter().map(|d| d.drain.stats()))
+    }
+}
+
+impl Drain for FailoverDrain {
+    type Error = SyslogError;
+
+    // Every destination formats the record with its own formatter
+    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<()> {
+        self.failover(|drain| {
+                          let buf = drain.format(info, logger_values)?;
+                          drain.send(buf.as_slice())
+                      })
+    }
+}
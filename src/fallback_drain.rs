@@ -0,0 +1,268 @@
+use drain::SyslogDrain;
+use error::{SyslogError, SyslogResult};
+use metrics::DrainStats;
+use slog::{Drain, OwnedKeyValueList, Record};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Default interval between self-diagnostic messages, in seconds
+pub const DEFAULT_DIAGNOSTICS_INTERVAL: u64 = 60;
+
+/// Callback for errors of the wrapped drain
+pub type ErrorHandler = Box<Fn(&SyslogError) + Send + Sync>;
+
+/// Local sink for records which couldn't be delivered
+#[derive(Debug, Clone, PartialEq)]
+pub enum FallbackSink {
+    /// Standard error of the process
+    Stderr,
+    /// System console `/dev/console`, like `LOG_CONS`
+    Console,
+    /// Local file, records are appended to it
+    File(PathBuf),
+}
+
+impl FromStr for FallbackSink {
+    type Err = SyslogError;
+    fn from_str(s: &str) -> Result<FallbackSink, SyslogError> {
+        let result = match s {
+            "" => return Err(SyslogError::ConfigInvalid("Empty fallback sink".to_string())),
+            "stderr" => FallbackSink::Stderr,
+            "console" => FallbackSink::Console,
+            path => FallbackSink::File(PathBuf::from(path)),
+        };
+        Ok(result)
+    }
+}
+
+impl FallbackSink {
+    fn open(&self) -> io::Result<Option<File>> {
+        match *self {
+            FallbackSink::Stderr => Ok(None),
+            FallbackSink::Console => OpenOptions::new().write(true).open("/dev/console").map(Some),
+            FallbackSink::File(ref path) => {
+                OpenOptions::new().append(true).create(true).open(path).map(Some)
+            }
+        }
+    }
+}
+
+impl fmt::Display for FallbackSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FallbackSink::Stderr => write!(f, "stderr"),
+            FallbackSink::Console => write!(f, "/dev/console"),
+            FallbackSink::File(ref path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Diagnostics {
+    last_report: Option<Instant>,
+    suppressed: usize,
+}
+
+/// Drain which never fails
+///
+/// Errors of the wrapped drain are passed to the error handler, and the formatted
+/// record is written to the fallback sink, if there is one. Failures are also
+/// reported on stderr, at most once per diagnostics interval, with the number
+/// of failures suppressed since the last report. Makes `.fuse()` safe,
+/// a syslog outage doesn't panic the application.
+pub struct FallbackDrain<D> {
+    drain: D,
+    on_error: Option<ErrorHandler>,
+    sink: Option<FallbackSink>,
+    // Opened sink file, `None` for stderr, or until the first failure
+    file: Mutex<Option<File>>,
+    diagnostics_interval: Option<Duration>,
+    diagnostics: Mutex<Diagnostics>,
+    failures: AtomicUsize,
+}
+
+impl<D> FallbackDrain<D>
+    where D: SyslogDrain
+{
+    /// Wrap connected drain
+    pub fn new(drain: D) -> FallbackDrain<D> {
+        FallbackDrain {
+            drain: drain,
+            on_error: None,
+            sink: None,
+            file: Mutex::new(None),
+            diagnostics_interval: Some(Duration::from_secs(DEFAULT_DIAGNOSTICS_INTERVAL)),
+            diagnostics: Mutex::new(Diagnostics {
+                                        last_report: None,
+                                        suppressed: 0,
+                                    }),
+            failures: AtomicUsize::new(0),
+        }
+    }
+
+    /// Callback invoked on every error of the wrapped drain
+    pub fn on_error<H>(mut self, handler: H) -> Self
+        where H: Fn(&SyslogError) + Send + Sync + 'static
+    {
+        self.on_error = Some(Box::new(handler));
+        self
+    }
+
+    /// Sink for records which couldn't be delivered [FallbackSink](enum.FallbackSink.html)
+    ///
+    /// Default: `None`, records are discarded
+    pub fn fallback<VALUE: Into<FallbackSink>>(mut self, value: VALUE) -> Self {
+        self.sink = Some(value.into());
+        self
+    }
+
+    /// Minimal interval between failure reports on stderr, `None` disables them
+    ///
+    /// Default: 60 seconds
+    pub fn diagnostics_interval(mut self, value: Option<Duration>) -> Self {
+        self.diagnostics_interval = value;
+        self
+    }
+
+    /// Number of records which couldn't be delivered
+    pub fn failures(&self) -> usize {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Wrapped drain
+    pub fn get_ref(&self) -> &D {
+        &self.drain
+    }
+
+    fn fail(&self, err: &SyslogError, message: Option<&[u8]>) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        if let Some(ref on_error) = self.on_error {
+            on_error(err);
+        }
+        let sink_result = match message {
+            Some(message) => self.write_fallback(message),
+            None => Ok(()),
+        };
+        self.diagnose(err, sink_result.err());
+    }
+
+    // Message is written as a single LF terminated line
+    fn write_fallback(&self, message: &[u8]) -> io::Result<()> {
+        let sink = match self.sink {
+            Some(ref sink) => sink,
+            None => return Ok(()),
+        };
+
+        let mut end = message.len();
+        while end > 0 && (message[end - 1] == b'\0' || message[end - 1] == b'\n') {
+            end -= 1;
+        }
+        let mut line = Vec::<u8>::with_capacity(end + 1);
+        line.extend_from_slice(&message[..end]);
+        line.push(b'\n');
+
+        let mut file = self.file
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "fallback sink lock poisoned"))?;
+        if file.is_none() {
+            *file = sink.open()?;
+        }
+        let result = match *file {
+            Some(ref mut file) => file.write_all(&line),
+            None => io::stderr().write_all(&line),
+        };
+        if result.is_err() {
+            // Reopen on the next failure, the file may have been rotated or removed
+            *file = None;
+        }
+        result
+    }
+
+    fn diagnose(&self, err: &SyslogError, sink_error: Option<io::Error>) {
+        let interval = match self.diagnostics_interval {
+            Some(interval) => interval,
+            None => return,
+        };
+        let mut diagnostics = match self.diagnostics.lock() {
+            Ok(diagnostics) => diagnostics,
+            Err(_) => return,
+        };
+
+        let due = diagnostics.last_report.map(|t| t.elapsed() >= interval).unwrap_or(true);
+        if !due {
+            diagnostics.suppressed += 1;
+            return;
+        }
+
+        let mut report = format!("slog-syslog-ng: {}", err);
+        if let Some(sink_error) = sink_error {
+            let sink = self.sink.as_ref().map(|s| s.to_string()).unwrap_or_default();
+            report.push_str(&format!(", fallback to {} failed: {}", sink, sink_error));
+        }
+        if diagnostics.suppressed > 0 {
+            report.push_str(&format!(" ({} similar errors suppressed)", diagnostics.suppressed));
+        }
+        let _ = writeln!(io::stderr(), "{}", report);
+
+        diagnostics.last_report = Some(Instant::now());
+        diagnostics.suppressed = 0;
+    }
+}
+
+impl<D> fmt::Debug for FallbackDrain<D>
+    where D: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "FallbackDrain {{ drain: {:?}, sink: {:?}, failures: {} }}",
+               self.drain,
+               self.sink,
+               self.failures())
+    }
+}
+
+impl<D> SyslogDrain for FallbackDrain<D>
+    where D: SyslogDrain
+{
+    fn format(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        self.drain.format(info, logger_values)
+    }
+
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        if let Err(err) = self.drain.send(message) {
+            self.fail(&err, Some(message));
+        }
+        Ok(())
+    }
+
+    fn reconnect(&self) -> SyslogResult<()> {
+        self.drain.reconnect()
+    }
+
+    fn stats(&self) -> Option<DrainStats> {
+        self.drain.stats()
+    }
+}
+
+impl<D> Drain for FallbackDrain<D>
+    where D: SyslogDrain
+{
+    type Error = SyslogError;
+
+    // Never fails, records which couldn't be formatted are only reported
+    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<()> {
+        match self.drain.format(info, logger_values) {
+            Ok(buf) => self.send(buf.as_slice()),
+            Err(err) => {
+                self.fail(&err, None);
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::borrow::Cow;
+
+/// Order of record and logger values in the output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldOrder {
+    /// Record values, then logger values
+    RecordFirst,
+    /// Logger values, then record values
+    LoggerFirst,
+}
+
+impl Default for FieldOrder {
+    fn default() -> FieldOrder {
+        FieldOrder::RecordFirst
+    }
+}
+
+/// Transforms of record and logger keys, applied during serialization
+///
+/// Keys are dropped and renamed by their original name, the prefix is
+/// added to every emitted key, renamed ones included. Redaction is also
+/// decided by the original key.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FieldTransforms {
+    /// Prefix added to every key, e.g. `app.`
+    ///
+    /// Default: `None`
+    pub prefix: Option<String>,
+    /// Key renames: original key and its new name
+    pub renames: Vec<(String, String)>,
+    /// Keys which are not emitted
+    pub dropped: Vec<String>,
+    /// Emit only the first of the values with the same key: record values
+    /// shadow logger values, child logger values shadow the parent ones
+    ///
+    /// Default: `false`, every value is emitted
+    pub dedup: bool,
+    /// Order of record and logger values [FieldOrder](enum.FieldOrder.html)
+    ///
+    /// Default: `RecordFirst`
+    pub order: FieldOrder,
+}
+
+impl FieldTransforms {
+    /// Transforms which keep keys as they are
+    pub fn new() -> FieldTransforms {
+        FieldTransforms::default()
+    }
+
+    /// Prefix added to every key, e.g. `app.`
+    pub fn prefix<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.prefix = Some(value.into());
+        self
+    }
+
+    /// Emit values of `key` as `name`
+    pub fn rename<K: Into<String>, N: Into<String>>(mut self, key: K, name: N) -> Self {
+        self.renames.push((key.into(), name.into()));
+        self
+    }
+
+    /// Don't emit values of the key
+    pub fn drop_key<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.dropped.push(value.into());
+        self
+    }
+
+    /// Emit only the first of the values with the same key
+    pub fn dedup(mut self, value: bool) -> Self {
+        self.dedup = value;
+        self
+    }
+
+    /// Order of record and logger values [FieldOrder](enum.FieldOrder.html)
+    pub fn order<VALUE: Into<FieldOrder>>(mut self, value: VALUE) -> Self {
+        self.order = value.into();
+        self
+    }
+
+    /// Whether values of the key are emitted
+    pub fn keeps(&self, key: &str) -> bool {
+        !self.dropped.iter().any(|k| k == key)
+    }
+
+    /// Emitted name of the key
+    pub fn name<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        let renamed = self.renames
+            .iter()
+            .rev()
+            .find(|&&(ref k, _)| k == key)
+            .map(|&(_, ref name)| name.as_str());
+        match (self.prefix.as_ref(), renamed) {
+            (None, None) => Cow::Borrowed(key),
+            (None, Some(name)) => Cow::Owned(name.to_string()),
+            (Some(prefix), name) => Cow::Owned(format!("{}{}", prefix, name.unwrap_or(key))),
+        }
+    }
+}
@@ -19,23 +19,97 @@ mod rfc3164;
 
 use self::rfc3164::{Rfc3164, Rfc3164Short, Rfc3164Full};
 use self::rfc5424::{Rfc5424, Rfc5424Short, Rfc5424Full};
-use serializers::KsvSerializerUnquoted;
-
+use fields::{FieldOrder, FieldTransforms};
+use posix::{HOSTNAME_FALLBACK, HostnameSource, get_host_name_from, get_pid,
+             get_primary_address};
+use redaction::RedactionPolicy;
+use serializers::{KsvSerializer, KsvSerializerUnquoted};
+use structured_data::{AutoStructuredData, SdElement, SourceLocation};
+
+use slog;
 use slog::{Record, OwnedKeyValueList};
+use slog::ser::{Serialize, Serializer};
 use slog_stream::Format as StreamFormat;
 use std::io;
 use std::marker::PhantomData;
-use syslog::Facility;
+use std::sync::{Mutex, RwLock};
+use std::sync::atomic::{ATOMIC_USIZE_INIT, AtomicBool, AtomicIsize, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use syslog::{Facility, Severity};
 use time::{FormatTimestamp, OmitTimestamp, Ts3164Local, Ts3164Utc, TsIsoLocal, TsIsoUtc};
 
 
+/// RFC5424 PROCID, or the `[pid]` part of RFC3164 TAG
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcId {
+    /// Process id
+    Pid,
+    /// No PROCID: NILVALUE in RFC5424, TAG without `[pid]` in RFC3164
+    Omit,
+    /// Custom value, e.g. a worker id
+    Custom(String),
+}
+
+impl Default for ProcId {
+    fn default() -> ProcId {
+        ProcId::Pid
+    }
+}
+
+// Bumped by `refresh_hostname`, formatters compare it with the one of their lookup
+static HOSTNAME_GENERATION: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Make formatters look the hostname up again before their next message, e.g. after
+/// a container rename. Applies to formatters with
+/// [HostnameRefresh](enum.HostnameRefresh.html) other than `Never`.
+pub fn refresh_hostname() {
+    HOSTNAME_GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// When the hostname is looked up again
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HostnameRefresh {
+    /// Never, the hostname is fixed
+    Never,
+    /// After `refresh_hostname` is called
+    OnDemand,
+    /// After `refresh_hostname` is called, or when the interval elapses
+    Every(Duration),
+}
+
+impl Default for HostnameRefresh {
+    fn default() -> HostnameRefresh {
+        HostnameRefresh::Never
+    }
+}
+
+#[derive(Debug)]
+struct CachedHostname {
+    value: Option<String>,
+    generation: usize,
+    looked_up: Instant,
+}
+
 /// Syslog header fields
+///
+/// The pid is replaced by the one of the current process when it changes,
+/// so forked children don't report the parent's pid.
 #[derive(Debug)]
 pub struct HeaderFields {
-    hostname: Option<String>,
+    hostname: RwLock<CachedHostname>,
+    // Set while a thread looks the hostname up, the others keep using the cached one
+    refreshing: AtomicBool,
+    // Primary address, looked up for the first message without a hostname
+    address: Mutex<Option<String>>,
+    hostname_refresh: HostnameRefresh,
+    hostname_source: HostnameSource,
     process_name: Option<String>,
-    pid: i32,
+    pid: AtomicIsize,
+    // getpid() when `pid` was set
+    pid_of: AtomicIsize,
+    proc_id: ProcId,
     facility: Facility,
+    severity: Option<Severity>,
 }
 
 impl HeaderFields {
@@ -46,14 +120,350 @@ impl HeaderFields {
                facility: Facility)
                -> Self {
         HeaderFields {
-            hostname: hostname,
+            hostname: RwLock::new(CachedHostname {
+                                      value: hostname,
+                                      generation: HOSTNAME_GENERATION.load(Ordering::Relaxed),
+                                      looked_up: Instant::now(),
+                                  }),
+            refreshing: AtomicBool::new(false),
+            address: Mutex::new(None),
+            hostname_refresh: HostnameRefresh::default(),
+            hostname_source: HostnameSource::default(),
             process_name: process_name,
-            pid: pid,
+            pid: AtomicIsize::new(pid as isize),
+            pid_of: AtomicIsize::new(get_pid() as isize),
+            proc_id: ProcId::default(),
             facility: facility,
+            severity: None,
+        }
+    }
+
+    /// When the hostname is looked up again [HostnameRefresh](enum.HostnameRefresh.html)
+    ///
+    /// Default: `Never`
+    pub fn hostname_refresh<VALUE: Into<HostnameRefresh>>(mut self, value: VALUE) -> Self {
+        self.hostname_refresh = value.into();
+        self
+    }
+
+    /// Where the refreshed hostname comes from [HostnameSource](enum.HostnameSource.html)
+    ///
+    /// Default: `System`
+    pub fn hostname_source<VALUE: Into<HostnameSource>>(mut self, value: VALUE) -> Self {
+        self.hostname_source = value.into();
+        self
+    }
+
+    /// PROCID [ProcId](enum.ProcId.html)
+    ///
+    /// Default: `Pid`
+    pub fn proc_id<VALUE: Into<ProcId>>(mut self, value: VALUE) -> Self {
+        self.proc_id = value.into();
+        self
+    }
+
+    /// Severity of every message, instead of the one mapped from the record level
+    ///
+    /// Default: `None`
+    pub fn severity<VALUE: Into<Severity>>(mut self, value: VALUE) -> Self {
+        self.severity = Some(value.into());
+        self
+    }
+}
+
+impl HeaderFields {
+    // Configured severity, or the one mapped from the record level
+    fn severity_of(&self, record: &Record) -> Severity {
+        self.severity.unwrap_or_else(|| record.level().into())
+    }
+
+    // Pid of the current process, unless it's the one the fields were created in
+    fn pid(&self) -> i32 {
+        let current = get_pid() as isize;
+        if self.pid_of.load(Ordering::Relaxed) != current {
+            self.pid.store(current, Ordering::Relaxed);
+            self.pid_of.store(current, Ordering::Relaxed);
+        }
+        self.pid.load(Ordering::Relaxed) as i32
+    }
+
+    // Whether the hostname is due for another lookup
+    fn hostname_stale(&self, cached: &CachedHostname) -> bool {
+        let requested = cached.generation != HOSTNAME_GENERATION.load(Ordering::Relaxed);
+        match self.hostname_refresh {
+            HostnameRefresh::Never => false,
+            HostnameRefresh::OnDemand => requested,
+            HostnameRefresh::Every(interval) => requested || cached.looked_up.elapsed() >= interval,
+        }
+    }
+
+    // Call `f` with the hostname, looked up again if it's stale.
+    // Only one thread looks it up, failed lookup keeps the previous hostname.
+    fn with_hostname<F, R>(&self, f: F) -> R
+        where F: FnOnce(Option<&str>) -> R
+    {
+        if self.hostname_refresh != HostnameRefresh::Never {
+            let stale = match self.hostname.read() {
+                Ok(cached) => self.hostname_stale(&cached),
+                Err(_) => false,
+            };
+            if stale && !self.refreshing.swap(true, Ordering::Acquire) {
+                let hostname = get_host_name_from(self.hostname_source);
+                if let Ok(mut cached) = self.hostname.write() {
+                    if hostname.is_some() {
+                        cached.value = hostname;
+                    }
+                    cached.generation = HOSTNAME_GENERATION.load(Ordering::Relaxed);
+                    cached.looked_up = Instant::now();
+                }
+                self.refreshing.store(false, Ordering::Release);
+            }
+        }
+        match self.hostname.read() {
+            Ok(cached) => f(cached.value.as_ref().map(String::as_str)),
+            Err(_) => f(None),
+        }
+    }
+
+    // Call `f` with the hostname, or the primary address of the host without one,
+    // for formats without NILVALUE
+    fn with_hostname_or_address<F, R>(&self, f: F) -> R
+        where F: FnOnce(&str) -> R
+    {
+        self.with_hostname(|hostname| match hostname {
+            Some(hostname) => f(hostname),
+            None => {
+                let mut address = match self.address.lock() {
+                    Ok(address) => address,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                if address.is_none() {
+                    *address = Some(get_primary_address()
+                        .map(|ip| ip.to_string())
+                        .unwrap_or_else(|| HOSTNAME_FALLBACK.to_string()));
+                }
+                f(address.as_ref().map(String::as_str).unwrap_or(HOSTNAME_FALLBACK))
+            }
+        })
+    }
+}
+
+/// SD-ID of the RFC5424 SD-ELEMENT with record values
+pub const RECORD_SD_ID: &'static str = "msg@32473";
+
+/// SD-ID of the RFC5424 SD-ELEMENT with logger values
+pub const LOGGER_SD_ID: &'static str = "logger@32473";
+
+/// Options of structured data serialization, shared by header and message formatters
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Redaction of sensitive values [RedactionPolicy](struct.RedactionPolicy.html)
+    pub redaction: RedactionPolicy,
+    /// Key transforms [FieldTransforms](struct.FieldTransforms.html)
+    pub fields: FieldTransforms,
+    /// RFC5424 SD-ELEMENTs for groups of keys [SdElement](struct.SdElement.html)
+    pub sd_elements: Vec<SdElement>,
+    /// Registered RFC5424 SD-ELEMENTs added to every message
+    /// [AutoStructuredData](struct.AutoStructuredData.html)
+    pub auto_sd: AutoStructuredData,
+    /// Source location of records [SourceLocation](struct.SourceLocation.html)
+    pub source_location: Option<SourceLocation>,
+    /// SD-ID of the RFC5424 SD-ELEMENT with record values
+    ///
+    /// Default: `msg@32473`
+    pub record_sd_id: String,
+    /// SD-ID of the RFC5424 SD-ELEMENT with logger values
+    ///
+    /// Default: `logger@32473`
+    pub logger_sd_id: String,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            redaction: RedactionPolicy::default(),
+            fields: FieldTransforms::default(),
+            sd_elements: Vec::new(),
+            auto_sd: AutoStructuredData::default(),
+            source_location: None,
+            record_sd_id: RECORD_SD_ID.to_string(),
+            logger_sd_id: LOGGER_SD_ID.to_string(),
         }
     }
 }
 
+impl FormatOptions {
+    /// Redaction of sensitive values [RedactionPolicy](struct.RedactionPolicy.html)
+    pub fn redaction<VALUE: Into<RedactionPolicy>>(mut self, value: VALUE) -> Self {
+        self.redaction = value.into();
+        self
+    }
+
+    /// Key transforms [FieldTransforms](struct.FieldTransforms.html)
+    pub fn fields<VALUE: Into<FieldTransforms>>(mut self, value: VALUE) -> Self {
+        self.fields = value.into();
+        self
+    }
+
+    /// RFC5424 SD-ELEMENT for a group of keys [SdElement](struct.SdElement.html)
+    pub fn sd_element<VALUE: Into<SdElement>>(mut self, value: VALUE) -> Self {
+        self.sd_elements.push(value.into());
+        self
+    }
+
+    /// Registered RFC5424 SD-ELEMENTs added to every message
+    /// [AutoStructuredData](struct.AutoStructuredData.html)
+    pub fn auto_sd<VALUE: Into<AutoStructuredData>>(mut self, value: VALUE) -> Self {
+        self.auto_sd = value.into();
+        self
+    }
+
+    /// Source location of records [SourceLocation](struct.SourceLocation.html)
+    pub fn source_location<VALUE: Into<SourceLocation>>(mut self, value: VALUE) -> Self {
+        self.source_location = Some(value.into());
+        self
+    }
+
+    /// SD-ID of the RFC5424 SD-ELEMENT with record values
+    pub fn record_sd_id<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.record_sd_id = value.into();
+        self
+    }
+
+    /// SD-ID of the RFC5424 SD-ELEMENT with logger values
+    pub fn logger_sd_id<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.logger_sd_id = value.into();
+        self
+    }
+
+    // Index of the first SD-ELEMENT the key goes to
+    fn sd_element_index(&self, key: &str) -> Option<usize> {
+        self.sd_elements.iter().position(|e| e.param_name(key).is_some())
+    }
+}
+
+// Visit record values in output order, skipping dropped and duplicate keys.
+// Visitor gets the original key, its emitted name and the value.
+fn visit_record_values<F>(record: &Record,
+                          options: &FormatOptions,
+                          mut visit: F)
+                          -> slog::ser::Result
+    where F: FnMut(&str, &str, &Serialize) -> slog::ser::Result
+{
+    let fields = &options.fields;
+    let mut seen = Vec::<&str>::new();
+    for &(k, v) in record.values().iter().rev() {
+        if !fields.keeps(k) || (fields.dedup && seen.contains(&k)) {
+            continue;
+        }
+        seen.push(k);
+        visit(k, fields.name(k).as_ref(), v)?;
+    }
+    Ok(())
+}
+
+// Visit the source location of the record like `visit_record_values`, as keys with
+// the prefix of the source location, none without one or for records without a file
+fn visit_source_values<F>(record: &Record,
+                          options: &FormatOptions,
+                          mut visit: F)
+                          -> slog::ser::Result
+    where F: FnMut(&str, &str, &Serialize) -> slog::ser::Result
+{
+    let source = match options.source_location {
+        Some(ref source) => source,
+        None => return Ok(()),
+    };
+    let fields = &options.fields;
+    for (name, value) in source_params(record, source) {
+        let key = format!("{}{}", source.prefix, name);
+        if !fields.keeps(&key) {
+            continue;
+        }
+        visit(&key, fields.name(&key).as_ref(), &value)?;
+    }
+    Ok(())
+}
+
+// Visit logger values like `visit_record_values`, record keys shadow them when deduplicating
+fn visit_logger_values<F>(record: &Record,
+                          logger_values: &OwnedKeyValueList,
+                          options: &FormatOptions,
+                          mut visit: F)
+                          -> slog::ser::Result
+    where F: FnMut(&str, &str, &Serialize) -> slog::ser::Result
+{
+    let fields = &options.fields;
+    let mut seen = Vec::<&str>::new();
+    if fields.dedup {
+        seen.extend(record.values().iter().map(|&(k, _)| k));
+    }
+    for (k, v) in logger_values.iter() {
+        if !fields.keeps(k) || (fields.dedup && seen.contains(&k)) {
+            continue;
+        }
+        seen.push(k);
+        visit(k, fields.name(k).as_ref(), v)?;
+    }
+    Ok(())
+}
+
+// Visit record and logger values in the configured order,
+// the source location follows the record values
+fn visit_values<F>(record: &Record,
+                   logger_values: &OwnedKeyValueList,
+                   options: &FormatOptions,
+                   mut visit: F)
+                   -> slog::ser::Result
+    where F: FnMut(&str, &str, &Serialize) -> slog::ser::Result
+{
+    match options.fields.order {
+        FieldOrder::RecordFirst => {
+            visit_record_values(record, options, &mut visit)?;
+            visit_source_values(record, options, &mut visit)?;
+            visit_logger_values(record, logger_values, options, &mut visit)
+        }
+        FieldOrder::LoggerFirst => {
+            visit_logger_values(record, logger_values, options, &mut visit)?;
+            visit_record_values(record, options, &mut visit)?;
+            visit_source_values(record, options, &mut visit)
+        }
+    }
+}
+
+// Serialize key-value pair preceded by the delimiter, unless the options drop the key
+fn serialize_pair<W, Q>(serializer: &mut KsvSerializer<W, Q>,
+                        record: &Record,
+                        key: &str,
+                        name: &str,
+                        value: &Serialize,
+                        options: &FormatOptions)
+                        -> slog::ser::Result
+    where W: io::Write,
+          KsvSerializer<W, Q>: Serializer
+{
+    if !options.redaction.allows(key) {
+        return Ok(());
+    }
+    serializer.emit_delimiter()?;
+    options.redaction.serialize_as(record, key, name, value, serializer)
+}
+
+// Source location of the record as (PARAM-NAME, value) pairs,
+// none for records without a file
+fn source_params(record: &Record, source: &SourceLocation) -> Vec<(&'static str, String)> {
+    if record.file().is_empty() {
+        return Vec::new();
+    }
+    let mut params = vec![("file", source.trim_path(record.file()).to_string()),
+                          ("line", record.line().to_string()),
+                          ("module", record.module().to_string())];
+    if !record.function().is_empty() {
+        params.push(("function", record.function().to_string()));
+    }
+    params
+}
+
 /// Generic Syslog Header Formatter
 pub trait FormatHeader {
     /// Associated `time::Timestamp`
@@ -67,7 +477,8 @@ pub trait FormatHeader {
     fn format(&self,
               io: &mut io::Write,
               record: &Record,
-              logger_values: &OwnedKeyValueList)
+              logger_values: &OwnedKeyValueList,
+              options: &FormatOptions)
               -> io::Result<()>;
 }
 
@@ -84,7 +495,8 @@ pub trait FormatMessage {
     /// Format syslog message
     fn format(io: &mut io::Write,
               record: &Record,
-              logger_values: &OwnedKeyValueList)
+              logger_values: &OwnedKeyValueList,
+              options: &FormatOptions)
               -> io::Result<()>;
 }
 
@@ -92,7 +504,8 @@ impl FormatMessage for MessageOnly {
     #[allow(unused_variables)]
     fn format(io: &mut io::Write,
               record: &Record,
-              logger_values: &OwnedKeyValueList)
+              logger_values: &OwnedKeyValueList,
+              options: &FormatOptions)
               -> io::Result<()> {
 
         // MESSAGE
@@ -105,7 +518,8 @@ impl FormatMessage for MessageOnly {
 impl FormatMessage for MessageWithKsv {
     fn format(io: &mut io::Write,
               record: &Record,
-              logger_values: &OwnedKeyValueList)
+              logger_values: &OwnedKeyValueList,
+              options: &FormatOptions)
               -> io::Result<()> {
 
         // MESSAGE
@@ -114,15 +528,9 @@ impl FormatMessage for MessageWithKsv {
         // MESSAGE STRUCTURED_DATA
         let mut serializer = KsvSerializerUnquoted::new(io, "=");
 
-        for &(k, v) in record.values().iter().rev() {
-            serializer.emit_delimiter()?;
-            v.serialize(record, k, &mut serializer)?;
-        }
-
-        for (k, v) in logger_values.iter() {
-            serializer.emit_delimiter()?;
-            v.serialize(record, k, &mut serializer)?;
-        }
+        visit_values(record, logger_values, options, |key, name, value| {
+            serialize_pair(&mut serializer, record, key, name, value, options)
+        })?;
 
         Ok(())
     }
@@ -137,6 +545,7 @@ pub struct SyslogFormatter<H, M>
           M: FormatMessage
 {
     header: H,
+    options: FormatOptions,
     _message: PhantomData<M>,
 }
 
@@ -162,14 +571,22 @@ impl<H, M> SyslogFormatter<H, M>
                facility: Facility)
                -> Self {
 
-        let header_fields = HeaderFields::new(hostname, process_name, pid, facility);
-        let header = H::new(header_fields);
+        SyslogFormatter::from_fields(HeaderFields::new(hostname, process_name, pid, facility))
+    }
 
+    /// Formatter with all the header fields
+    pub fn from_fields(fields: HeaderFields) -> Self {
         SyslogFormatter {
-            header: header,
+            header: H::new(fields),
+            options: FormatOptions::default(),
             _message: PhantomData,
         }
+    }
 
+    /// Structured data serialization options [FormatOptions](struct.FormatOptions.html)
+    pub fn options<VALUE: Into<FormatOptions>>(mut self, value: VALUE) -> Self {
+        self.options = value.into();
+        self
     }
 }
 
@@ -186,12 +603,12 @@ impl<H, M> SyslogFormat for SyslogFormatter<H, M>
               -> io::Result<()> {
 
         // HEADER
-        H::format(&self.header, io, record, logger_values)?;
+        H::format(&self.header, io, record, logger_values, &self.options)?;
 
         write_sp!(io)?; // SP
 
         // MESSAGE
-        M::format(io, record, logger_values)?;
+        M::format(io, record, logger_values, &self.options)?;
 
         // EOM
         write_eom!(io)?;
@@ -1,4 +1,4 @@
-use super::{HeaderFields, FormatHeader};
+use super::{HeaderFields, FormatHeader, FormatOptions, ProcId};
 use slog::{Record, OwnedKeyValueList};
 use std::io;
 use std::marker::PhantomData;
@@ -34,15 +34,20 @@ impl<T, F> Rfc3164<T, F>
           F: Rfc3164Header
 {
     fn format_prioriy(&self, io: &mut io::Write, record: &Record) -> io::Result<()> {
-        let priority = Priority::new(self.fields.facility, record.level().into());
+        let priority = Priority::new(self.fields.facility, self.fields.severity_of(record));
         write!(io, "<{}>", priority)?;
         Ok(())
     }
 
     fn format_tag(&self, io: &mut io::Write) -> io::Result<()> {
-        match self.fields.process_name {
-            Some(ref process_name) => write!(io, "{}[{}]:", process_name, self.fields.pid)?,
-            None => write!(io, "[{}]:", self.fields.pid)?,
+        if let Some(ref process_name) = self.fields.process_name {
+            write!(io, "{}", process_name)?;
+        }
+        match self.fields.proc_id {
+            ProcId::Pid => write!(io, "[{}]:", self.fields.pid())?,
+            ProcId::Custom(ref proc_id) => write!(io, "[{}]:", proc_id)?,
+            ProcId::Omit if self.fields.process_name.is_some() => write!(io, ":")?,
+            ProcId::Omit => {}
         }
         Ok(())
     }
@@ -52,11 +57,9 @@ impl<T, F> Rfc3164<T, F>
         Ok(())
     }
 
+    // RFC3164 has no NILVALUE, the primary address stands in for a missing hostname
     fn format_hostname(&self, io: &mut io::Write) -> io::Result<()> {
-        if let Some(ref hostname) = self.fields.hostname {
-            write!(io, "{}", hostname)?
-        }
-        Ok(())
+        self.fields.with_hostname_or_address(|hostname| write!(io, "{}", hostname))
     }
 }
 
@@ -75,7 +78,8 @@ impl FormatHeader for Rfc3164<OmitTimestamp, Rfc3164Short> {
     fn format(&self,
               io: &mut io::Write,
               record: &Record,
-              logger_values: &OwnedKeyValueList)
+              logger_values: &OwnedKeyValueList,
+              options: &FormatOptions)
               -> io::Result<()> {
 
         // PRIORITY: <PRI>
@@ -109,7 +113,8 @@ impl<T> FormatHeader for Rfc3164<T, Rfc3164Full>
     fn format(&self,
               io: &mut io::Write,
               record: &Record,
-              logger_values: &OwnedKeyValueList)
+              logger_values: &OwnedKeyValueList,
+              options: &FormatOptions)
               -> io::Result<()> {
 
         // PRIORITY: <PRI>
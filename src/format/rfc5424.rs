@@ -1,8 +1,15 @@
-use super::{HeaderFields, FormatHeader};
+use super::{HeaderFields, FormatHeader, FormatOptions, ProcId, serialize_pair,
+            visit_logger_values, visit_record_values, visit_source_values, visit_values};
+use fields::FieldOrder;
 use serializers::KsvSerializerQuotedValue;
 use slog::{Record, OwnedKeyValueList};
+use structured_data::{SourceLocation, escape_param_value};
+use posix::{get_local_addresses, get_uptime, is_clock_synced};
 use std::io;
 use std::marker::PhantomData;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use syslog::Priority;
 use time::FormatTimestamp;
 
@@ -58,6 +65,10 @@ use time::FormatTimestamp;
 #[derive(Debug)]
 pub struct Rfc5424<T, F> {
     fields: HeaderFields,
+    // `meta` sequenceId counter
+    sequence: AtomicUsize,
+    // Local addresses for `origin`, detected on the first use
+    origin_ips: Mutex<Option<Vec<IpAddr>>>,
     _timestamp: PhantomData<T>,
     _header_format: PhantomData<F>,
 }
@@ -80,7 +91,7 @@ impl<T, F> Rfc5424<T, F>
           F: Rfc5424Header
 {
     fn format_prioriy(&self, io: &mut io::Write, record: &Record) -> io::Result<()> {
-        let priority = Priority::new(self.fields.facility, record.level().into());
+        let priority = Priority::new(self.fields.facility, self.fields.severity_of(record));
         write!(io, "<{}>1", priority)?;
         Ok(())
     }
@@ -91,11 +102,10 @@ impl<T, F> Rfc5424<T, F>
     }
 
     fn format_hostname(&self, io: &mut io::Write) -> io::Result<()> {
-        match self.fields.hostname {
-            Some(ref hostname) => write!(io, "{}", hostname)?,
-            None => write_nilvalue!(io)?,
-        }
-        Ok(())
+        self.fields.with_hostname(|hostname| match hostname {
+                                      Some(hostname) => write!(io, "{}", hostname),
+                                      None => write_nilvalue!(io),
+                                  })
     }
 
     fn format_application(&self, io: &mut io::Write) -> io::Result<()> {
@@ -107,7 +117,11 @@ impl<T, F> Rfc5424<T, F>
     }
 
     fn format_pid(&self, io: &mut io::Write) -> io::Result<()> {
-        write!(io, "{}", self.fields.pid)?;
+        match self.fields.proc_id {
+            ProcId::Pid => write!(io, "{}", self.fields.pid())?,
+            ProcId::Custom(ref proc_id) => write!(io, "{}", proc_id)?,
+            ProcId::Omit => write_nilvalue!(io)?,
+        }
         Ok(())
     }
 
@@ -139,6 +153,76 @@ impl<T, F> Rfc5424<T, F>
         Ok(())
     }
 
+    // Next `meta` sequenceId, 1 to 2147483647, wrapping around
+    fn next_sequence_id(&self) -> usize {
+        self.sequence.fetch_add(1, Ordering::Relaxed) % 2147483647 + 1
+    }
+
+    fn format_origin_ips(&self, io: &mut io::Write, options: &FormatOptions) -> io::Result<()> {
+        for ip in &options.auto_sd.origin_ips {
+            write!(io, " ip=\"{}\"", ip)?;
+        }
+        if !options.auto_sd.origin_ips.is_empty() {
+            return Ok(());
+        }
+
+        let mut detected = match self.origin_ips.lock() {
+            Ok(detected) => detected,
+            Err(_) => return Ok(()),
+        };
+        if detected.is_none() {
+            *detected = Some(get_local_addresses().unwrap_or_default());
+        }
+        if let Some(ref ips) = *detected {
+            for ip in ips {
+                write!(io, " ip=\"{}\"", ip)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Registered SD-ELEMENTs enabled in the options
+    fn format_auto_elements(&self, io: &mut io::Write, options: &FormatOptions) -> io::Result<()> {
+        let auto = &options.auto_sd;
+
+        if auto.time_quality {
+            write!(io, "{}", "[timeQuality tzKnown=\"1\"")?;
+            if let Some(synced) = is_clock_synced() {
+                write!(io, " isSynced=\"{}\"", if synced { 1 } else { 0 })?;
+            }
+            write!(io, "{}", "]")?;
+        }
+
+        if auto.origin {
+            write!(io, "{}", "[origin")?;
+            self.format_origin_ips(io, options)?;
+            match auto.software {
+                Some((ref name, ref version)) => {
+                    write!(io, " software=\"{}\" swVersion=\"{}\"",
+                           escape_param_value(name),
+                           escape_param_value(version))?
+                }
+                None => {
+                    write!(io, " software=\"{}\" swVersion=\"{}\"",
+                           env!("CARGO_PKG_NAME"),
+                           env!("CARGO_PKG_VERSION"))?
+                }
+            }
+            write!(io, "{}", "]")?;
+        }
+
+        if auto.meta {
+            write!(io, "[meta sequenceId=\"{}\"", self.next_sequence_id())?;
+            if let Some(uptime) = get_uptime() {
+                let hundredths = uptime.as_secs() * 100 + uptime.subsec_nanos() as u64 / 10_000_000;
+                write!(io, " sysUpTime=\"{}\"", hundredths)?;
+            }
+            write!(io, "{}", "]")?;
+        }
+
+        Ok(())
+    }
+
 }
 
 impl<T> FormatHeader for Rfc5424<T, Rfc5424Short>
@@ -149,6 +233,8 @@ impl<T> FormatHeader for Rfc5424<T, Rfc5424Short>
     fn new(fields: HeaderFields) -> Self {
         Rfc5424::<T, Rfc5424Short> {
             fields: fields,
+            sequence: AtomicUsize::new(0),
+            origin_ips: Mutex::new(None),
             _timestamp: PhantomData,
             _header_format: PhantomData,
         }
@@ -157,14 +243,19 @@ impl<T> FormatHeader for Rfc5424<T, Rfc5424Short>
     fn format(&self,
               io: &mut io::Write,
               record: &Record,
-              logger_values: &OwnedKeyValueList)
+              logger_values: &OwnedKeyValueList,
+              options: &FormatOptions)
               -> io::Result<()> {
 
         self.format_header(io, record, logger_values)?; // HEADER
 
         write_sp!(io)?; // SP
 
-        write_nilvalue!(io)?; // NILVALUE
+        if options.auto_sd.is_empty() {
+            write_nilvalue!(io)?; // NILVALUE
+        } else {
+            self.format_auto_elements(io, options)?;
+        }
 
         Ok(())
     }
@@ -178,6 +269,8 @@ impl<T> FormatHeader for Rfc5424<T, Rfc5424Full>
     fn new(fields: HeaderFields) -> Self {
         Rfc5424::<T, Rfc5424Full> {
             fields: fields,
+            sequence: AtomicUsize::new(0),
+            origin_ips: Mutex::new(None),
             _timestamp: PhantomData,
             _header_format: PhantomData,
         }
@@ -186,35 +279,125 @@ impl<T> FormatHeader for Rfc5424<T, Rfc5424Full>
     fn format(&self,
               io: &mut io::Write,
               record: &Record,
-              logger_values: &OwnedKeyValueList)
+              logger_values: &OwnedKeyValueList,
+              options: &FormatOptions)
               -> io::Result<()> {
 
         self.format_header(io, record, logger_values)?; // HEADER
 
         write_sp!(io)?; // SP
 
-        // MESSAGE STRUCTURED_DATA
-
-        write!(io, "{}", "[")?;
-        write!(io, "{}{}", "msg@", record.line())?;
-        let mut serializer = KsvSerializerQuotedValue::new(io, "=");
-        for &(k, v) in record.values().iter().rev() {
-            serializer.emit_delimiter()?;
-            v.serialize(record, k, &mut serializer)?;
+        // MESSAGE STRUCTURED_DATA, NILVALUE without any SD-ELEMENT
+        let mut elements = Vec::<u8>::new();
+        self.format_auto_elements(&mut elements, options)?;
+        match options.fields.order {
+            FieldOrder::RecordFirst => {
+                self.format_record_element(&mut elements, record, options)?;
+                self.format_logger_element(&mut elements, record, logger_values, options)?;
+            }
+            FieldOrder::LoggerFirst => {
+                self.format_logger_element(&mut elements, record, logger_values, options)?;
+                self.format_record_element(&mut elements, record, options)?;
+            }
         }
-        let mut io = serializer.finish();
-        write!(io, "{}", "]")?;
-
-        write!(io, "{}", "[")?;
-        write!(io, "{}{}", "logger@", record.line())?;
-        let mut serializer = KsvSerializerQuotedValue::new(io, "=");
-        for (k, v) in logger_values.iter() {
-            serializer.emit_delimiter()?;
-            v.serialize(record, k, &mut serializer)?;
+        for index in 0..options.sd_elements.len() {
+            self.format_mapped_element(&mut elements, index, record, logger_values, options)?;
+        }
+        if let Some(ref source) = options.source_location {
+            self.format_source_element(&mut elements, record, options, source)?;
+        }
+        if elements.is_empty() {
+            write_nilvalue!(io)?;
+        } else {
+            io.write_all(&elements)?;
         }
-        let mut io = serializer.finish();
-        write!(io, "{}", "]")?;
 
         Ok(())
     }
 }
+
+impl<T> Rfc5424<T, Rfc5424Full>
+    where T: FormatTimestamp
+{
+    // SD-ELEMENT with record values, omitted without any
+    fn format_record_element(&self,
+                             io: &mut io::Write,
+                             record: &Record,
+                             options: &FormatOptions)
+                             -> io::Result<()> {
+        let mut serializer = KsvSerializerQuotedValue::new(Vec::<u8>::new(), "=");
+        visit_record_values(record, options, |key, name, value| {
+            if options.sd_element_index(key).is_some() {
+                return Ok(());
+            }
+            serialize_pair(&mut serializer, record, key, name, value, options)
+        })?;
+        write_element(io, &options.record_sd_id, &serializer.finish())
+    }
+
+    // SD-ELEMENT with logger values, omitted without any
+    fn format_logger_element(&self,
+                             io: &mut io::Write,
+                             record: &Record,
+                             logger_values: &OwnedKeyValueList,
+                             options: &FormatOptions)
+                             -> io::Result<()> {
+        let mut serializer = KsvSerializerQuotedValue::new(Vec::<u8>::new(), "=");
+        visit_logger_values(record, logger_values, options, |key, name, value| {
+            if options.sd_element_index(key).is_some() {
+                return Ok(());
+            }
+            serialize_pair(&mut serializer, record, key, name, value, options)
+        })?;
+        write_element(io, &options.logger_sd_id, &serializer.finish())
+    }
+
+    // Configured SD-ELEMENT, omitted when none of its keys are present
+    fn format_mapped_element(&self,
+                             io: &mut io::Write,
+                             index: usize,
+                             record: &Record,
+                             logger_values: &OwnedKeyValueList,
+                             options: &FormatOptions)
+                             -> io::Result<()> {
+        let element = &options.sd_elements[index];
+        let mut serializer = KsvSerializerQuotedValue::new(Vec::<u8>::new(), "=");
+        visit_values(record, logger_values, options, |key, _, value| {
+            if options.sd_element_index(key) != Some(index) {
+                return Ok(());
+            }
+            let name = element.param_name(key).unwrap_or(key);
+            serialize_pair(&mut serializer, record, key, name, value, options)
+        })?;
+        write_element(io, &element.id, &serializer.finish())
+    }
+
+    // Source location SD-ELEMENT, omitted for records without a file.
+    // Keys are allowed and redacted with the prefix, PARAM-NAMEs are without it.
+    fn format_source_element(&self,
+                             io: &mut io::Write,
+                             record: &Record,
+                             options: &FormatOptions,
+                             source: &SourceLocation)
+                             -> io::Result<()> {
+        let mut serializer = KsvSerializerQuotedValue::new(Vec::<u8>::new(), "=");
+        visit_source_values(record, options, |key, _, value| {
+            if options.sd_element_index(key).is_some() {
+                return Ok(());
+            }
+            let name = &key[source.prefix.len()..];
+            serialize_pair(&mut serializer, record, key, name, value, options)
+        })?;
+        write_element(io, &source.sd_id, &serializer.finish())
+    }
+}
+
+// SD-ELEMENT with the serialized SD-PARAMs, omitted without any
+fn write_element(io: &mut io::Write, sd_id: &str, params: &[u8]) -> io::Result<()> {
+    if params.is_empty() {
+        return Ok(());
+    }
+    write!(io, "[{}", sd_id)?;
+    io.write_all(params)?;
+    write!(io, "{}", "]")
+}
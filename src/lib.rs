@@ -3,6 +3,7 @@
 //! TODO Syslog drain for slog
 //!
 
+#[cfg_attr(feature = "log", macro_use)]
 extern crate slog;
 extern crate chrono;
 extern crate libc;
@@ -12,6 +13,9 @@ extern crate slog_stream;
 extern crate rustls;
 extern crate webpki;
 extern crate webpki_roots;
+extern crate regex;
+#[cfg(feature = "log")]
+extern crate log;
 
 /// TODO
 pub mod config;
@@ -23,16 +27,53 @@ pub mod posix;
 mod time;
 mod format;
 mod serializers;
+mod config_loader;
+mod drain;
+mod error;
+mod metrics;
+mod spool;
+mod structured_data;
+mod failover_drain;
+mod fallback_drain;
+mod fields;
+mod multi_drain;
+mod parser;
+mod rate_limit;
+mod redaction;
+mod relay;
+mod libc_drain;
+#[cfg(feature = "log")]
+mod log_bridge;
 mod uds_drain;
 mod udp_drain;
 mod tcp_drain;
 mod tls_drain;
 mod tls_client;
+mod tls_server;
 
 pub use self::config::*;
+pub use self::config_loader::*;
+pub use self::drain::*;
+pub use self::error::*;
+pub use self::failover_drain::*;
+pub use self::fallback_drain::*;
+pub use self::fields::*;
 pub use self::format::*;
-pub use self::posix::{get_pid, get_process_name, get_host_name};
+pub use self::libc_drain::*;
+#[cfg(feature = "log")]
+pub use self::log_bridge::*;
+pub use self::metrics::{ConnectionState, DrainStats, ErrorCounts, StateChangeHandler};
+pub use self::multi_drain::*;
+pub use self::parser::*;
+pub use self::posix::{HOSTNAME_FALLBACK, HostnameSource, ProcessNameSource, get_fqdn,
+                      get_host_name, get_host_name_from, get_pid, get_primary_address,
+                      get_process_name, get_process_name_from, sanitize_hostname};
+pub use self::rate_limit::*;
+pub use self::redaction::*;
+pub use self::relay::*;
 pub use self::serializers::*;
+pub use self::spool::*;
+pub use self::structured_data::*;
 pub use self::syslog::*;
 pub use self::tcp_drain::*;
 pub use self::time::*;
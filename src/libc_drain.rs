@@ -0,0 +1,183 @@
+use error::{SyslogError, SyslogResult};
+use format::{FormatMessage, FormatOptions, MessageWithKsv};
+use libc;
+use slog::{Drain, OwnedKeyValueList, Record};
+use std::ffi::CString;
+use syslog::{Facility, Severity};
+
+/// State: `LibcSyslogDisconnected` for the libc syslog drain
+#[derive(Debug)]
+pub struct LibcSyslogDisconnected {
+    ident: CString,
+    facility: Facility,
+    options: libc::c_int,
+    mask: Option<Severity>,
+}
+
+/// State: `LibcSyslogConnected` for the libc syslog drain
+///
+/// Calls `closelog` when dropped.
+#[derive(Debug)]
+pub struct LibcSyslogConnected {
+    ident: CString,
+    facility: Facility,
+    options: libc::c_int,
+    mask: Option<Severity>,
+}
+
+impl Drop for LibcSyslogConnected {
+    fn drop(&mut self) {
+        unsafe { libc::closelog() }
+    }
+}
+
+/// Drain passing records to the platform `syslog(3)`
+///
+/// Message bodies are formatted with [MessageWithKsv](struct.MessageWithKsv.html),
+/// headers are left to libc, so they are identical to the ones of any other
+/// program on the host. `openlog` settings are process global, so only one
+/// connected `LibcSyslogDrain` should exist at a time.
+#[derive(Debug)]
+pub struct LibcSyslogDrain<C> {
+    connection: C,
+    format_options: FormatOptions,
+}
+
+// Strip NUL bytes, which C strings can't carry
+fn to_c_string(bytes: Vec<u8>) -> CString {
+    let mut bytes = bytes;
+    bytes.retain(|b| *b != 0);
+    CString::new(bytes).expect("NUL bytes are stripped")
+}
+
+// Equivalent of LOG_UPTO macro
+fn log_upto(severity: Severity) -> libc::c_int {
+    (1 << (severity as libc::c_int + 1)) - 1
+}
+
+// libc facility codes are shifted by 3 bits, to be OR-ed with severity
+fn facility_code(facility: Facility) -> libc::c_int {
+    (facility as libc::c_int) << 3
+}
+
+impl LibcSyslogDrain<LibcSyslogDisconnected> {
+    /// LibcSyslogDrain constructor
+    ///
+    /// `ident` is prepended to every message, usually the program name.
+    pub fn new(ident: &str, facility: Facility) -> LibcSyslogDrain<LibcSyslogDisconnected> {
+        LibcSyslogDrain {
+            connection: LibcSyslogDisconnected {
+                ident: to_c_string(ident.as_bytes().to_vec()),
+                facility: facility,
+                options: 0,
+                mask: None,
+            },
+            format_options: FormatOptions::default(),
+        }
+    }
+
+    fn option(mut self, option: libc::c_int, value: bool) -> Self {
+        if value {
+            self.connection.options |= option;
+        } else {
+            self.connection.options &= !option;
+        }
+        self
+    }
+
+    /// `LOG_PID`: include pid with every message
+    pub fn log_pid(self, value: bool) -> Self {
+        self.option(libc::LOG_PID, value)
+    }
+
+    /// `LOG_CONS`: write to system console, if message can't be sent to syslog
+    pub fn log_cons(self, value: bool) -> Self {
+        self.option(libc::LOG_CONS, value)
+    }
+
+    /// `LOG_NDELAY`: open connection immediately, instead of on the first message
+    pub fn log_ndelay(self, value: bool) -> Self {
+        self.option(libc::LOG_NDELAY, value)
+    }
+
+    /// `LOG_PERROR`: also write messages to stderr
+    pub fn log_perror(self, value: bool) -> Self {
+        self.option(libc::LOG_PERROR, value)
+    }
+
+    /// `setlogmask(LOG_UPTO(severity))`: drop messages less severe than `severity`
+    pub fn mask_up_to(mut self, severity: Severity) -> Self {
+        self.connection.mask = Some(severity);
+        self
+    }
+
+    /// Message serialization options [FormatOptions](struct.FormatOptions.html)
+    pub fn format_options<VALUE: Into<FormatOptions>>(mut self, value: VALUE) -> Self {
+        self.format_options = value.into();
+        self
+    }
+
+    /// Call `openlog` (and `setlogmask` if configured)
+    pub fn connect(self) -> SyslogResult<LibcSyslogDrain<LibcSyslogConnected>> {
+        let connection = self.connection;
+
+        unsafe {
+            if let Some(severity) = connection.mask {
+                libc::setlogmask(log_upto(severity));
+            }
+            libc::openlog(connection.ident.as_ptr(),
+                          connection.options,
+                          facility_code(connection.facility));
+        }
+
+        Ok(LibcSyslogDrain {
+               connection: LibcSyslogConnected {
+                   ident: connection.ident,
+                   facility: connection.facility,
+                   options: connection.options,
+                   mask: connection.mask,
+               },
+               format_options: self.format_options,
+           })
+    }
+}
+
+impl LibcSyslogDrain<LibcSyslogConnected> {
+    /// Call `closelog`
+    pub fn disconnect(self) -> SyslogResult<LibcSyslogDrain<LibcSyslogDisconnected>> {
+        let connection = LibcSyslogDisconnected {
+            ident: self.connection.ident.clone(),
+            facility: self.connection.facility,
+            options: self.connection.options,
+            mask: self.connection.mask,
+        };
+        // Dropping connected state closes the log
+        drop(self.connection);
+        Ok(LibcSyslogDrain {
+               connection: connection,
+               format_options: self.format_options,
+           })
+    }
+}
+
+impl Drain for LibcSyslogDrain<LibcSyslogConnected> {
+    type Error = SyslogError;
+
+    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<()> {
+        let mut buf = Vec::<u8>::with_capacity(4096);
+        MessageWithKsv::format(&mut buf, info, logger_values, &self.format_options)
+            .map_err(SyslogError::FormatFailed)?;
+        let message = to_c_string(buf);
+
+        let severity: Severity = info.level().into();
+        let priority = facility_code(self.connection.facility) | severity as libc::c_int;
+
+        unsafe {
+            libc::syslog(priority,
+                         b"%s\0".as_ptr() as *const libc::c_char,
+                         message.as_ptr());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use error::SyslogError;
+use log::{self, Log, LogLevel, LogLevelFilter, LogMetadata, LogRecord, SetLoggerError};
+use slog::{Drain, DrainExt, Level, Logger, OwnedKeyValueList, Record, RecordStatic};
+use slog::ser::Serialize;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use syslog::Severity;
+
+impl From<LogLevel> for Severity {
+    fn from(level: LogLevel) -> Severity {
+        match level {
+            LogLevel::Error => Severity::LOG_ERR,
+            LogLevel::Warn => Severity::LOG_WARN,
+            LogLevel::Info => Severity::LOG_INFO,
+            LogLevel::Debug | LogLevel::Trace => Severity::LOG_DEBUG,
+        }
+    }
+}
+
+// slog level of the same severity
+fn level_of(level: LogLevel) -> Level {
+    match level {
+        LogLevel::Error => Level::Error,
+        LogLevel::Warn => Level::Warning,
+        LogLevel::Info => Level::Info,
+        LogLevel::Debug => Level::Debug,
+        LogLevel::Trace => Level::Trace,
+    }
+}
+
+// Counts errors of the wrapped drain instead of returning them,
+// `log` has no way to report them and `.fuse()` would panic
+struct CountFailures<D> {
+    drain: D,
+    failures: Arc<AtomicUsize>,
+}
+
+impl<D> Drain for CountFailures<D>
+    where D: Drain
+{
+    type Error = SyslogError;
+
+    fn log(&self, record: &Record, logger_values: &OwnedKeyValueList) -> Result<(), SyslogError> {
+        if self.drain.log(record, logger_values).is_err() {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+/// `log` crate logger sending records through a syslog drain
+///
+/// Records of the `log` macros get the same headers as the slog ones, their
+/// location goes to the `log.target`, `log.module_path`, `log.file` and `log.line`
+/// keys: key=value pairs, or the record SD-ELEMENT of RFC5424 native serialization
+/// (`FormatOptions::record_sd_id`), unless an element takes them, e.g.
+/// `SdElement::new("log@32473").prefix("log.")`. Record and logger elements left
+/// without values are omitted.
+///
+/// ```ignore
+/// let drain = syslog().mode(FormatMode::RFC5424).connect()?;
+/// SyslogLog::new(drain).max_level(LogLevelFilter::Debug).init()?;
+/// ```
+pub struct SyslogLog {
+    logger: Logger,
+    max_level: LogLevelFilter,
+    failures: Arc<AtomicUsize>,
+}
+
+impl SyslogLog {
+    /// Logger sending through the drain, e.g. the one returned by `SyslogConfig::connect`
+    pub fn new<D>(drain: D) -> SyslogLog
+        where D: Drain + Send + Sync + 'static
+    {
+        let failures = Arc::new(AtomicUsize::new(0));
+        let drain = CountFailures {
+            drain: drain,
+            failures: failures.clone(),
+        };
+        SyslogLog {
+            logger: Logger::root(drain.fuse(), o!()),
+            max_level: LogLevelFilter::Info,
+            failures: failures,
+        }
+    }
+
+    /// Most verbose level sent, less severe records are discarded
+    ///
+    /// Default: `Info`
+    pub fn max_level<VALUE: Into<LogLevelFilter>>(mut self, value: VALUE) -> Self {
+        self.max_level = value.into();
+        self
+    }
+
+    /// Number of records the drain failed to send
+    pub fn failures(&self) -> usize {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Install as the global `log` logger, with `max_level` as the global max level
+    ///
+    /// Fails if a logger was installed already.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_logger(|max_level| {
+                            max_level.set(self.max_level);
+                            Box::new(self)
+                        })
+    }
+}
+
+impl fmt::Debug for SyslogLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "SyslogLog {{ max_level: {:?}, failures: {} }}",
+               self.max_level,
+               self.failures())
+    }
+}
+
+impl Log for SyslogLog {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        metadata.level() <= self.max_level
+    }
+
+    fn log(&self, record: &LogRecord) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        // `log` locations aren't `'static`, they go to the values instead
+        let record_static = RecordStatic {
+            level: level_of(record.level()),
+            file: "",
+            line: 0,
+            column: 0,
+            function: "",
+            module: "",
+            target: "",
+        };
+        let target = record.target().to_string();
+        let module_path = record.location().module_path().to_string();
+        let file = record.location().file().to_string();
+        let line = record.location().line();
+        let values: &[(&'static str, &Serialize)] = &[("log.target", &target),
+                                                      ("log.module_path", &module_path),
+                                                      ("log.file", &file),
+                                                      ("log.line", &line)];
+        self.logger.log(&Record::new(&record_static, format_args!("{}", record.args()), values));
+    }
+}
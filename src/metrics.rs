@@ -0,0 +1,207 @@
+use error::{SyslogError, SyslogResult};
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Connection state reported to the state change handler
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ConnectionState {
+    /// Drain connected, or sent a message after failing
+    Connected,
+    /// Sending failed, or drain was disconnected
+    Disconnected,
+}
+
+/// Handler called with the new state and the destination, when drain connection state changes
+///
+/// Called from the logging thread, it should return quickly.
+pub type StateChangeHandler = Fn(ConnectionState, &str) + Send + Sync;
+
+/// Failed sends by kind of the error
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ErrorCounts {
+    /// Failures to connect or reconnect
+    pub connect: usize,
+    /// Failures to send a message
+    pub send: usize,
+    /// Messages too large for the transport
+    pub too_large: usize,
+    /// Failures to format a record
+    pub format: usize,
+    /// Other failures
+    pub other: usize,
+}
+
+impl ErrorCounts {
+    /// Errors of all kinds
+    pub fn total(&self) -> usize {
+        self.connect + self.send + self.too_large + self.format + self.other
+    }
+}
+
+/// Drain metrics snapshot
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DrainStats {
+    /// Messages sent
+    pub records_sent: usize,
+    /// Bytes sent, including framing
+    pub bytes_sent: usize,
+    /// Errors by kind
+    pub errors: ErrorCounts,
+    /// Successful reconnects
+    pub reconnects: usize,
+    /// Messages truncated to fit the transport
+    pub truncated: usize,
+    /// Messages dropped because the receiving queue was full
+    pub dropped: usize,
+    /// Whether the drain is connected, as of the last send
+    pub connected: bool,
+}
+
+impl DrainStats {
+    /// Add up metrics of another drain, connected if any of them is
+    pub fn merge(&mut self, other: &DrainStats) {
+        self.records_sent += other.records_sent;
+        self.bytes_sent += other.bytes_sent;
+        self.errors.connect += other.errors.connect;
+        self.errors.send += other.errors.send;
+        self.errors.too_large += other.errors.too_large;
+        self.errors.format += other.errors.format;
+        self.errors.other += other.errors.other;
+        self.reconnects += other.reconnects;
+        self.truncated += other.truncated;
+        self.dropped += other.dropped;
+        self.connected = self.connected || other.connected;
+    }
+}
+
+/// Sum of metrics of the drains, `None` if none of them keeps any
+pub fn merged_stats<I>(stats: I) -> Option<DrainStats>
+    where I: IntoIterator<Item = Option<DrainStats>>
+{
+    let mut merged: Option<DrainStats> = None;
+    for stats in stats.into_iter().filter_map(|stats| stats) {
+        if let Some(ref mut total) = merged {
+            total.merge(&stats);
+            continue;
+        }
+        merged = Some(stats);
+    }
+    merged
+}
+
+/// Drain metrics counters, kept by the drain across its states
+#[derive(Default)]
+pub struct DrainMetrics {
+    records_sent: AtomicUsize,
+    bytes_sent: AtomicUsize,
+    connect_errors: AtomicUsize,
+    send_errors: AtomicUsize,
+    too_large_errors: AtomicUsize,
+    format_errors: AtomicUsize,
+    other_errors: AtomicUsize,
+    reconnects: AtomicUsize,
+    truncated: AtomicUsize,
+    dropped: AtomicUsize,
+    connected: AtomicBool,
+    handler: Option<Arc<StateChangeHandler>>,
+}
+
+impl DrainMetrics {
+    /// Set state change handler
+    pub fn set_handler(&mut self, handler: Arc<StateChangeHandler>) {
+        self.handler = Some(handler);
+    }
+
+    /// Metrics snapshot
+    pub fn snapshot(&self) -> DrainStats {
+        DrainStats {
+            records_sent: self.records_sent.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            errors: ErrorCounts {
+                connect: self.connect_errors.load(Ordering::Relaxed),
+                send: self.send_errors.load(Ordering::Relaxed),
+                too_large: self.too_large_errors.load(Ordering::Relaxed),
+                format: self.format_errors.load(Ordering::Relaxed),
+                other: self.other_errors.load(Ordering::Relaxed),
+            },
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            truncated: self.truncated.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            connected: self.connected.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Record connection state, calling the handler if it has changed
+    pub fn set_state(&self, state: ConnectionState, destination: &fmt::Display) {
+        let connected = state == ConnectionState::Connected;
+        if self.connected.swap(connected, Ordering::Relaxed) != connected {
+            if let Some(ref handler) = self.handler {
+                handler(state, &destination.to_string());
+            }
+        }
+    }
+
+    /// Record message sent
+    pub fn sent(&self, bytes: usize, destination: &fmt::Display) {
+        self.records_sent.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+        self.set_state(ConnectionState::Connected, destination);
+    }
+
+    /// Record successful reconnect
+    pub fn reconnected(&self, destination: &fmt::Display) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+        self.set_state(ConnectionState::Connected, destination);
+    }
+
+    /// Record message truncated to fit the transport
+    pub fn truncated(&self) {
+        self.truncated.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record message dropped because the receiving queue was full
+    pub fn dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record error by its kind
+    pub fn error(&self, err: &SyslogError) {
+        let counter = match *err {
+            SyslogError::ResolveFailed { .. } |
+            SyslogError::ConnectFailed { .. } |
+            SyslogError::Tls { .. } => &self.connect_errors,
+            SyslogError::SendFailed { .. } => &self.send_errors,
+            SyslogError::MessageTooLarge { .. } => &self.too_large_errors,
+            SyslogError::FormatFailed(_) => &self.format_errors,
+            _ => &self.other_errors,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record error of the result, if any
+    pub fn record<T>(&self, result: SyslogResult<T>) -> SyslogResult<T> {
+        if let Err(ref err) = result {
+            self.error(err);
+        }
+        result
+    }
+
+    /// Record error of connecting or sending, if any, marking the drain disconnected
+    pub fn record_connection<T>(&self,
+                                result: SyslogResult<T>,
+                                destination: &fmt::Display)
+                                -> SyslogResult<T> {
+        if let Err(ref err) = result {
+            self.error(err);
+            self.set_state(ConnectionState::Disconnected, destination);
+        }
+        result
+    }
+}
+
+impl fmt::Debug for DrainMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DrainMetrics {{ {:?} }}", self.snapshot())
+    }
+}
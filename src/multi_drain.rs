@@ -0,0 +1,331 @@
+use drain::{BoxedSyslogDrain, SyslogDrain};
+use error::{SyslogError, SyslogResult};
+use metrics::{DrainStats, merged_stats};
+use serializers::KsvSerializerUnquoted;
+use slog::{Drain, OwnedKeyValueList, Record};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::Hasher;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use syslog::Severity;
+
+/// How `MultiDrain` distributes records across destinations
+#[derive(Debug, Clone, PartialEq)]
+pub enum Distribution {
+    /// Every record goes to the next destination in turn
+    RoundRobin,
+    /// Records with the same value of the key go to the same destination.
+    ///
+    /// Key is looked up in record values first, then in logger values.
+    /// Records without the key, and formatted messages passed to
+    /// `SyslogDrain::send`, e.g. on spool replay, are distributed round robin.
+    HashByKey(String),
+    /// Every record is copied to all the destinations accepting it
+    Broadcast,
+}
+
+impl Default for Distribution {
+    fn default() -> Distribution {
+        Distribution::RoundRobin
+    }
+}
+
+/// Callback for destination errors: destination index and the error
+pub type DestinationErrorHandler = Box<Fn(usize, &SyslogError) + Send + Sync>;
+
+/// Predicate of `RecordFilter::Custom`
+pub type RecordPredicate = Box<Fn(&Record, &OwnedKeyValueList) -> bool + Send + Sync>;
+
+/// Records a `MultiDrain` destination accepts, e.g. to copy security records
+/// to a second destination with `Distribution::Broadcast`
+pub enum RecordFilter {
+    /// Records at least as severe as the severity
+    MinSeverity(Severity),
+    /// Records with the key in record or logger values
+    HasKey(String),
+    /// Records where the key, looked up like `HasKey`, has the value
+    KeyValue(String, String),
+    /// Records the predicate returns `true` for
+    Custom(RecordPredicate),
+}
+
+impl RecordFilter {
+    /// Whether the destination takes the record
+    pub fn accepts(&self, record: &Record, logger_values: &OwnedKeyValueList) -> bool {
+        match *self {
+            RecordFilter::MinSeverity(severity) => {
+                Severity::from(record.level()) as u8 <= severity as u8
+            }
+            RecordFilter::HasKey(ref key) => find_value(key, record, logger_values).is_some(),
+            RecordFilter::KeyValue(ref key, ref value) => {
+                find_value(key, record, logger_values)
+                    .map(|found| found == value.as_bytes())
+                    .unwrap_or(false)
+            }
+            RecordFilter::Custom(ref predicate) => predicate(record, logger_values),
+        }
+    }
+}
+
+impl fmt::Debug for RecordFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecordFilter::MinSeverity(ref severity) => write!(f, "MinSeverity({:?})", severity),
+            RecordFilter::HasKey(ref key) => write!(f, "HasKey({:?})", key),
+            RecordFilter::KeyValue(ref key, ref value) => {
+                write!(f, "KeyValue({:?}, {:?})", key, value)
+            }
+            RecordFilter::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+// Serialized value of the key, looked up in record values first, then in logger values
+fn find_value(key: &str, record: &Record, logger_values: &OwnedKeyValueList) -> Option<Vec<u8>> {
+    let serialize = |k: &str, v: &::slog::ser::Serialize| {
+        let mut serializer = KsvSerializerUnquoted::new(Vec::<u8>::new(), "");
+        v.serialize(record, k, &mut serializer).ok().map(|_| {
+            let mut serialized = serializer.finish();
+            // Drop the key, the separator is empty
+            serialized.drain(..k.len());
+            serialized
+        })
+    };
+
+    for &(k, v) in record.values().iter() {
+        if k == key {
+            return serialize(k, v);
+        }
+    }
+    for (k, v) in logger_values.iter() {
+        if k == key {
+            return serialize(k, v);
+        }
+    }
+    None
+}
+
+/// Drain sending records to multiple syslog destinations
+///
+/// When a destination fails, round robin and hash distributions
+/// move on to the next destination, broadcast just skips it.
+/// Failures are counted per destination and reported to the error handler,
+/// the record is failed only when no destination accepted it.
+///
+/// Destinations with a [RecordFilter](enum.RecordFilter.html) only get the records
+/// it accepts, records no destination accepts are discarded. Formatted messages
+/// passed to `SyslogDrain::send` carry no record, they only go to the destinations
+/// without a filter.
+pub struct MultiDrain {
+    destinations: Vec<BoxedSyslogDrain>,
+    filters: Vec<Option<RecordFilter>>,
+    distribution: Distribution,
+    next: AtomicUsize,
+    errors: Vec<AtomicUsize>,
+    on_error: Option<DestinationErrorHandler>,
+}
+
+impl MultiDrain {
+    /// MultiDrain constructor
+    pub fn new(destinations: Vec<BoxedSyslogDrain>, distribution: Distribution) -> MultiDrain {
+        let errors = destinations.iter().map(|_| AtomicUsize::new(0)).collect();
+        let filters = destinations.iter().map(|_| None).collect();
+        MultiDrain {
+            destinations: destinations,
+            filters: filters,
+            distribution: distribution,
+            next: AtomicUsize::new(0),
+            errors: errors,
+            on_error: None,
+        }
+    }
+
+    /// Callback invoked with the destination index on every destination error
+    pub fn on_error<H>(mut self, handler: H) -> Self
+        where H: Fn(usize, &SyslogError) + Send + Sync + 'static
+    {
+        self.on_error = Some(Box::new(handler));
+        self
+    }
+
+    /// Records the destination at the index takes, it gets all of them by default
+    pub fn filter(mut self, index: usize, filter: RecordFilter) -> Self {
+        if let Some(slot) = self.filters.get_mut(index) {
+            *slot = Some(filter);
+        }
+        self
+    }
+
+    /// Number of errors for every destination, in order
+    pub fn errors(&self) -> Vec<usize> {
+        self.errors
+            .iter()
+            .map(|e| e.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    fn report(&self, index: usize, err: &SyslogError) {
+        self.errors[index].fetch_add(1, Ordering::Relaxed);
+        if let Some(ref on_error) = self.on_error {
+            on_error(index, err);
+        }
+    }
+
+    fn next_index(&self) -> usize {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+
+    // Destination index for a record, by its key value
+    fn hash_index(&self, key: &str, record: &Record, logger_values: &OwnedKeyValueList) -> usize {
+        match find_value(key, record, logger_values) {
+            Some(value) => {
+                let mut hasher = DefaultHasher::new();
+                hasher.write(value.as_slice());
+                hasher.finish() as usize
+            }
+            None => self.next_index(),
+        }
+    }
+
+    // Whether the destination takes the record, `None` for formatted messages
+    fn accepts(&self, index: usize, record: Option<(&Record, &OwnedKeyValueList)>) -> bool {
+        match (&self.filters[index], record) {
+            (&None, _) => true,
+            (&Some(ref filter), Some((record, logger_values))) => {
+                filter.accepts(record, logger_values)
+            }
+            (&Some(_), None) => false,
+        }
+    }
+
+    // Try accepting destinations starting with `first`, until one of them takes the record
+    fn send_to_one<S>(&self,
+                      first: usize,
+                      record: Option<(&Record, &OwnedKeyValueList)>,
+                      mut send: S)
+                      -> SyslogResult<()>
+        where S: FnMut(&BoxedSyslogDrain) -> SyslogResult<()>
+    {
+        let count = self.destinations.len();
+        if count == 0 {
+            return Err(SyslogError::NoDestination);
+        }
+        let mut last_error = None;
+
+        for offset in 0..count {
+            let index = (first % count + offset) % count;
+            if !self.accepts(index, record) {
+                continue;
+            }
+            match send(&self.destinations[index]) {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    self.report(index, &err);
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        match last_error {
+            Some(err) => Err(err),
+            // No destination takes the record
+            None => Ok(()),
+        }
+    }
+
+    // Send record to every accepting destination, succeeding if any of them takes it
+    fn send_to_all<S>(&self, record: Option<(&Record, &OwnedKeyValueList)>, mut send: S)
+                      -> SyslogResult<()>
+        where S: FnMut(&BoxedSyslogDrain) -> SyslogResult<()>
+    {
+        if self.destinations.is_empty() {
+            return Err(SyslogError::NoDestination);
+        }
+        let mut delivered = false;
+        let mut last_error = None;
+
+        for (index, destination) in self.destinations.iter().enumerate() {
+            if !self.accepts(index, record) {
+                continue;
+            }
+            match send(destination) {
+                Ok(()) => delivered = true,
+                Err(err) => {
+                    self.report(index, &err);
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        match (delivered, last_error) {
+            (true, _) => Ok(()),
+            (false, Some(err)) => Err(err),
+            // No destination takes the record
+            (false, None) => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for MultiDrain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "MultiDrain {{ destinations: {}, distribution: {:?}, errors: {:?} }}",
+               self.destinations.len(),
+               self.distribution,
+               self.errors())
+    }
+}
+
+impl SyslogDrain for MultiDrain {
+    // Destinations are expected to share the format, first one is used
+    fn format(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        match self.destinations.first() {
+            Some(destination) => destination.format(info, logger_values),
+            None => Err(SyslogError::NoDestination),
+        }
+    }
+
+    // Formatted message doesn't carry the record, so hash distribution
+    // falls back to round robin and only destinations without a filter take it
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        match self.distribution {
+            Distribution::Broadcast => self.send_to_all(None, |drain| drain.send(message)),
+            _ => self.send_to_one(self.next_index(), None, |drain| drain.send(message)),
+        }
+    }
+
+    fn reconnect(&self) -> SyslogResult<()> {
+        for (index, destination) in self.destinations.iter().enumerate() {
+            if let Err(err) = destination.reconnect() {
+                self.report(index, &err);
+            }
+        }
+        Ok(())
+    }
+
+    // Metrics of all the destinations added up
+    fn stats(&self) -> Option<DrainStats> {
+        merged_stats(self.destinations.iter().map(|d| d.stats()))
+    }
+}
+
+impl Drain for MultiDrain {
+    type Error = SyslogError;
+
+    // Every destination formats the record with its own formatter
+    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<()> {
+        let send = |drain: &BoxedSyslogDrain| {
+            let buf = drain.format(info, logger_values)?;
+            drain.send(buf.as_slice())
+        };
+
+        let record = Some((info, logger_values));
+        match self.distribution {
+            Distribution::RoundRobin => self.send_to_one(self.next_index(), record, send),
+            Distribution::HashByKey(ref key) => {
+                self.send_to_one(self.hash_index(key, info, logger_values), record, send)
+            }
+            Distribution::Broadcast => self.send_to_all(record, send),
+        }
+    }
+}
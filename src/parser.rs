@@ -0,0 +1,387 @@
+use chrono;
+use chrono::{Datelike, TimeZone};
+use config::FormatMode;
+use error::{SyslogError, SyslogResult};
+use structured_data::{escape_param_value, validate_sd_name};
+
+/// Largest PRI value: facility 23, severity 7
+pub const MAX_PRIORITY: u8 = 191;
+
+/// PRI of messages without a valid one, `user.notice` as RFC3164 relays use
+pub const DEFAULT_PRIORITY: u8 = 13;
+
+const MONTHS: &'static [&'static str] = &["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug",
+                                          "Sep", "Oct", "Nov", "Dec"];
+
+// UTF-8 byte order mark, which may start RFC5424 MSG
+const BOM: char = '\u{feff}';
+
+/// Syslog message parsed from RFC3164 or RFC5424 text
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyslogMessage {
+    /// Format the message was received in
+    pub mode: FormatMode,
+    /// PRI: facility * 8 + severity
+    pub priority: u8,
+    /// TIMESTAMP as received: RFC3164 `Mmm dd hh:mm:ss`, or RFC3339
+    pub timestamp: Option<String>,
+    /// HOSTNAME
+    pub hostname: Option<String>,
+    /// RFC5424 APP-NAME, name part of RFC3164 TAG
+    pub app_name: Option<String>,
+    /// RFC5424 PROCID, `[pid]` part of RFC3164 TAG
+    pub proc_id: Option<String>,
+    /// RFC5424 MSGID
+    pub msg_id: Option<String>,
+    /// RFC5424 SD-ELEMENTs: SD-ID and its parameters
+    pub structured_data: Vec<(String, Vec<(String, String)>)>,
+    /// MSG
+    pub message: String,
+}
+
+fn parse_failed(reason: &str) -> SyslogError {
+    SyslogError::ParseFailed(reason.to_string())
+}
+
+// Split the next space separated field off the text
+fn next_field(text: &str) -> (&str, &str) {
+    match text.find(' ') {
+        Some(index) => (&text[..index], &text[index + 1..]),
+        None => (text, ""),
+    }
+}
+
+// Field value, `None` for NILVALUE
+fn nil(field: &str) -> Option<String> {
+    if field.is_empty() || field == "-" {
+        None
+    } else {
+        Some(field.to_string())
+    }
+}
+
+fn is_rfc3164_timestamp(timestamp: &str) -> bool {
+    MONTHS.iter().any(|month| timestamp.starts_with(month))
+}
+
+// Length of the timestamp at the start of RFC3164 text: `Mmm dd hh:mm:ss`,
+// or RFC3339 sent by this crate's ISO timestamp formatters
+fn timestamp_len(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let digit = |index: usize| bytes[index] >= b'0' && bytes[index] <= b'9';
+
+    if bytes.len() >= 15 && is_rfc3164_timestamp(text) && bytes[3] == b' ' &&
+       (bytes[4] == b' ' || digit(4)) && digit(5) && bytes[6] == b' ' && digit(7) &&
+       digit(8) && bytes[9] == b':' && digit(10) && digit(11) && bytes[12] == b':' &&
+       digit(13) && digit(14) {
+        return Some(15);
+    }
+    if bytes.len() >= 19 && (0..4).all(|index| digit(index)) && bytes[4] == b'-' &&
+       bytes[10] == b'T' {
+        return Some(text.find(' ').unwrap_or_else(|| text.len()));
+    }
+    None
+}
+
+// RFC3164 TAG: `name[pid]:`, `name:` or `[pid]:`, and the rest of the text
+fn parse_tag(text: &str) -> (Option<String>, Option<String>, String) {
+    let (field, rest) = next_field(text);
+    if field.len() < 2 || !field.ends_with(':') {
+        return (None, None, text.to_string());
+    }
+    let tag = &field[..field.len() - 1];
+    match tag.find('[') {
+        Some(index) if tag.ends_with(']') => {
+            (nil(&tag[..index]), nil(&tag[index + 1..tag.len() - 1]), rest.to_string())
+        }
+        _ => (Some(tag.to_string()), None, rest.to_string()),
+    }
+}
+
+// PARAM-VALUE after the opening quote, and the text after the closing one
+fn parse_param_value(text: &str) -> SyslogResult<(String, &str)> {
+    let mut value = String::new();
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        if escaped {
+            // Backslash is kept unless it escapes one of the special characters
+            if c != '"' && c != '\\' && c != ']' {
+                value.push('\\');
+            }
+            value.push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            return Ok((value, &text[index + 1..]));
+        } else {
+            value.push(c);
+        }
+    }
+    Err(parse_failed("unterminated PARAM-VALUE"))
+}
+
+// SD-ELEMENT after the opening bracket, and the text after the closing one
+fn parse_sd_element(text: &str) -> SyslogResult<((String, Vec<(String, String)>), &str)> {
+    let id_end = text.find(|c| c == ' ' || c == ']')
+        .ok_or_else(|| parse_failed("unterminated SD-ELEMENT"))?;
+    let id = text[..id_end].to_string();
+    let mut params = Vec::new();
+    let mut rest = &text[id_end..];
+    loop {
+        if rest.starts_with(']') {
+            return Ok(((id, params), &rest[1..]));
+        }
+        if !rest.starts_with(' ') {
+            return Err(parse_failed("SD-PARAM not separated by space"));
+        }
+        rest = &rest[1..];
+        let name_end = rest.find('=').ok_or_else(|| parse_failed("SD-PARAM without value"))?;
+        let name = rest[..name_end].to_string();
+        rest = &rest[name_end + 1..];
+        if !rest.starts_with('"') {
+            return Err(parse_failed("PARAM-VALUE not quoted"));
+        }
+        let (value, after) = parse_param_value(&rest[1..])?;
+        params.push((name, value));
+        rest = after;
+    }
+}
+
+// STRUCTURED-DATA, and the text after it
+fn parse_structured_data(text: &str)
+                         -> SyslogResult<(Vec<(String, Vec<(String, String)>)>, &str)> {
+    if text.starts_with('-') {
+        return Ok((Vec::new(), &text[1..]));
+    }
+    let mut elements = Vec::new();
+    let mut rest = text;
+    while rest.starts_with('[') {
+        let (element, after) = parse_sd_element(&rest[1..])?;
+        elements.push(element);
+        rest = after;
+    }
+    if elements.is_empty() {
+        return Err(parse_failed("missing STRUCTURED-DATA"));
+    }
+    Ok((elements, rest))
+}
+
+fn parse_rfc5424(priority: u8, text: &str) -> SyslogResult<SyslogMessage> {
+    let (timestamp, text) = next_field(text);
+    let (hostname, text) = next_field(text);
+    let (app_name, text) = next_field(text);
+    let (proc_id, text) = next_field(text);
+    let (msg_id, text) = next_field(text);
+    let (structured_data, text) = parse_structured_data(text)?;
+    let message = match text {
+        "" => "",
+        text if text.starts_with(' ') => text[1..].trim_left_matches(BOM),
+        _ => return Err(parse_failed("MSG not separated by space")),
+    };
+
+    Ok(SyslogMessage {
+           mode: FormatMode::RFC5424,
+           priority: priority,
+           timestamp: nil(timestamp),
+           hostname: nil(hostname),
+           app_name: nil(app_name),
+           proc_id: nil(proc_id),
+           msg_id: nil(msg_id),
+           structured_data: structured_data,
+           message: message.to_string(),
+       })
+}
+
+fn parse_rfc3164(priority: u8, text: &str) -> SyslogMessage {
+    // Short headers put a space between PRI and TAG
+    let text = text.trim_left_matches(' ');
+    let (timestamp, text) = match timestamp_len(text) {
+        Some(len) => (Some(text[..len].to_string()), text[len..].trim_left_matches(' ')),
+        None => (None, text),
+    };
+    // Hostname follows the timestamp, unless the next field is already the TAG
+    let (hostname, text) = match next_field(text) {
+        (field, rest) if timestamp.is_some() && !field.is_empty() && !field.ends_with(':') &&
+                         !field.contains('[') => (nil(field), rest),
+        _ => (None, text),
+    };
+    let (app_name, proc_id, message) = parse_tag(text);
+
+    SyslogMessage {
+        mode: FormatMode::RFC3164,
+        priority: priority,
+        timestamp: timestamp,
+        hostname: hostname,
+        app_name: app_name,
+        proc_id: proc_id,
+        msg_id: None,
+        structured_data: Vec::new(),
+        message: message,
+    }
+}
+
+impl SyslogMessage {
+    /// Parse RFC5424 (`<PRI>1 ...`) or RFC3164 message, trailing CR, LF and NUL
+    /// characters are dropped
+    ///
+    /// RFC3164 header fields are optional, so only a missing or invalid PRI fails
+    /// such messages.
+    pub fn parse(bytes: &[u8]) -> SyslogResult<SyslogMessage> {
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim_right_matches(|c| c == '\n' || c == '\r' || c == '\0');
+        if !text.starts_with('<') {
+            return Err(parse_failed("missing PRI"));
+        }
+        let end = text.find('>').ok_or_else(|| parse_failed("unterminated PRI"))?;
+        let priority = match text[1..end].parse::<u8>() {
+            Ok(priority) if end > 1 && end <= 4 && priority <= MAX_PRIORITY => priority,
+            _ => return Err(parse_failed("invalid PRI")),
+        };
+
+        let rest = &text[end + 1..];
+        if rest.starts_with("1 ") {
+            parse_rfc5424(priority, &rest[2..])
+        } else {
+            Ok(parse_rfc3164(priority, rest))
+        }
+    }
+
+    /// RFC3164 message with the whole text as MSG and `user.notice` priority, for text
+    /// which couldn't be parsed
+    pub fn unparsed(bytes: &[u8]) -> SyslogMessage {
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim_right_matches(|c| c == '\n' || c == '\r' || c == '\0');
+        parse_rfc3164(DEFAULT_PRIORITY, text)
+    }
+
+    /// Move `key=value` pairs from the end of MSG to an SD-ELEMENT with the SD-ID,
+    /// e.g. the ones of RFC3164 key=value serialization
+    ///
+    /// Pairs with quoted values or keys which aren't valid SD-NAMEs stay in MSG.
+    pub fn ksv_to_structured_data(mut self, sd_id: &str) -> Self {
+        let mut params = Vec::new();
+        let mut end = self.message.len();
+        while end > 0 {
+            let start = self.message[..end].rfind(' ').map(|index| index + 1).unwrap_or(0);
+            let pair = {
+                let token = &self.message[start..end];
+                match token.find('=') {
+                    Some(index) if validate_sd_name(&token[..index]).is_ok() &&
+                                   !token[index + 1..].contains('"') => {
+                        Some((token[..index].to_string(), token[index + 1..].to_string()))
+                    }
+                    _ => None,
+                }
+            };
+            match pair {
+                Some(pair) => params.push(pair),
+                None => break,
+            }
+            end = start.saturating_sub(1);
+        }
+
+        if !params.is_empty() {
+            params.reverse();
+            self.message.truncate(end);
+            self.structured_data.push((sd_id.to_string(), params));
+        }
+        self
+    }
+
+    // Timestamp as RFC3339, RFC3164 ones are taken as local time of the current year
+    fn rfc3339_timestamp(&self) -> Option<String> {
+        let timestamp = match self.timestamp {
+            Some(ref timestamp) if is_rfc3164_timestamp(timestamp) => timestamp,
+            Some(ref timestamp) => return Some(timestamp.clone()),
+            None => return None,
+        };
+        let text = format!("{} {}", chrono::Local::now().year(), timestamp.replace("  ", " "));
+        chrono::NaiveDateTime::parse_from_str(&text, "%Y %b %d %H:%M:%S")
+            .ok()
+            .and_then(|naive| chrono::Local.from_local_datetime(&naive).single())
+            .map(|datetime| datetime.format("%Y-%m-%dT%H:%M:%S%:z").to_string())
+    }
+
+    // Timestamp in RFC3164 format, local time
+    fn rfc3164_timestamp(&self) -> Option<String> {
+        match self.timestamp {
+            Some(ref timestamp) if is_rfc3164_timestamp(timestamp) => Some(timestamp.clone()),
+            Some(ref timestamp) => {
+                chrono::DateTime::parse_from_rfc3339(timestamp)
+                    .ok()
+                    .map(|datetime| {
+                             datetime.with_timezone(&chrono::Local).format("%b %d %T").to_string()
+                         })
+            }
+            None => None,
+        }
+    }
+
+    /// RFC5424 text of the message, RFC3164 timestamps are converted to RFC3339
+    pub fn to_rfc5424(&self) -> Vec<u8> {
+        let or_nil = |field: &Option<String>| field.clone().unwrap_or_else(|| "-".to_string());
+        let mut text = format!("<{}>1 {} {} {} {} {} ",
+                               self.priority,
+                               or_nil(&self.rfc3339_timestamp()),
+                               or_nil(&self.hostname),
+                               or_nil(&self.app_name),
+                               or_nil(&self.proc_id),
+                               or_nil(&self.msg_id));
+
+        if self.structured_data.is_empty() {
+            text.push('-');
+        }
+        for &(ref id, ref params) in &self.structured_data {
+            text.push('[');
+            text.push_str(id);
+            for &(ref name, ref value) in params {
+                text.push_str(&format!(" {}=\"{}\"", name, escape_param_value(value)));
+            }
+            text.push(']');
+        }
+
+        if !self.message.is_empty() {
+            text.push(' ');
+            text.push_str(&self.message);
+        }
+        text.into_bytes()
+    }
+
+    /// RFC3164 text of the message, structured data is appended to MSG as
+    /// `key=value` pairs
+    ///
+    /// Missing timestamp is replaced by the current time, missing hostname by `-`.
+    pub fn to_rfc3164(&self) -> Vec<u8> {
+        let timestamp = self.rfc3164_timestamp()
+            .unwrap_or_else(|| chrono::Local::now().format("%b %d %T").to_string());
+        let mut text = format!("<{}>{} {}",
+                               self.priority,
+                               timestamp,
+                               self.hostname.as_ref().map(String::as_str).unwrap_or("-"));
+
+        match (self.app_name.as_ref(), self.proc_id.as_ref()) {
+            (Some(app_name), Some(proc_id)) => {
+                text.push_str(&format!(" {}[{}]:", app_name, proc_id))
+            }
+            (Some(app_name), None) => text.push_str(&format!(" {}:", app_name)),
+            (None, Some(proc_id)) => text.push_str(&format!(" [{}]:", proc_id)),
+            (None, None) => {}
+        }
+
+        if !self.message.is_empty() {
+            text.push(' ');
+            text.push_str(&self.message);
+        }
+        for &(_, ref params) in &self.structured_data {
+            for &(ref name, ref value) in params {
+                if value.contains(' ') {
+                    text.push_str(&format!(" {}=\"{}\"", name, value));
+                } else {
+                    text.push_str(&format!(" {}={}", name, value));
+                }
+            }
+        }
+        text.into_bytes()
+    }
+}
@@ -1,20 +1,26 @@
 extern crate libc;
 
+use error::{SyslogError, SyslogResult};
 use libc::getpid;
-use std::{env, ffi};
+use std::{env, ffi, fs, io, mem, ptr};
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::path::{PathBuf, Path};
+use std::str::FromStr;
+use std::time::Duration;
 
-use syslog::SYSLOG_DEFAULT_UDS_LOCATIONS;
+use syslog::{HOSTNAME_MAX_LEN, SYSLOG_DEFAULT_UDS_LOCATIONS};
 
 
 /// Check for existence of domain sockets
-pub fn locate_default_uds_socket() -> Result<PathBuf, String> {
+pub fn locate_default_uds_socket() -> SyslogResult<PathBuf> {
     SYSLOG_DEFAULT_UDS_LOCATIONS.iter()
         .map(PathBuf::from)
         .find(|p| p.exists())
         .ok_or_else(|| {
-                        format!("Couldn't find socket file (tried {:?})",
-                                SYSLOG_DEFAULT_UDS_LOCATIONS)
+                        SyslogError::SocketNotFound(SYSLOG_DEFAULT_UDS_LOCATIONS.iter()
+                                                        .map(PathBuf::from)
+                                                        .collect())
                     })
 }
 
@@ -29,30 +35,275 @@ pub fn get_process_name() -> Option<String> {
         .map(String::from)
 }
 
+/// Source of the process name, used as RFC3164 TAG and RFC5424 APP-NAME
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProcessNameSource {
+    /// File name of `current_exe`
+    Executable,
+    /// `/proc/self/comm`, the name shown by `ps`, Linux only
+    Comm,
+    /// File name of `argv[0]`, which may differ from the executable, e.g. for symlinks
+    Argv0,
+}
+
+impl Default for ProcessNameSource {
+    fn default() -> ProcessNameSource {
+        ProcessNameSource::Executable
+    }
+}
+
+/// Get current process name from the source
+pub fn get_process_name_from(source: ProcessNameSource) -> Option<String> {
+    match source {
+        ProcessNameSource::Executable => get_process_name(),
+        ProcessNameSource::Comm => {
+            let mut comm = String::new();
+            let read = fs::File::open("/proc/self/comm")
+                .and_then(|mut file| file.read_to_string(&mut comm));
+            let comm = comm.trim_right_matches('\n');
+            match read {
+                Ok(_) if !comm.is_empty() => Some(comm.to_string()),
+                _ => None,
+            }
+        }
+        ProcessNameSource::Argv0 => {
+            env::args_os()
+                .next()
+                .as_ref()
+                .map(Path::new)
+                .and_then(Path::file_name)
+                .and_then(ffi::OsStr::to_str)
+                .map(String::from)
+        }
+    }
+}
+
 /// Get current proccess pid
 pub fn get_pid() -> i32 {
     unsafe { getpid() }
 }
 
 /// Get local hostname
-pub fn get_host_name() -> Result<String, String> {
+pub fn get_host_name() -> SyslogResult<String> {
 
     extern "C" {
         pub fn gethostname(name: *mut libc::c_char, size: libc::size_t) -> libc::c_int;
     }
 
-    let len = u8::max_value() as usize;
+    // Longest hostname of the system, and room for the terminating NULL
+    let max_len = match unsafe { libc::sysconf(libc::_SC_HOST_NAME_MAX) } {
+        max_len if max_len > 0 => max_len as usize,
+        _ => HOSTNAME_MAX_LEN,
+    };
+    let len = max_len + 1;
     let mut buf = vec![0; len];
 
     let err = unsafe { gethostname(buf.as_mut_ptr() as *mut libc::c_char, len as libc::size_t) };
 
     match err {
         0 => {
-            // If we have NULL, terminate, otherwise take whole buffer
-            let actual_len = buf.iter().position(|byte| *byte == 0).unwrap_or(len);
+            // Truncated name may not be NULL terminated
+            let actual_len = buf.iter().position(|byte| *byte == 0).unwrap_or(max_len);
             // trim the hostname to the actual len
-            String::from_utf8(buf.split_at(actual_len).0.to_vec()).map_err(|err| err.to_string())
+            String::from_utf8(buf.split_at(actual_len).0.to_vec()).map_err(|err| {
+                SyslogError::ResolveFailed {
+                    destination: "local hostname".to_owned(),
+                    cause: io::Error::new(io::ErrorKind::InvalidData, err),
+                }
+            })
+        }
+        _ => {
+            Err(SyslogError::ResolveFailed {
+                    destination: "local hostname".to_owned(),
+                    cause: io::Error::last_os_error(),
+                })
+        }
+    }
+}
+
+/// Get fully qualified name of the local host: `gethostname` name resolved
+/// by `getaddrinfo` with `AI_CANONNAME`
+pub fn get_fqdn() -> SyslogResult<String> {
+    let hostname = get_host_name()?;
+    let resolve_failed = |cause: io::Error| {
+        SyslogError::ResolveFailed {
+            destination: format!("canonical name of {}", hostname),
+            cause: cause,
         }
-        _ => Err("Couldn't get my own hostname".to_string()),
+    };
+    let name = ffi::CString::new(hostname.as_str())
+        .map_err(|err| resolve_failed(io::Error::new(io::ErrorKind::InvalidInput, err)))?;
+
+    let mut hints: libc::addrinfo = unsafe { mem::zeroed() };
+    hints.ai_flags = libc::AI_CANONNAME;
+    hints.ai_family = libc::AF_UNSPEC;
+    // One entry per address, instead of one per socket type
+    hints.ai_socktype = libc::SOCK_DGRAM;
+
+    let mut addrs: *mut libc::addrinfo = ptr::null_mut();
+    let err = unsafe { libc::getaddrinfo(name.as_ptr(), ptr::null(), &hints, &mut addrs) };
+    if err != 0 {
+        let message = unsafe { ffi::CStr::from_ptr(libc::gai_strerror(err)) }
+            .to_string_lossy()
+            .into_owned();
+        return Err(resolve_failed(io::Error::new(io::ErrorKind::NotFound, message)));
     }
+
+    // Canonical name is set on the first entry only
+    let canonname = unsafe { (*addrs).ai_canonname };
+    let canonical = if canonname.is_null() {
+        None
+    } else {
+        Some(unsafe { ffi::CStr::from_ptr(canonname) }.to_string_lossy().into_owned())
+    };
+    unsafe { libc::freeaddrinfo(addrs) };
+    canonical.ok_or_else(|| {
+                             resolve_failed(io::Error::new(io::ErrorKind::NotFound,
+                                                           "no canonical name"))
+                         })
+}
+
+/// Hostname of RFC3164 messages when neither a hostname nor a non-loopback
+/// address of the local host is available, as RFC3164 has no NILVALUE
+pub const HOSTNAME_FALLBACK: &'static str = "127.0.0.1";
+
+/// Get primary address of the local host: the first IPv4 address of the
+/// local interfaces, or the first IPv6 one
+pub fn get_primary_address() -> Option<IpAddr> {
+    let addresses = get_local_addresses().unwrap_or_default();
+    addresses.iter()
+        .find(|ip| ip.is_ipv4())
+        .or_else(|| addresses.first())
+        .cloned()
+}
+
+/// Make hostname fit the message header: printable US-ASCII characters
+/// without spaces, up to 255 of them. Other characters and the trailing
+/// dot of a FQDN are dropped.
+pub fn sanitize_hostname(hostname: &str) -> String {
+    hostname.trim_right_matches('.')
+        .chars()
+        .filter(|c| *c > ' ' && *c <= '~')
+        .take(HOSTNAME_MAX_LEN)
+        .collect()
+}
+
+/// Source of the hostname sent in the message header
+///
+/// Without a name or an address from the source, RFC5424 sends NILVALUE and
+/// RFC3164 the primary address, or [HOSTNAME_FALLBACK](constant.HOSTNAME_FALLBACK.html)
+/// on hosts with only loopback interfaces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HostnameSource {
+    /// Name returned by `gethostname`, short or fully qualified depending on the system
+    System,
+    /// `gethostname` name up to the first dot
+    Short,
+    /// Fully qualified name [get_fqdn](fn.get_fqdn.html), `gethostname` name if it
+    /// can't be resolved
+    Fqdn,
+    /// Primary address of the local host [get_primary_address](fn.get_primary_address.html)
+    Ip,
+}
+
+impl Default for HostnameSource {
+    fn default() -> HostnameSource {
+        HostnameSource::System
+    }
+}
+
+impl FromStr for HostnameSource {
+    type Err = SyslogError;
+    fn from_str(s: &str) -> Result<HostnameSource, SyslogError> {
+        let result = match &s.to_lowercase()[..] {
+            "system" => HostnameSource::System,
+            "short" => HostnameSource::Short,
+            "fqdn" => HostnameSource::Fqdn,
+            "ip" => HostnameSource::Ip,
+            _ => return Err(SyslogError::ConfigInvalid(format!("Unknown hostname source: {}", s))),
+        };
+        Ok(result)
+    }
+}
+
+/// Get local hostname from the source, sanitized
+///
+/// Falls back to the primary address when the name is unavailable, as RFC3164 recommends.
+/// `None` without a non-loopback address, RFC3164 formatters send
+/// [HOSTNAME_FALLBACK](constant.HOSTNAME_FALLBACK.html) then.
+pub fn get_host_name_from(source: HostnameSource) -> Option<String> {
+    let name = match source {
+        HostnameSource::System => get_host_name().ok(),
+        HostnameSource::Short => {
+            get_host_name().ok().map(|name| name.split('.').next().unwrap_or("").to_string())
+        }
+        HostnameSource::Fqdn => get_fqdn().or_else(|_| get_host_name()).ok(),
+        HostnameSource::Ip => None,
+    };
+    match name.map(|name| sanitize_hostname(&name)) {
+        Some(ref name) if !name.is_empty() => Some(name.clone()),
+        _ => get_primary_address().map(|ip| ip.to_string()),
+    }
+}
+
+/// Get addresses of the local network interfaces, except loopback ones
+pub fn get_local_addresses() -> io::Result<Vec<IpAddr>> {
+    let mut addrs: *mut libc::ifaddrs = ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut addrs) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut result = Vec::<IpAddr>::new();
+    let mut cursor = addrs;
+    while !cursor.is_null() {
+        let ifa = unsafe { &*cursor };
+        if !ifa.ifa_addr.is_null() {
+            let family = unsafe { (*ifa.ifa_addr).sa_family } as libc::c_int;
+            if family == libc::AF_INET {
+                let sin = unsafe { &*(ifa.ifa_addr as *const libc::sockaddr_in) };
+                let ip = Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr));
+                if !ip.is_loopback() {
+                    result.push(IpAddr::V4(ip));
+                }
+            } else if family == libc::AF_INET6 {
+                let sin6 = unsafe { &*(ifa.ifa_addr as *const libc::sockaddr_in6) };
+                let ip = Ipv6Addr::from(sin6.sin6_addr.s6_addr);
+                if !ip.is_loopback() {
+                    result.push(IpAddr::V6(ip));
+                }
+            }
+        }
+        cursor = ifa.ifa_next;
+    }
+
+    unsafe { libc::freeifaddrs(addrs) };
+    Ok(result)
+}
+
+/// Get time since the system start, by the monotonic clock
+pub fn get_uptime() -> Option<Duration> {
+    let mut ts: libc::timespec = unsafe { mem::zeroed() };
+    match unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) } {
+        0 => Some(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)),
+        _ => None,
+    }
+}
+
+/// Whether the system clock is synchronized (by NTP), known on Linux only
+#[cfg(target_os = "linux")]
+pub fn is_clock_synced() -> Option<bool> {
+    // Clock state returned by adjtimex when it's not synchronized
+    const TIME_ERROR: libc::c_int = 5;
+
+    let mut tx: libc::timex = unsafe { mem::zeroed() };
+    match unsafe { libc::adjtimex(&mut tx) } {
+        -1 => None,
+        state => Some(state != TIME_ERROR),
+    }
+}
+
+/// Whether the system clock is synchronized (by NTP), known on Linux only
+#[cfg(not(target_os = "linux"))]
+pub fn is_clock_synced() -> Option<bool> {
+    None
 }
@@ -0,0 +1,477 @@
+use drain::SyslogDrain;
+use error::{SyslogError, SyslogResult};
+use metrics::DrainStats;
+use serializers::KsvSerializerUnquoted;
+use slog::{Drain, OwnedKeyValueList, Record, RecordStatic};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::Hasher;
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use syslog::Severity;
+
+/// Token bucket limit: bursts of up to `burst` messages,
+/// refilled at `burst` messages per `interval`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Bucket size, messages
+    pub burst: u32,
+    /// Time to refill the whole bucket
+    pub interval: Duration,
+}
+
+impl RateLimit {
+    /// Limit of `burst` messages per `interval`
+    pub fn new(burst: u32, interval: Duration) -> RateLimit {
+        RateLimit {
+            burst: burst,
+            interval: interval,
+        }
+    }
+}
+
+/// Rate limiting and duplicate suppression configuration
+///
+/// `LOG_CRIT` and more severe messages are never rate limited, nor collapsed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RateLimitConfig {
+    /// Limits by severity, later entries override earlier ones
+    ///
+    /// Default: empty, no rate limiting
+    pub limits: Vec<(Severity, RateLimit)>,
+    /// Collapse identical consecutive messages, reporting them as
+    /// "last message repeated N times" once per this interval,
+    /// or when a different message arrives
+    ///
+    /// Default: `None`, duplicates are sent
+    pub suppress_duplicates: Option<Duration>,
+}
+
+impl RateLimitConfig {
+    /// Configuration without any limits
+    pub fn new() -> RateLimitConfig {
+        RateLimitConfig::default()
+    }
+
+    /// Limit for every rate limited severity, `LOG_ERR` to `LOG_DEBUG`
+    pub fn limit(mut self, value: RateLimit) -> Self {
+        for severity in &[Severity::LOG_ERR,
+                          Severity::LOG_WARN,
+                          Severity::LOG_NOTICE,
+                          Severity::LOG_INFO,
+                          Severity::LOG_DEBUG] {
+            self.limits.push((*severity, value));
+        }
+        self
+    }
+
+    /// Limit for a single severity
+    pub fn severity_limit(mut self, severity: Severity, value: RateLimit) -> Self {
+        self.limits.push((severity, value));
+        self
+    }
+
+    /// Collapse identical consecutive messages, reporting the number
+    /// of repeats once per interval
+    pub fn suppress_duplicates(mut self, interval: Duration) -> Self {
+        self.suppress_duplicates = Some(interval);
+        self
+    }
+
+    fn limit_for(&self, severity: Severity) -> Option<RateLimit> {
+        if is_exempt(severity) {
+            return None;
+        }
+        self.limits
+            .iter()
+            .rev()
+            .find(|&&(s, _)| s == severity)
+            .map(|&(_, limit)| limit)
+    }
+}
+
+// `LOG_CRIT` and more severe messages are always sent
+fn is_exempt(severity: Severity) -> bool {
+    severity as u8 <= Severity::LOG_CRIT as u8
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn new(limit: &RateLimit) -> Bucket {
+        Bucket {
+            tokens: limit.burst as f64,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    fn take(&mut self, limit: &RateLimit) -> bool {
+        let now = Instant::now();
+        let elapsed = seconds(now.duration_since(self.refilled_at));
+        let interval = seconds(limit.interval);
+        let refill = if interval > 0.0 {
+            elapsed / interval * limit.burst as f64
+        } else {
+            limit.burst as f64
+        };
+        self.tokens = (self.tokens + refill).min(limit.burst as f64);
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Repeats {
+    // Hash of the last message
+    last: Option<u64>,
+    count: usize,
+    since: Instant,
+    // Formatted "last message repeated N times" record, waiting to be sent
+    summary: Option<Vec<u8>>,
+}
+
+// Identity of a message: level, text and all the values
+fn message_hash(info: &Record, logger_values: &OwnedKeyValueList) -> u64 {
+    let mut serializer = KsvSerializerUnquoted::new(Vec::<u8>::new(), "=");
+    for &(k, v) in info.values().iter() {
+        let _ = v.serialize(info, k, &mut serializer);
+    }
+    for (k, v) in logger_values.iter() {
+        let _ = v.serialize(info, k, &mut serializer);
+    }
+
+    let mut hasher = DefaultHasher::new();
+    hasher.write_usize(Severity::from(info.level()) as usize);
+    hasher.write(format!("{}", info.msg()).as_bytes());
+    hasher.write(serializer.finish().as_slice());
+    hasher.finish()
+}
+
+// Summary record takes the location and logger values of the repeated one
+fn format_summary<D>(drain: &D,
+                     info: &Record,
+                     logger_values: &OwnedKeyValueList,
+                     count: usize)
+                     -> SyslogResult<Vec<u8>>
+    where D: SyslogDrain + ?Sized
+{
+    let record_static = RecordStatic {
+        level: info.level(),
+        file: info.file(),
+        line: info.line(),
+        column: info.column(),
+        function: info.function(),
+        module: info.module(),
+        target: info.target(),
+    };
+    drain.format(&Record::new(&record_static,
+                              format_args!("last message repeated {} time{}",
+                                           count,
+                                           if count == 1 { "" } else { "s" }),
+                              &[]),
+                 logger_values)
+}
+
+/// Rate limiter and duplicate suppressor for records sent to a syslog drain
+///
+/// Used by `RateLimitDrain`, and by the drain returned from `SyslogConfig::connect`.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<Vec<(Severity, Bucket)>>,
+    repeats: Mutex<Repeats>,
+    limited: AtomicUsize,
+    repeated: AtomicUsize,
+}
+
+impl RateLimiter {
+    /// RateLimiter constructor
+    pub fn new(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter {
+            config: config,
+            buckets: Mutex::new(Vec::new()),
+            repeats: Mutex::new(Repeats {
+                                    last: None,
+                                    count: 0,
+                                    since: Instant::now(),
+                                    summary: None,
+                                }),
+            limited: AtomicUsize::new(0),
+            repeated: AtomicUsize::new(0),
+        }
+    }
+
+    /// Rate limiter shared with a thread sending expired repeat summaries
+    /// through the drain, until either of them is dropped
+    ///
+    /// The thread is only started if duplicates are suppressed.
+    pub fn with_timer<D>(config: RateLimitConfig, drain: &Arc<D>) -> Arc<RateLimiter>
+        where D: SyslogDrain + Send + Sync + ?Sized + 'static
+    {
+        let interval = config.suppress_duplicates;
+        let limiter = Arc::new(RateLimiter::new(config));
+        if let Some(interval) = interval {
+            spawn_repeat_timer(Arc::downgrade(drain), Arc::downgrade(&limiter), interval);
+        }
+        limiter
+    }
+
+    /// Number of messages dropped by rate limits
+    pub fn limited(&self) -> usize {
+        self.limited.load(Ordering::Relaxed)
+    }
+
+    /// Number of duplicate messages collapsed into repeat summaries
+    pub fn repeated(&self) -> usize {
+        self.repeated.load(Ordering::Relaxed)
+    }
+
+    /// Format and send record through the drain, unless it's a duplicate or over the limit
+    pub fn log<D>(&self,
+                  drain: &D,
+                  info: &Record,
+                  logger_values: &OwnedKeyValueList)
+                  -> SyslogResult<()>
+        where D: SyslogDrain + ?Sized
+    {
+        let severity = Severity::from(info.level());
+        match self.config.suppress_duplicates {
+            Some(_) if !is_exempt(severity) => {
+                if !self.suppress_duplicate(drain, severity, info, logger_values)? {
+                    return Ok(());
+                }
+            }
+            _ => {
+                if !self.allow(severity) {
+                    self.limited.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+            }
+        }
+
+        let buf = drain.format(info, logger_values)?;
+        drain.send(buf.as_slice())
+    }
+
+    /// Send pending repeat summary, if any
+    pub fn flush<D>(&self, drain: &D) -> SyslogResult<()>
+        where D: SyslogDrain + ?Sized
+    {
+        let summary = match self.repeats.lock() {
+            Ok(mut repeats) => {
+                repeats.last = None;
+                repeats.summary.take()
+            }
+            Err(_) => None,
+        };
+        match summary {
+            Some(summary) => drain.send(summary.as_slice()),
+            None => Ok(()),
+        }
+    }
+
+    /// Send pending repeat summary, if the duplicate suppression interval has elapsed
+    ///
+    /// Called periodically by the timer of `RateLimitDrain` and `ConfiguredDrain`,
+    /// later duplicates are still collapsed.
+    pub fn flush_expired<D>(&self, drain: &D) -> SyslogResult<()>
+        where D: SyslogDrain + ?Sized
+    {
+        let interval = match self.config.suppress_duplicates {
+            Some(interval) => interval,
+            None => return Ok(()),
+        };
+        // Summary is sent after releasing the lock, so a slow send doesn't block logging
+        let summary = {
+            let mut repeats = match self.repeats.lock() {
+                Ok(repeats) => repeats,
+                Err(_) => return Ok(()),
+            };
+            if repeats.summary.is_none() || repeats.since.elapsed() < interval {
+                return Ok(());
+            }
+            repeats.count = 0;
+            repeats.since = Instant::now();
+            repeats.summary.take()
+        };
+        match summary {
+            Some(summary) => drain.send(summary.as_slice()),
+            None => Ok(()),
+        }
+    }
+
+    // Whether the record should be sent: it's not a duplicate of the last one,
+    // nor over the limit. Summary of the previous duplicates is sent
+    // before a different message
+    fn suppress_duplicate<D>(&self,
+                             drain: &D,
+                             severity: Severity,
+                             info: &Record,
+                             logger_values: &OwnedKeyValueList)
+                             -> SyslogResult<bool>
+        where D: SyslogDrain + ?Sized
+    {
+        let key = message_hash(info, logger_values);
+        let mut repeats = match self.repeats.lock() {
+            // Deliver the message rather than lose it
+            Err(_) => return Ok(true),
+            Ok(repeats) => repeats,
+        };
+
+        if repeats.last == Some(key) {
+            repeats.count += 1;
+            self.repeated.fetch_add(1, Ordering::Relaxed);
+            repeats.summary = Some(format_summary(drain, info, logger_values, repeats.count)?);
+            return Ok(false);
+        }
+
+        // Dropped record doesn't end the run of duplicates
+        if !self.allow(severity) {
+            self.limited.fetch_add(1, Ordering::Relaxed);
+            return Ok(false);
+        }
+
+        let summary = repeats.summary.take();
+        repeats.last = Some(key);
+        repeats.count = 0;
+        repeats.since = Instant::now();
+        drop(repeats);
+        if let Some(summary) = summary {
+            drain.send(summary.as_slice())?;
+        }
+        Ok(true)
+    }
+
+    fn allow(&self, severity: Severity) -> bool {
+        let limit = match self.config.limit_for(severity) {
+            Some(limit) => limit,
+            None => return true,
+        };
+        let mut buckets = match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(_) => return true,
+        };
+        let index = match buckets.iter().position(|&(s, _)| s == severity) {
+            Some(index) => index,
+            None => {
+                buckets.push((severity, Bucket::new(&limit)));
+                buckets.len() - 1
+            }
+        };
+        buckets[index].1.take(&limit)
+    }
+}
+
+// Sends expired repeat summaries until the drain or the limiter is dropped
+fn spawn_repeat_timer<D>(drain: Weak<D>, limiter: Weak<RateLimiter>, interval: Duration)
+    where D: SyslogDrain + Send + Sync + ?Sized + 'static
+{
+    thread::spawn(move || loop {
+                      thread::sleep(interval);
+                      match (drain.upgrade(), limiter.upgrade()) {
+                          (Some(drain), Some(limiter)) => {
+                              let _ = limiter.flush_expired(&*drain);
+                          }
+                          _ => return,
+                      }
+                  });
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "RateLimiter {{ config: {:?}, limited: {}, repeated: {} }}",
+               self.config,
+               self.limited(),
+               self.repeated())
+    }
+}
+
+/// Drain rate limiting records and suppressing duplicates
+/// [RateLimitConfig](struct.RateLimitConfig.html)
+///
+/// Records over the limit are dropped silently. Pending repeat summary
+/// is sent once the interval elapses, and when the drain is dropped.
+#[derive(Debug)]
+pub struct RateLimitDrain<D>
+    where D: SyslogDrain
+{
+    drain: Arc<D>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<D> RateLimitDrain<D>
+    where D: SyslogDrain + Send + Sync + 'static
+{
+    /// Wrap connected drain
+    pub fn new(drain: D, config: RateLimitConfig) -> RateLimitDrain<D> {
+        let drain = Arc::new(drain);
+        RateLimitDrain {
+            limiter: RateLimiter::with_timer(config, &drain),
+            drain: drain,
+        }
+    }
+}
+
+impl<D> RateLimitDrain<D>
+    where D: SyslogDrain
+{
+    /// Rate limiter, for its counters
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+}
+
+impl<D> SyslogDrain for RateLimitDrain<D>
+    where D: SyslogDrain
+{
+    fn format(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        self.drain.format(info, logger_values)
+    }
+
+    // Formatted messages carry no severity, they are passed through
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        self.drain.send(message)
+    }
+
+    fn reconnect(&self) -> SyslogResult<()> {
+        self.drain.reconnect()
+    }
+
+    fn stats(&self) -> Option<DrainStats> {
+        self.drain.stats()
+    }
+}
+
+impl<D> Drain for RateLimitDrain<D>
+    where D: SyslogDrain
+{
+    type Error = SyslogError;
+
+    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<()> {
+        self.limiter.log(&*self.drain, info, logger_values)
+    }
+}
+
+impl<D> Drop for RateLimitDrain<D>
+    where D: SyslogDrain
+{
+    fn drop(&mut self) {
+        let _ = self.limiter.flush(&*self.drain);
+    }
+}
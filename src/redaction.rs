@@ -0,0 +1,184 @@
+use error::{SyslogError, SyslogResult};
+use regex::{Captures, Regex};
+use serializers::KsvSerializerUnquoted;
+use slog;
+use slog::Record;
+use slog::ser::{Serialize, Serializer};
+
+/// Default replacement of redacted values
+pub const DEFAULT_REDACTION_MASK: &'static str = "[REDACTED]";
+
+/// How redacted values are replaced
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Redaction {
+    /// Replace with the mask
+    Mask,
+    /// Replace with a hash of the value, so equal values can still be correlated
+    ///
+    /// The hash (64 bit FNV-1a, hex encoded) is not cryptographic, it won't
+    /// protect short or guessable secrets.
+    Hash,
+}
+
+impl Default for Redaction {
+    fn default() -> Redaction {
+        Redaction::Mask
+    }
+}
+
+/// Redaction of sensitive record and logger values
+///
+/// Values of denied keys are replaced as a whole, parts of any value matching
+/// a mask pattern are replaced in place. With an allow-list, keys missing
+/// from it are not emitted at all. Applied to key=value messages and RFC5424
+/// structured data, other serializers can use it through `serialize`.
+#[derive(Debug, Clone, Default)]
+pub struct RedactionPolicy {
+    deny_keys: Vec<String>,
+    deny_key_patterns: Vec<Regex>,
+    value_masks: Vec<Regex>,
+    allow_keys: Option<Vec<String>>,
+    redaction: Redaction,
+    mask: Option<String>,
+}
+
+fn compile(pattern: &str) -> SyslogResult<Regex> {
+    Regex::new(pattern)
+        .map_err(|err| SyslogError::ConfigInvalid(format!("Invalid pattern {}: {}", pattern, err)))
+}
+
+// 64 bit FNV-1a, stable across builds and platforms
+fn fnv1a(value: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in value {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+impl RedactionPolicy {
+    /// Policy which doesn't redact anything
+    pub fn new() -> RedactionPolicy {
+        RedactionPolicy::default()
+    }
+
+    /// Redact values of the key, compared case insensitively
+    pub fn deny_key<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.deny_keys.push(value.into().to_lowercase());
+        self
+    }
+
+    /// Redact values of keys matching the regular expression, e.g. `(?i)token|secret`
+    pub fn deny_key_pattern(mut self, pattern: &str) -> SyslogResult<Self> {
+        self.deny_key_patterns.push(compile(pattern)?);
+        Ok(self)
+    }
+
+    /// Redact parts of values matching the regular expression, e.g. credit card numbers
+    pub fn mask_values(mut self, pattern: &str) -> SyslogResult<Self> {
+        self.value_masks.push(compile(pattern)?);
+        Ok(self)
+    }
+
+    /// Emit only the allowed keys, the first call turns the allow-list mode on
+    pub fn allow_key<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        let mut allowed = self.allow_keys.take().unwrap_or_default();
+        allowed.push(value.into());
+        self.allow_keys = Some(allowed);
+        self
+    }
+
+    /// How redacted values are replaced [Redaction](enum.Redaction.html)
+    ///
+    /// Default: `Mask`
+    pub fn redaction<VALUE: Into<Redaction>>(mut self, value: VALUE) -> Self {
+        self.redaction = value.into();
+        self
+    }
+
+    /// Replacement of redacted values
+    ///
+    /// Default: `[REDACTED]`
+    pub fn mask<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.mask = Some(value.into());
+        self
+    }
+
+    /// Whether the policy changes anything at all
+    pub fn is_empty(&self) -> bool {
+        self.deny_keys.is_empty() && self.deny_key_patterns.is_empty() &&
+        self.value_masks.is_empty() && self.allow_keys.is_none()
+    }
+
+    /// Whether the key is emitted
+    pub fn allows(&self, key: &str) -> bool {
+        match self.allow_keys {
+            Some(ref allowed) => allowed.iter().any(|k| k == key),
+            None => true,
+        }
+    }
+
+    fn denies(&self, key: &str) -> bool {
+        let lowercase = key.to_lowercase();
+        self.deny_keys.iter().any(|k| *k == lowercase) ||
+        self.deny_key_patterns.iter().any(|p| p.is_match(key))
+    }
+
+    fn replacement(&self, value: &str) -> String {
+        match self.redaction {
+            Redaction::Mask => {
+                self.mask.clone().unwrap_or_else(|| DEFAULT_REDACTION_MASK.to_string())
+            }
+            Redaction::Hash => format!("{:016x}", fnv1a(value.as_bytes())),
+        }
+    }
+
+    /// Serialize value of an allowed key, redacting it if needed
+    ///
+    /// Values which don't need redaction keep their type.
+    pub fn serialize(&self,
+                     record: &Record,
+                     key: &str,
+                     value: &Serialize,
+                     serializer: &mut Serializer)
+                     -> slog::ser::Result {
+        self.serialize_as(record, key, key, value, serializer)
+    }
+
+    /// Serialize value of an allowed key as `name`, redaction is decided by `key`
+    pub fn serialize_as(&self,
+                        record: &Record,
+                        key: &str,
+                        name: &str,
+                        value: &Serialize,
+                        serializer: &mut Serializer)
+                        -> slog::ser::Result {
+        let denied = self.denies(key);
+        if !denied && self.value_masks.is_empty() {
+            return value.serialize(record, name, serializer);
+        }
+
+        // Serializer without key and separator writes just the value
+        let mut plain = KsvSerializerUnquoted::new(Vec::<u8>::new(), "");
+        value.serialize(record, "", &mut plain)?;
+        let plain = String::from_utf8_lossy(&plain.finish()).into_owned();
+
+        if denied {
+            return serializer.emit_str(name, &self.replacement(&plain));
+        }
+
+        let mut masked = plain.clone();
+        for pattern in &self.value_masks {
+            masked = pattern.replace_all(&masked, |captures: &Captures| {
+                    self.replacement(&captures[0])
+                })
+                .into_owned();
+        }
+        if masked == plain {
+            value.serialize(record, name, serializer)
+        } else {
+            serializer.emit_str(name, &masked)
+        }
+    }
+}
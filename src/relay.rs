@@ -0,0 +1,461 @@
+use config::FormatMode;
+use drain::SyslogDrain;
+use error::{SyslogError, SyslogResult};
+use parser::SyslogMessage;
+use std::fmt;
+use std::cmp;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::os::unix::net::UnixDatagram;
+use std::str;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tls_server::{TlsServer, make_server_config};
+
+/// Largest message accepted by the relay listeners
+pub const RELAY_MAX_MESSAGE_SIZE: usize = 65536;
+
+// Longest pause of a listener after repeated receive errors
+const RELAY_MAX_BACKOFF_MS: u64 = 1000;
+
+/// Relay configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelayConfig {
+    /// Format the messages are converted to
+    ///
+    /// Default: `None`, messages are forwarded as received
+    pub convert: Option<FormatMode>,
+    /// SD-ID of the element for `key=value` pairs at the end of RFC3164 messages
+    /// converted to RFC5424, e.g. `msg@32473`
+    ///
+    /// Default: `None`, the pairs stay in MSG
+    pub ksv_sd_id: Option<String>,
+    /// Time a TCP or TLS connection may stay idle before it's closed
+    ///
+    /// Default: `60s`, `None` keeps idle connections open
+    pub read_timeout: Option<Duration>,
+    /// Maximal number of open TCP and TLS connections, further connections
+    /// are closed when accepted
+    ///
+    /// Default: `256`
+    pub max_connections: usize,
+}
+
+impl Default for RelayConfig {
+    fn default() -> RelayConfig {
+        RelayConfig {
+            convert: None,
+            ksv_sd_id: None,
+            read_timeout: Some(Duration::from_secs(60)),
+            max_connections: 256,
+        }
+    }
+}
+
+impl RelayConfig {
+    /// Configuration forwarding messages as received
+    pub fn new() -> RelayConfig {
+        RelayConfig::default()
+    }
+
+    /// Convert messages to the format
+    pub fn convert<VALUE: Into<FormatMode>>(mut self, value: VALUE) -> Self {
+        self.convert = Some(value.into());
+        self
+    }
+
+    /// Move `key=value` pairs of RFC3164 messages converted to RFC5424 to
+    /// an SD-ELEMENT with the SD-ID
+    pub fn ksv_sd_id<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.ksv_sd_id = Some(value.into());
+        self
+    }
+
+    /// Close TCP and TLS connections idle for the duration, `None` keeps them open
+    pub fn read_timeout<VALUE: Into<Option<Duration>>>(mut self, value: VALUE) -> Self {
+        self.read_timeout = value.into();
+        self
+    }
+
+    /// Maximal number of open TCP and TLS connections
+    pub fn max_connections<VALUE: Into<usize>>(mut self, value: VALUE) -> Self {
+        self.max_connections = value.into();
+        self
+    }
+}
+
+/// Relay forwarding received syslog messages through a drain
+///
+/// Messages are sent with `SyslogDrain::send`, so reconnects, failover and
+/// spooling of the drain apply to them.
+pub struct Relay<D>
+    where D: SyslogDrain
+{
+    drain: D,
+    config: RelayConfig,
+    received: AtomicUsize,
+    forwarded: AtomicUsize,
+    unparsed: AtomicUsize,
+    failed: AtomicUsize,
+    connections: AtomicUsize,
+}
+
+// Message without trailing CR, LF and NUL characters
+fn trim_eom(message: &[u8]) -> &[u8] {
+    let mut end = message.len();
+    while end > 0 && (message[end - 1] == b'\n' || message[end - 1] == b'\r' ||
+                      message[end - 1] == b'\0') {
+        end -= 1;
+    }
+    &message[..end]
+}
+
+impl<D> Relay<D>
+    where D: SyslogDrain
+{
+    /// Relay forwarding through the drain
+    pub fn new(drain: D, config: RelayConfig) -> Relay<D> {
+        Relay {
+            drain: drain,
+            config: config,
+            received: AtomicUsize::new(0),
+            forwarded: AtomicUsize::new(0),
+            unparsed: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            connections: AtomicUsize::new(0),
+        }
+    }
+
+    /// Drain the messages are forwarded through
+    pub fn drain(&self) -> &D {
+        &self.drain
+    }
+
+    /// Number of messages received
+    pub fn received(&self) -> usize {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages sent through the drain
+    pub fn forwarded(&self) -> usize {
+        self.forwarded.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages which couldn't be parsed for conversion
+    pub fn unparsed(&self) -> usize {
+        self.unparsed.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages the drain failed to send
+    pub fn failed(&self) -> usize {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Number of open TCP and TLS connections
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    /// Convert the message as configured and send it through the drain
+    ///
+    /// Messages which can't be parsed are converted with the whole text
+    /// as MSG and `user.notice` priority, as RFC3164 relays do.
+    pub fn relay(&self, message: &[u8]) -> SyslogResult<()> {
+        let message = trim_eom(message);
+        if message.is_empty() {
+            return Ok(());
+        }
+        self.received.fetch_add(1, Ordering::Relaxed);
+
+        let mut message = match self.config.convert {
+            None => message.to_vec(),
+            Some(ref mode) => self.convert(message, mode),
+        };
+        // Same end of message as the formatters write
+        message.extend_from_slice(b"\n\0");
+
+        match self.drain.send(&message) {
+            Ok(()) => {
+                self.forwarded.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(err) => {
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                Err(err)
+            }
+        }
+    }
+
+    fn convert(&self, message: &[u8], mode: &FormatMode) -> Vec<u8> {
+        let mut parsed = SyslogMessage::parse(message).unwrap_or_else(|_| {
+            self.unparsed.fetch_add(1, Ordering::Relaxed);
+            SyslogMessage::unparsed(message)
+        });
+
+        match *mode {
+            FormatMode::RFC3164 => parsed.to_rfc3164(),
+            FormatMode::RFC5424 => {
+                if let Some(ref sd_id) = self.config.ksv_sd_id {
+                    if parsed.mode == FormatMode::RFC3164 {
+                        parsed = parsed.ksv_to_structured_data(sd_id);
+                    }
+                }
+                parsed.to_rfc5424()
+            }
+        }
+    }
+
+    /// Relay messages read from a stream: octet counted (`LEN SP MSG`),
+    /// or delimited by LF or NUL (RFC6587), until the stream is closed
+    pub fn relay_stream<R: Read>(&self, stream: R) -> io::Result<()> {
+        let mut reader = BufReader::new(stream);
+        loop {
+            if reader.fill_buf()?.is_empty() {
+                return Ok(());
+            }
+            let octet_counted = is_octet_counted(reader.fill_buf()?);
+            let frame = if octet_counted {
+                read_octet_counted(&mut reader)?
+            } else {
+                read_delimited(&mut reader)?
+            };
+            let _ = self.relay(&frame);
+        }
+    }
+}
+
+impl<D> fmt::Debug for Relay<D>
+    where D: SyslogDrain
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "Relay {{ config: {:?}, received: {}, forwarded: {}, unparsed: {}, failed: {}, \
+                connections: {} }}",
+               self.config,
+               self.received(),
+               self.forwarded(),
+               self.unparsed(),
+               self.failed(),
+               self.connections())
+    }
+}
+
+fn frame_too_large() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData,
+                   format!("message exceeds {} bytes", RELAY_MAX_MESSAGE_SIZE))
+}
+
+fn is_digit(byte: &u8) -> bool {
+    *byte >= b'0' && *byte <= b'9'
+}
+
+// Frame starts with MSG-LEN: a non-zero digit and more digits up to SP.
+// Digits up to the end of the buffered data may be the start of a MSG-LEN too.
+fn is_octet_counted(buf: &[u8]) -> bool {
+    let digits = buf.iter().take_while(|byte| is_digit(byte)).count();
+    digits > 0 && buf[0] != b'0' && buf.get(digits).map(|byte| *byte == b' ').unwrap_or(true)
+}
+
+// `MSG-LEN SP SYSLOG-MSG` frame
+fn read_octet_counted<R: BufRead>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut len = Vec::new();
+    // MSG-LEN of any usize and SP
+    reader.by_ref().take(21).read_until(b' ', &mut len)?;
+    let len = str::from_utf8(&len)
+        .ok()
+        .and_then(|len| len.trim_right().parse::<usize>().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid MSG-LEN"))?;
+    if len > RELAY_MAX_MESSAGE_SIZE {
+        return Err(frame_too_large());
+    }
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame)?;
+    Ok(frame)
+}
+
+// Frame up to the next LF or NUL, or the end of the stream
+fn read_delimited<R: BufRead>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut frame = Vec::new();
+    loop {
+        let (done, used) = {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(frame);
+            }
+            match buf.iter().position(|byte| *byte == b'\n' || *byte == b'\0') {
+                Some(index) => {
+                    frame.extend_from_slice(&buf[..index]);
+                    (true, index + 1)
+                }
+                None => {
+                    frame.extend_from_slice(buf);
+                    (false, buf.len())
+                }
+            }
+        };
+        reader.consume(used);
+        if frame.len() > RELAY_MAX_MESSAGE_SIZE {
+            return Err(frame_too_large());
+        }
+        if done {
+            return Ok(frame);
+        }
+    }
+}
+
+// Report a receive or accept error of a listener on stderr, and pause for
+// the backoff, doubled for the next consecutive error
+fn back_off(listener: &str, err: &io::Error, backoff: &mut u64) {
+    let _ = writeln!(io::stderr(),
+                     "slog-syslog-ng: {} relay listener failed: {}, retrying in {}ms",
+                     listener,
+                     err,
+                     *backoff);
+    thread::sleep(Duration::from_millis(*backoff));
+    *backoff = cmp::min(*backoff * 2, RELAY_MAX_BACKOFF_MS);
+}
+
+// Open connection, counted until dropped
+struct Connection<D>
+    where D: SyslogDrain
+{
+    relay: Arc<Relay<D>>,
+}
+
+impl<D> Connection<D>
+    where D: SyslogDrain
+{
+    // Connection of the stream, `None` if the relay has `max_connections` open.
+    // The read timeout is set on the stream.
+    fn open(relay: &Arc<Relay<D>>, stream: &TcpStream) -> Option<Connection<D>> {
+        let open = relay.connections.fetch_add(1, Ordering::Relaxed);
+        let connection = Connection { relay: relay.clone() };
+        if open >= relay.config.max_connections {
+            return None;
+        }
+        match stream.set_read_timeout(relay.config.read_timeout) {
+            Ok(()) => Some(connection),
+            Err(_) => None,
+        }
+    }
+}
+
+impl<D> Drop for Connection<D>
+    where D: SyslogDrain
+{
+    fn drop(&mut self) {
+        self.relay.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Relay datagrams received on the UDP socket, in a new thread
+///
+/// Receive errors are reported on stderr, and the listener backs off
+/// before receiving again.
+pub fn listen_udp<D>(relay: Arc<Relay<D>>, socket: UdpSocket) -> JoinHandle<()>
+    where D: SyslogDrain + Send + Sync + 'static
+{
+    thread::spawn(move || {
+        let mut buf = vec![0; RELAY_MAX_MESSAGE_SIZE];
+        let mut backoff = 10;
+        loop {
+            match socket.recv_from(&mut buf) {
+                Ok((len, _)) => {
+                    backoff = 10;
+                    let _ = relay.relay(&buf[..len]);
+                }
+                Err(err) => back_off("UDP", &err, &mut backoff),
+            }
+        }
+    })
+}
+
+/// Relay datagrams received on the unix domain socket, in a new thread
+///
+/// Receive errors are reported on stderr, and the listener backs off
+/// before receiving again.
+pub fn listen_uds<D>(relay: Arc<Relay<D>>, socket: UnixDatagram) -> JoinHandle<()>
+    where D: SyslogDrain + Send + Sync + 'static
+{
+    thread::spawn(move || {
+        let mut buf = vec![0; RELAY_MAX_MESSAGE_SIZE];
+        let mut backoff = 10;
+        loop {
+            match socket.recv(&mut buf) {
+                Ok(len) => {
+                    backoff = 10;
+                    let _ = relay.relay(&buf[..len]);
+                }
+                Err(err) => back_off("UDS", &err, &mut backoff),
+            }
+        }
+    })
+}
+
+/// Relay messages of TCP connections accepted by the listener, in a new
+/// thread per connection
+///
+/// Connections idle for `RelayConfig::read_timeout`, and connections over
+/// `RelayConfig::max_connections` are closed.
+pub fn listen_tcp<D>(relay: Arc<Relay<D>>, listener: TcpListener) -> JoinHandle<()>
+    where D: SyslogDrain + Send + Sync + 'static
+{
+    thread::spawn(move || {
+        let mut backoff = 10;
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    back_off("TCP", &err, &mut backoff);
+                    continue;
+                }
+            };
+            backoff = 10;
+            if let Some(connection) = Connection::open(&relay, &stream) {
+                thread::spawn(move || connection.relay.relay_stream(stream));
+            }
+        }
+    })
+}
+
+/// Relay messages of TLS connections accepted by the listener (RFC5425), in a new
+/// thread per connection, with the certificate chain and private key from PEM files
+///
+/// Connections idle for `RelayConfig::read_timeout`, and connections over
+/// `RelayConfig::max_connections` are closed.
+pub fn listen_tls<D>(relay: Arc<Relay<D>>,
+                     listener: TcpListener,
+                     certs: &str,
+                     key: &str)
+                     -> SyslogResult<JoinHandle<()>>
+    where D: SyslogDrain + Send + Sync + 'static
+{
+    let config = make_server_config(certs, key).map_err(|message| {
+            SyslogError::Tls {
+                destination: listener.local_addr()
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_else(|_| "listener".to_string()),
+                message: message,
+            }
+        })?;
+
+    Ok(thread::spawn(move || {
+        let mut backoff = 10;
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    back_off("TLS", &err, &mut backoff);
+                    continue;
+                }
+            };
+            backoff = 10;
+            if let Some(connection) = Connection::open(&relay, &stream) {
+                let session = TlsServer::new(stream, &config);
+                thread::spawn(move || connection.relay.relay_stream(session));
+            }
+        }
+    }))
+}
@@ -0,0 +1,573 @@
+use drain::SyslogDrain;
+use error::{SyslogError, SyslogResult, Transport};
+use metrics::DrainStats;
+use slog::{Drain, OwnedKeyValueList, Record};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Spool segment file extension
+const SEGMENT_EXTENSION: &'static str = "spool";
+
+/// Marker starting every spooled entry, replay resynchronizes at the next one
+/// after a corrupted entry. 0xFE never occurs in UTF-8 text.
+const ENTRY_MAGIC: [u8; 4] = [0xFE, b'S', b'P', b'L'];
+
+/// Spooled entry header: marker + payload length + CRC32 of the payload
+const ENTRY_HEADER_LEN: usize = 12;
+
+/// Largest message accepted from a segment on replay,
+/// anything bigger is treated as a corrupted entry
+const MAX_ENTRY_LEN: u32 = 1 << 20;
+
+/// On-disk spool configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpoolConfig {
+    /// Directory holding spool segment files
+    pub directory: PathBuf,
+    /// Size cap for all the segments together, in bytes.
+    /// Oldest segments are discarded, when the cap is exceeded.
+    ///
+    /// Default: 64MB
+    pub max_size: u64,
+    /// Size of a single segment file, in bytes
+    ///
+    /// Default: 1MB
+    pub segment_size: u64,
+    /// Minimal interval between attempts to reconnect and replay spooled messages
+    ///
+    /// Default: 5 seconds
+    pub retry_interval: Duration,
+    /// Number of undeliverable messages `SpoolDrain` keeps in memory, messages
+    /// overflowing it go to the segment files, the rest when the drain is dropped
+    ///
+    /// Default: 0, all of them go to the segment files
+    pub memory_buffer: usize,
+}
+
+impl SpoolConfig {
+    /// Spool configuration with default limits
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        SpoolConfig {
+            directory: directory.into(),
+            max_size: 64 * 1024 * 1024,
+            segment_size: 1024 * 1024,
+            retry_interval: Duration::from_secs(5),
+            memory_buffer: 0,
+        }
+    }
+
+    /// Size cap for all the segments together, in bytes
+    pub fn max_size(mut self, value: u64) -> Self {
+        self.max_size = value;
+        self
+    }
+
+    /// Size of a single segment file, in bytes
+    pub fn segment_size(mut self, value: u64) -> Self {
+        self.segment_size = value;
+        self
+    }
+
+    /// Minimal interval between attempts to replay spooled messages
+    pub fn retry_interval(mut self, value: Duration) -> Self {
+        self.retry_interval = value;
+        self
+    }
+
+    /// Number of undeliverable messages kept in memory before going to disk
+    pub fn memory_buffer(mut self, value: usize) -> Self {
+        self.memory_buffer = value;
+        self
+    }
+}
+
+/// Spool metrics snapshot
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SpoolStats {
+    /// Messages written to the spool
+    pub spooled: usize,
+    /// Messages successfully replayed from the spool
+    pub replayed: usize,
+    /// Messages discarded because of size cap, and corrupted regions of segments
+    pub discarded: usize,
+}
+
+#[derive(Debug)]
+struct Segment {
+    id: u64,
+    path: PathBuf,
+    size: u64,
+    // Nothing is appended anymore: left by a previous run, which may have
+    // torn the last entry, or a write failed
+    sealed: bool,
+}
+
+#[derive(Debug)]
+struct SpoolState {
+    segments: VecDeque<Segment>,
+    // Read position in the oldest segment
+    read_offset: u64,
+}
+
+impl SpoolState {
+    fn total_size(&self) -> u64 {
+        self.segments.iter().map(|s| s.size).sum()
+    }
+}
+
+/// Persistent on-disk message queue
+///
+/// Messages are appended to numbered segment files, every entry is
+/// prefixed with a marker, its length and CRC32 checksum. Segments left over
+/// from a previous run are picked up on `open`, so spooled messages
+/// survive process restarts, new messages go to a new segment. Delivery is at-least-once: a message
+/// may be replayed again if the process exits in the middle of a replay.
+#[derive(Debug)]
+pub struct Spool {
+    config: SpoolConfig,
+    state: Mutex<SpoolState>,
+    spooled: AtomicUsize,
+    replayed: AtomicUsize,
+    discarded: AtomicUsize,
+}
+
+impl Spool {
+    /// Open spool directory, creating it if necessary
+    pub fn open(config: SpoolConfig) -> SyslogResult<Spool> {
+        let segments = read_segments(&config).map_err(|err| {
+                SyslogError::connect(Transport::Spool, config.directory.display(), err)
+            })?;
+
+        Ok(Spool {
+               config: config,
+               state: Mutex::new(SpoolState {
+                                     segments: segments.into_iter().collect(),
+                                     read_offset: 0,
+                                 }),
+               spooled: AtomicUsize::new(0),
+               replayed: AtomicUsize::new(0),
+               discarded: AtomicUsize::new(0),
+           })
+    }
+
+    /// Spool configuration
+    pub fn config(&self) -> &SpoolConfig {
+        &self.config
+    }
+
+    /// Metrics snapshot
+    pub fn stats(&self) -> SpoolStats {
+        SpoolStats {
+            spooled: self.spooled.load(Ordering::Relaxed),
+            replayed: self.replayed.load(Ordering::Relaxed),
+            discarded: self.discarded.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Whether there is nothing left to replay
+    pub fn is_empty(&self) -> bool {
+        self.state
+            .lock()
+            .map(|s| s.segments.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Append message to the spool
+    pub fn push(&self, message: &[u8]) -> SyslogResult<()> {
+        let mut state = self.lock()?;
+
+        let needs_segment = match state.segments.back() {
+            Some(segment) => segment.sealed || segment.size >= self.config.segment_size,
+            None => true,
+        };
+
+        if needs_segment {
+            let id = state.segments.back().map(|s| s.id + 1).unwrap_or(0);
+            let path = self.config
+                .directory
+                .join(format!("{:020}.{}", id, SEGMENT_EXTENSION));
+            state.segments.push_back(Segment {
+                                         id: id,
+                                         path: path,
+                                         size: 0,
+                                         sealed: false,
+                                     });
+        }
+
+        {
+            let segment = state.segments.back_mut().expect("Spool segment is missing");
+            let mut file = fs::OpenOptions::new().create(true)
+                .append(true)
+                .open(&segment.path)
+                .map_err(|err| self.error(err))?;
+            let mut entry = Vec::<u8>::with_capacity(ENTRY_HEADER_LEN + message.len());
+            entry.extend_from_slice(&ENTRY_MAGIC);
+            entry.extend_from_slice(&u32_to_bytes(message.len() as u32));
+            entry.extend_from_slice(&u32_to_bytes(crc32(message)));
+            entry.extend_from_slice(message);
+            if let Err(err) = file.write_all(&entry) {
+                // Part of the entry may have been written, further ones go to a new segment
+                segment.sealed = true;
+                segment.size = file.metadata().map(|m| m.len()).unwrap_or(segment.size);
+                return Err(self.error(err));
+            }
+            segment.size += entry.len() as u64;
+        }
+        self.spooled.fetch_add(1, Ordering::Relaxed);
+
+        // Enforce size cap, always keeping the segment we've just written
+        while state.total_size() > self.config.max_size && state.segments.len() > 1 {
+            let offset = state.read_offset;
+            let segment = state.segments.pop_front().expect("Spool segment is missing");
+            let dropped = count_entries(&segment.path, offset).unwrap_or(1);
+            self.discarded.fetch_add(dropped, Ordering::Relaxed);
+            fs::remove_file(&segment.path).map_err(|err| self.error(err))?;
+            state.read_offset = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Replay spooled messages in order, oldest first
+    ///
+    /// Stops on the first `send` failure, leaving the failed message
+    /// at the head of the spool. Corrupted or torn bytes are skipped up to
+    /// the next valid entry, and counted as one discarded message.
+    /// Returns number of replayed messages.
+    pub fn replay<S>(&self, mut send: S) -> SyslogResult<usize>
+        where S: FnMut(&[u8]) -> SyslogResult<()>
+    {
+        let mut state = self.lock()?;
+        let mut replayed = 0;
+
+        while let Some(path) = state.segments.front().map(|s| s.path.clone()) {
+            let data = read_from(&path, state.read_offset).map_err(|err| self.error(err))?;
+
+            let mut position = 0;
+            while position < data.len() {
+                match next_entry(&data, position) {
+                    Entry::Valid(message, end) => {
+                        send(message)?;
+                        state.read_offset += (end - position) as u64;
+                        position = end;
+                        replayed += 1;
+                        self.replayed.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Entry::Corrupted(next) => {
+                        self.discarded.fetch_add(1, Ordering::Relaxed);
+                        state.read_offset += (next - position) as u64;
+                        position = next;
+                    }
+                }
+            }
+
+            state.segments.pop_front();
+            state.read_offset = 0;
+            fs::remove_file(&path).map_err(|err| self.error(err))?;
+        }
+
+        Ok(replayed)
+    }
+
+    fn lock(&self) -> SyslogResult<MutexGuard<SpoolState>> {
+        self.state
+            .lock()
+            .map_err(|_| SyslogError::poisoned(Transport::Spool, self.config.directory.display()))
+    }
+
+    fn error(&self, err: io::Error) -> SyslogError {
+        SyslogError::send(Transport::Spool, self.config.directory.display(), err)
+    }
+}
+
+// Segment contents from the offset
+fn read_from(path: &Path, offset: u64) -> io::Result<Vec<u8>> {
+    let mut file = fs::File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// Entry at a position of segment data
+enum Entry<'a> {
+    // Message and the position after it
+    Valid(&'a [u8], usize),
+    // Position of the next valid entry, or the end of the data
+    Corrupted(usize),
+}
+
+// Message of the entry at the position, `None` if it's corrupted or truncated
+fn parse_entry(data: &[u8], position: usize) -> Option<&[u8]> {
+    let data = &data[position..];
+    if data.len() < ENTRY_HEADER_LEN || data[0..4] != ENTRY_MAGIC[..] {
+        return None;
+    }
+    let len = u32_from_bytes(&data[4..8]);
+    let checksum = u32_from_bytes(&data[8..12]);
+    if len > MAX_ENTRY_LEN || data.len() - ENTRY_HEADER_LEN < len as usize {
+        return None;
+    }
+    let message = &data[ENTRY_HEADER_LEN..ENTRY_HEADER_LEN + len as usize];
+    if crc32(message) != checksum {
+        return None;
+    }
+    Some(message)
+}
+
+fn next_entry(data: &[u8], position: usize) -> Entry {
+    match parse_entry(data, position) {
+        Some(message) => Entry::Valid(message, position + ENTRY_HEADER_LEN + message.len()),
+        None => Entry::Corrupted(resync(data, position + 1)),
+    }
+}
+
+// Position of the next valid entry from the position, or the end of the data.
+// Only positions of entry markers are checked, keeping the scan linear.
+fn resync(data: &[u8], mut position: usize) -> usize {
+    while position < data.len() {
+        let marker = data[position..]
+            .windows(ENTRY_MAGIC.len())
+            .position(|window| window == &ENTRY_MAGIC[..]);
+        match marker {
+            Some(offset) if parse_entry(data, position + offset).is_some() => {
+                return position + offset;
+            }
+            Some(offset) => position += offset + 1,
+            None => break,
+        }
+    }
+    data.len()
+}
+
+// Number of entries left in the segment, starting at offset,
+// every corrupted region counts as one
+fn count_entries(path: &Path, offset: u64) -> io::Result<usize> {
+    let data = read_from(path, offset)?;
+    let mut count = 0;
+    let mut position = 0;
+    while position < data.len() {
+        position = match next_entry(&data, position) {
+            Entry::Valid(_, end) => end,
+            Entry::Corrupted(next) => next,
+        };
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn u32_to_bytes(value: u32) -> [u8; 4] {
+    [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+fn u32_from_bytes(bytes: &[u8]) -> u32 {
+    (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+// Segments left over from a previous run, oldest first
+fn read_segments(config: &SpoolConfig) -> io::Result<Vec<Segment>> {
+    fs::create_dir_all(&config.directory)?;
+
+    let mut segments = Vec::<Segment>::new();
+    for entry in fs::read_dir(&config.directory)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(SEGMENT_EXTENSION) {
+            continue;
+        }
+        let id = path.file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse::<u64>().ok());
+        if let Some(id) = id {
+            let size = fs::metadata(&path)?.len();
+            segments.push(Segment {
+                              id: id,
+                              path: path,
+                              size: size,
+                              sealed: true,
+                          });
+        }
+    }
+    segments.sort_by_key(|s| s.id);
+    Ok(segments)
+}
+
+/// CRC32 (IEEE 802.3) checksum
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Drain spooling undeliverable messages to disk
+///
+/// Messages which fail to send are kept in the in-memory buffer, messages
+/// overflowing it are appended to the on-disk spool, oldest first.
+/// While messages are pending, new messages are queued too, to keep them in order.
+/// Every `retry_interval` the drain reconnects the underlying transport
+/// and replays the spool, then the in-memory buffer. The in-memory buffer
+/// is written to the spool when the drain is dropped.
+#[derive(Debug)]
+pub struct SpoolDrain<D>
+    where D: SyslogDrain
+{
+    drain: D,
+    spool: Spool,
+    // Pending messages newer than the spooled ones
+    memory: Mutex<VecDeque<Vec<u8>>>,
+    last_attempt: Mutex<Option<Instant>>,
+}
+
+impl<D> SpoolDrain<D>
+    where D: SyslogDrain
+{
+    /// Wrap connected drain, opening the spool
+    pub fn new(drain: D, config: SpoolConfig) -> SyslogResult<SpoolDrain<D>> {
+        Ok(SpoolDrain {
+               drain: drain,
+               spool: Spool::open(config)?,
+               memory: Mutex::new(VecDeque::new()),
+               last_attempt: Mutex::new(None),
+           })
+    }
+
+    /// Spool metrics snapshot
+    pub fn stats(&self) -> SpoolStats {
+        self.spool.stats()
+    }
+
+    /// Reconnect and replay the spool and the in-memory buffer, regardless of retry interval
+    pub fn flush(&self) -> SyslogResult<usize> {
+        if !self.is_pending() {
+            return Ok(0);
+        }
+        self.drain.reconnect()?;
+        let drain = &self.drain;
+        // Locked first, so no message moves to the spool while it's replayed
+        let mut memory = self.lock_memory()?;
+        let mut replayed = self.spool.replay(|message| drain.send(message))?;
+        while let Some(message) = memory.pop_front() {
+            if let Err(err) = drain.send(&message) {
+                memory.push_front(message);
+                return Err(err);
+            }
+            replayed += 1;
+        }
+        Ok(replayed)
+    }
+
+    // Whether there are messages in the spool or the in-memory buffer
+    fn is_pending(&self) -> bool {
+        !self.spool.is_empty() || self.memory.lock().map(|m| !m.is_empty()).unwrap_or(false)
+    }
+
+    // Queue the message, moving the oldest ones overflowing the in-memory buffer to the spool
+    fn queue(&self, message: &[u8]) -> SyslogResult<()> {
+        let mut memory = self.lock_memory()?;
+        memory.push_back(message.to_vec());
+        while memory.len() > self.spool.config().memory_buffer {
+            match memory.pop_front() {
+                Some(oldest) => self.spool.push(&oldest)?,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn lock_memory(&self) -> SyslogResult<MutexGuard<VecDeque<Vec<u8>>>> {
+        self.memory
+            .lock()
+            .map_err(|_| {
+                         SyslogError::poisoned(Transport::Spool,
+                                               self.spool.config().directory.display())
+                     })
+    }
+
+    // Replay the spool, if retry interval has elapsed
+    fn try_replay(&self) {
+        if !self.is_pending() {
+            return;
+        }
+
+        let retry_interval = self.spool.config().retry_interval;
+        let due = match self.last_attempt.lock() {
+            Ok(mut last_attempt) => {
+                let due = last_attempt.map(|t| t.elapsed() >= retry_interval).unwrap_or(true);
+                if due {
+                    *last_attempt = Some(Instant::now());
+                }
+                due
+            }
+            Err(_) => false,
+        };
+
+        if due {
+            // Failures will be retried on the next attempt
+            let _ = self.flush();
+        }
+    }
+}
+
+impl<D> SyslogDrain for SpoolDrain<D>
+    where D: SyslogDrain
+{
+    fn format(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        self.drain.format(info, logger_values)
+    }
+
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        self.try_replay();
+
+        if self.is_pending() {
+            return self.queue(message);
+        }
+
+        match self.drain.send(message) {
+            Ok(()) => Ok(()),
+            Err(_) => self.queue(message),
+        }
+    }
+
+    fn reconnect(&self) -> SyslogResult<()> {
+        self.drain.reconnect()
+    }
+
+    // Spooled messages are reported by the inherent `stats`
+    fn stats(&self) -> Option<DrainStats> {
+        self.drain.stats()
+    }
+}
+
+impl<D> Drain for SpoolDrain<D>
+    where D: SyslogDrain
+{
+    type Error = SyslogError;
+
+    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<()> {
+        let buf = self.format(info, logger_values)?;
+        self.send(buf.as_slice())
+    }
+}
+
+impl<D> Drop for SpoolDrain<D>
+    where D: SyslogDrain
+{
+    fn drop(&mut self) {
+        if let Ok(mut memory) = self.memory.lock() {
+            for message in memory.drain(..) {
+                let _ = self.spool.push(&message);
+            }
+        }
+    }
+}
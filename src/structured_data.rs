@@ -0,0 +1,315 @@
+use error::{SyslogError, SyslogResult};
+use std::net::IpAddr;
+
+/// SD-IDs registered with IANA, which don't need the `@<enterprise number>` suffix
+pub const REGISTERED_SD_IDS: &'static [&'static str] = &["timeQuality", "origin", "meta"];
+
+/// SD-NAME length limit
+pub const SD_NAME_MAX_LEN: usize = 32;
+
+/// `origin` element `software` length limit
+pub const SD_SOFTWARE_MAX_LEN: usize = 48;
+
+/// `origin` element `swVersion` length limit
+pub const SD_SW_VERSION_MAX_LEN: usize = 32;
+
+/// Check SD-ID or PARAM-NAME against RFC5424 SD-NAME: 1-32 printable US-ASCII
+/// characters, except `=`, space, `]` and `"`
+pub fn validate_sd_name(name: &str) -> SyslogResult<()> {
+    if name.is_empty() {
+        return invalid("SD-NAME is empty".to_string());
+    }
+    if name.len() > SD_NAME_MAX_LEN {
+        return invalid(format!("SD-NAME {:?} is longer than {} characters",
+                               name,
+                               SD_NAME_MAX_LEN));
+    }
+    if !name.bytes().all(|b| b >= 33 && b <= 126 && b != b'=' && b != b']' && b != b'"') {
+        return invalid(format!("SD-NAME {:?} contains characters not allowed by RFC5424", name));
+    }
+    Ok(())
+}
+
+fn invalid(message: String) -> SyslogResult<()> {
+    Err(SyslogError::ConfigInvalid(message))
+}
+
+/// RFC5424 PARAM-VALUE with `"`, `\\` and `]` escaped
+pub fn escape_param_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '"' || c == '\\' || c == ']' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// RFC5424 SD-ELEMENT for a group of keys
+///
+/// Values of the keys are moved out of the record and logger elements
+/// into this one. Used by RFC5424 native serialization only, key=value
+/// formats emit the keys as usual.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdElement {
+    /// SD-ID, one of the registered ones or `name@<private enterprise number>`
+    pub id: String,
+    /// Keys starting with the prefix go to the element, PARAM-NAME is the rest of the key
+    ///
+    /// Default: `None`
+    pub prefix: Option<String>,
+    /// Keys going to the element, with their PARAM-NAMEs
+    pub params: Vec<(String, String)>,
+}
+
+impl SdElement {
+    /// Element with the SD-ID and no keys
+    pub fn new<VALUE: Into<String>>(id: VALUE) -> SdElement {
+        SdElement {
+            id: id.into(),
+            prefix: None,
+            params: Vec::new(),
+        }
+    }
+
+    /// Registered `origin` element for `origin.` keys, e.g. `origin.ip`, `origin.software`
+    pub fn origin() -> SdElement {
+        SdElement::new("origin").prefix("origin.")
+    }
+
+    /// Registered `timeQuality` element for `timeQuality.` keys, e.g. `timeQuality.isSynced`
+    pub fn time_quality() -> SdElement {
+        SdElement::new("timeQuality").prefix("timeQuality.")
+    }
+
+    /// Registered `meta` element for `meta.` keys, e.g. `meta.sequenceId`
+    pub fn meta() -> SdElement {
+        SdElement::new("meta").prefix("meta.")
+    }
+
+    /// Keys starting with the prefix go to the element, without the prefix
+    pub fn prefix<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.prefix = Some(value.into());
+        self
+    }
+
+    /// Key going to the element as `name` parameter
+    pub fn param<K: Into<String>, N: Into<String>>(mut self, key: K, name: N) -> Self {
+        self.params.push((key.into(), name.into()));
+        self
+    }
+
+    /// Key going to the element under its own name
+    pub fn key<VALUE: Into<String>>(self, value: VALUE) -> Self {
+        let key = value.into();
+        self.param(key.clone(), key)
+    }
+
+    /// PARAM-NAME of the key, if it goes to this element
+    ///
+    /// Keys with the prefix, the rest of which isn't a valid PARAM-NAME, don't.
+    pub fn param_name<'a>(&'a self, key: &'a str) -> Option<&'a str> {
+        if let Some(&(_, ref name)) = self.params.iter().find(|&&(ref k, _)| k == key) {
+            return Some(name);
+        }
+        match self.prefix {
+            Some(ref prefix) if key.starts_with(prefix.as_str()) => {
+                let name = &key[prefix.len()..];
+                validate_sd_name(name).ok().map(|_| name)
+            }
+            _ => None,
+        }
+    }
+
+    /// Check SD-ID and PARAM-NAMEs against RFC5424
+    pub fn validate(&self) -> SyslogResult<()> {
+        validate_sd_name(&self.id)?;
+        match self.id.find('@') {
+            Some(index) => {
+                let number = &self.id[index + 1..];
+                let digits = number.bytes().all(|b| (b >= b'0' && b <= b'9') || b == b'.');
+                if number.is_empty() || !digits {
+                    return invalid(format!("SD-ID {:?} doesn't end with @<enterprise number>",
+                                           self.id));
+                }
+            }
+            None => {
+                if !REGISTERED_SD_IDS.contains(&self.id.as_str()) {
+                    let message = format!("SD-ID {:?} is not registered, add @<enterprise number>",
+                                          self.id);
+                    return invalid(message);
+                }
+            }
+        }
+        for &(_, ref name) in &self.params {
+            validate_sd_name(name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Registered RFC5424 SD-ELEMENTs added to every message
+///
+/// * `[timeQuality tzKnown="1" isSynced="1"]`: timestamps always carry their
+///   offset, `isSynced` is only known on Linux
+/// * `[origin ip="10.0.0.1" software="app" swVersion="1.0"]`: addresses of the local
+///   interfaces, this crate name and version unless `software` is set
+/// * `[meta sequenceId="1" sysUpTime="12345"]`: sequence counted by every drain from 1,
+///   uptime in hundredths of a second
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AutoStructuredData {
+    /// Add `timeQuality` element
+    pub time_quality: bool,
+    /// Add `origin` element
+    pub origin: bool,
+    /// Add `meta` element
+    pub meta: bool,
+    /// Addresses for the `origin` element
+    ///
+    /// Default: empty, addresses of the local interfaces are detected
+    pub origin_ips: Vec<IpAddr>,
+    /// `software` and `swVersion` for the `origin` element
+    ///
+    /// Default: `None`, this crate name and version
+    pub software: Option<(String, String)>,
+}
+
+impl AutoStructuredData {
+    /// No automatic elements
+    pub fn new() -> AutoStructuredData {
+        AutoStructuredData::default()
+    }
+
+    /// All of `timeQuality`, `origin` and `meta` elements
+    pub fn all() -> AutoStructuredData {
+        AutoStructuredData::new().time_quality(true).origin(true).meta(true)
+    }
+
+    /// Add `timeQuality` element
+    pub fn time_quality(mut self, value: bool) -> Self {
+        self.time_quality = value;
+        self
+    }
+
+    /// Add `origin` element
+    pub fn origin(mut self, value: bool) -> Self {
+        self.origin = value;
+        self
+    }
+
+    /// Add `meta` element
+    pub fn meta(mut self, value: bool) -> Self {
+        self.meta = value;
+        self
+    }
+
+    /// Address for the `origin` element, instead of the detected ones
+    pub fn origin_ip<VALUE: Into<IpAddr>>(mut self, value: VALUE) -> Self {
+        self.origin_ips.push(value.into());
+        self
+    }
+
+    /// `software` and `swVersion` for the `origin` element, e.g. application name and version
+    pub fn software<N: Into<String>, V: Into<String>>(mut self, name: N, version: V) -> Self {
+        self.software = Some((name.into(), version.into()));
+        self
+    }
+
+    /// Whether any element is added
+    pub fn is_empty(&self) -> bool {
+        !(self.time_quality || self.origin || self.meta)
+    }
+
+    /// Check `software` and `swVersion` against RFC5424 limits
+    pub fn validate(&self) -> SyslogResult<()> {
+        if let Some((ref name, ref version)) = self.software {
+            if name.is_empty() || name.len() > SD_SOFTWARE_MAX_LEN {
+                return invalid(format!("origin software {:?} must be 1-{} characters",
+                                       name,
+                                       SD_SOFTWARE_MAX_LEN));
+            }
+            if version.is_empty() || version.len() > SD_SW_VERSION_MAX_LEN {
+                return invalid(format!("origin swVersion {:?} must be 1-{} characters",
+                                       version,
+                                       SD_SW_VERSION_MAX_LEN));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Source location of records: file, line, module and function (when the record has one)
+///
+/// Added as `source.file`, `source.line`, ... keys by key=value formats, and as
+/// `[source@32473 file="src/db.rs" line="42" module="app::db"]` SD-ELEMENT by RFC5424
+/// native serialization. Records without a file, e.g. of the `log` bridge, get none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLocation {
+    /// SD-ID of the element, `name@<private enterprise number>`
+    pub sd_id: String,
+    /// Prefix of the keys of key=value formats
+    ///
+    /// Default: `source.`
+    pub prefix: String,
+    /// Leading part removed from the file paths, e.g. `env!("CARGO_MANIFEST_DIR")`
+    ///
+    /// Default: `None`, only paths of cargo registry and git checkouts are trimmed
+    pub trim_prefix: Option<String>,
+}
+
+impl SourceLocation {
+    /// Location in the element with the SD-ID
+    pub fn new<VALUE: Into<String>>(sd_id: VALUE) -> SourceLocation {
+        SourceLocation {
+            sd_id: sd_id.into(),
+            prefix: "source.".to_string(),
+            trim_prefix: None,
+        }
+    }
+
+    /// Prefix of the keys of key=value formats
+    pub fn prefix<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.prefix = value.into();
+        self
+    }
+
+    /// Leading part removed from the file paths, e.g. the crate root
+    pub fn trim_prefix<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.trim_prefix = Some(value.into());
+        self
+    }
+
+    /// File path relative to the crate root
+    pub fn trim_path<'a>(&self, path: &'a str) -> &'a str {
+        trim_source_path(path, self.trim_prefix.as_ref().map(String::as_str))
+    }
+
+    /// Check SD-ID against RFC5424
+    pub fn validate(&self) -> SyslogResult<()> {
+        SdElement::new(self.sd_id.as_str()).validate()
+    }
+}
+
+/// Source file path relative to its crate root: without the prefix directory, or the
+/// `~/.cargo/registry/src/<index>/` and `~/.cargo/git/checkouts/` parts of dependencies
+pub fn trim_source_path<'a>(path: &'a str, prefix: Option<&str>) -> &'a str {
+    if let Some(prefix) = prefix.map(|prefix| prefix.trim_right_matches('/')) {
+        // Whole path components only, `/home/ci/app` isn't a prefix of `/home/ci/app2/..`
+        if !prefix.is_empty() && path.starts_with(prefix) &&
+           path[prefix.len()..].starts_with('/') {
+            return path[prefix.len()..].trim_left_matches('/');
+        }
+    }
+    for marker in &["/.cargo/registry/src/", "/.cargo/git/checkouts/"] {
+        if let Some(index) = path.find(marker) {
+            // Skip the registry index, or the repository, directory
+            let rest = &path[index + marker.len()..];
+            return match rest.find('/') {
+                Some(index) => &rest[index + 1..],
+                None => rest,
+            };
+        }
+    }
+    path.trim_left_matches("./")
+}
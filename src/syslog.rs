@@ -1,3 +1,4 @@
+use error::SyslogError;
 use slog;
 use std::fmt;
 use std::str::FromStr;
@@ -7,6 +8,15 @@ use std::str::FromStr;
 pub const SYSLOG_DEFAULT_UDS_LOCATIONS: &'static [&'static str] =
     &["/dev/log", "/var/run/syslog", "/var/run/log"];
 
+/// Default syslog port for UDP and TCP
+pub const SYSLOG_DEFAULT_PORT: u16 = 514;
+
+/// Default syslog over TLS port (RFC5425)
+pub const SYSLOG_DEFAULT_TLS_PORT: u16 = 6514;
+
+/// RFC5424 HOSTNAME length limit
+pub const HOSTNAME_MAX_LEN: usize = 255;
+
 /// Syslog Severity
 #[allow(non_camel_case_types)]
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -43,8 +53,8 @@ impl From<slog::Level> for Severity {
 }
 
 impl FromStr for Severity {
-    type Err = ();
-    fn from_str(s: &str) -> Result<Severity, ()> {
+    type Err = SyslogError;
+    fn from_str(s: &str) -> Result<Severity, SyslogError> {
         let result = match &s.to_lowercase()[..] {
             "log_emerg" | "emerg" | "panic" => Severity::LOG_EMERG,
             "log_alert" | "alert" => Severity::LOG_ALERT,
@@ -54,7 +64,7 @@ impl FromStr for Severity {
             "log_notice" | "notice" => Severity::LOG_NOTICE,
             "log_info" | "info" => Severity::LOG_INFO,
             "log_debug" | "debug" => Severity::LOG_DEBUG,
-            _ => return Err(()),
+            _ => return Err(SyslogError::ConfigInvalid(format!("Unknown severity: {}", s))),
         };
         Ok(result)
     }
@@ -121,8 +131,8 @@ impl Facility {
 }
 
 impl FromStr for Facility {
-    type Err = ();
-    fn from_str(s: &str) -> Result<Facility, ()> {
+    type Err = SyslogError;
+    fn from_str(s: &str) -> Result<Facility, SyslogError> {
         let result = match &s.to_lowercase()[..] {
             "log_kern" | "kern" => Facility::LOG_KERN,
             "log_user" | "user" => Facility::LOG_USER,
@@ -144,7 +154,7 @@ impl FromStr for Facility {
             "log_local5" | "local5" => Facility::LOG_LOCAL5,
             "log_local6" | "local6" => Facility::LOG_LOCAL6,
             "log_local7" | "local7" => Facility::LOG_LOCAL7,
-            _ => return Err(()),
+            _ => return Err(SyslogError::ConfigInvalid(format!("Unknown facility: {}", s))),
         };
         Ok(result)
     }
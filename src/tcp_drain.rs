@@ -1,10 +1,15 @@
+use config::FormatMode;
+use drain::SyslogDrain;
+use error::{SyslogError, SyslogResult, Transport};
+use metrics::{ConnectionState, DrainMetrics, DrainStats, StateChangeHandler};
 use slog::{Drain, OwnedKeyValueList, Record};
 use slog_stream::Format as StreamFormat;
-use std::io;
-use std::io::{Write, Cursor};
+use std::io::{self, Write};
 use std::marker::PhantomData;
 use std::net::{Shutdown, TcpStream, SocketAddr};
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 
 /// Delimited messages
@@ -13,17 +18,107 @@ pub struct DelimitedMessages;
 /// Framed messages
 pub struct FramedMessages;
 
+/// Message framing for stream transports (RFC6587)
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Framing {
+    /// Octet counting: message is prefixed with its length and a space
+    Octet,
+    /// Non-transparent framing: message is followed by the delimiter, usually LF
+    NonTransparent(u8),
+}
+
+impl Framing {
+    /// Framing recommended for the format: octet counting for RFC5424, LF for RFC3164
+    pub fn for_mode(mode: &FormatMode) -> Framing {
+        match *mode {
+            FormatMode::RFC3164 => Framing::NonTransparent(b'\n'),
+            FormatMode::RFC5424 => Framing::Octet,
+        }
+    }
+
+    /// Frame formatted message
+    ///
+    /// With non-transparent framing, message end (LF, NUL) written by the formatter
+    /// is replaced by the delimiter, so it doesn't leak into the next message.
+    pub fn frame(&self, message: &[u8]) -> Vec<u8> {
+        match *self {
+            Framing::Octet => {
+                let mut frame = format!("{} ", message.len()).into_bytes();
+                frame.extend_from_slice(message);
+                frame
+            }
+            Framing::NonTransparent(delimiter) => {
+                let mut end = message.len();
+                while end > 0 && (message[end - 1] == b'\0' || message[end - 1] == b'\n') {
+                    end -= 1;
+                }
+                let mut frame = Vec::<u8>::with_capacity(end + 1);
+                frame.extend_from_slice(&message[..end]);
+                frame.push(delimiter);
+                frame
+            }
+        }
+    }
+}
+
+impl FromStr for Framing {
+    type Err = SyslogError;
+    fn from_str(s: &str) -> Result<Framing, SyslogError> {
+        let result = match &s.to_lowercase()[..] {
+            "octet" | "octet-counting" => Framing::Octet,
+            "lf" | "newline" => Framing::NonTransparent(b'\n'),
+            "nul" | "null" => Framing::NonTransparent(b'\0'),
+            _ => return Err(SyslogError::ConfigInvalid(format!("Unknown framing: {}", s))),
+        };
+        Ok(result)
+    }
+}
+
+/// Timeouts of TCP and TLS streams
+///
+/// `None` means the OS default for connect and blocking writes.
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct StreamTimeouts {
+    /// Timeout of establishing the connection
+    pub connect: Option<Duration>,
+    /// Timeout of writing a message
+    pub write: Option<Duration>,
+}
+
+impl StreamTimeouts {
+    /// Open TCP stream to the address, applying the timeouts
+    pub fn open(&self, addr: SocketAddr) -> io::Result<TcpStream> {
+        let stream = match self.connect {
+            Some(timeout) => TcpStream::connect_timeout(&addr, timeout)?,
+            None => TcpStream::connect(addr)?,
+        };
+        stream.set_write_timeout(self.write)?;
+        Ok(stream)
+    }
+}
+
 /// State: `TCPDisconnected`` for the TCP drain
 #[derive(Debug)]
 pub struct TCPDisconnected {
     addr: SocketAddr,
+    delimiter: u8,
+    timeouts: StreamTimeouts,
+    lazy: bool,
+    metrics: DrainMetrics,
 }
 
 /// State: `TCPConnected` for the TCP drain
+///
+/// With lazy connect, the stream is opened when the first message is sent,
+/// a stream broken by a failed write is opened again on the next one.
 #[derive(Debug)]
 pub struct TCPConnected {
-    stream: Arc<Mutex<TcpStream>>,
+    stream: Arc<Mutex<Option<TcpStream>>>,
     addr: SocketAddr,
+    delimiter: u8,
+    timeouts: StreamTimeouts,
+    lazy: bool,
+    metrics: DrainMetrics,
 }
 
 /// TCP drain
@@ -43,92 +138,237 @@ impl<T, F> TCPDrain<T, TCPDisconnected, F>
     pub fn new(addr: SocketAddr, formatter: F) -> TCPDrain<T, TCPDisconnected, F> {
         TCPDrain::<T, TCPDisconnected, F> {
             formatter: formatter,
-            connection: TCPDisconnected { addr: addr },
+            connection: TCPDisconnected {
+                addr: addr,
+                delimiter: b'\n',
+                timeouts: StreamTimeouts::default(),
+                lazy: false,
+                metrics: DrainMetrics::default(),
+            },
             _message_type: PhantomData,
         }
     }
 
+    /// Timeout of establishing the connection
+    ///
+    /// Default: `None`, OS default
+    pub fn connect_timeout(mut self, value: Duration) -> Self {
+        self.connection.timeouts.connect = Some(value);
+        self
+    }
+
+    /// Timeout of writing a message
+    ///
+    /// Default: `None`, writes block until the server accepts the message
+    pub fn write_timeout(mut self, value: Duration) -> Self {
+        self.connection.timeouts.write = Some(value);
+        self
+    }
+
+    /// Defer connecting until the first message is sent, like `openlog`
+    /// without `LOG_NDELAY`
+    ///
+    /// `connect` then never fails, connection errors are returned by `log`.
+    ///
+    /// Default: false
+    pub fn lazy_connect(mut self, value: bool) -> Self {
+        self.connection.lazy = value;
+        self
+    }
+
+    /// Handler called when the drain connects or disconnects
+    pub fn on_state_change<H>(mut self, handler: H) -> Self
+        where H: Fn(ConnectionState, &str) + Send + Sync + 'static
+    {
+        self.connection.metrics.set_handler(Arc::new(handler) as Arc<StateChangeHandler>);
+        self
+    }
+
     /// Connect TCP stream
-    pub fn connect(self) -> io::Result<TCPDrain<T, TCPConnected, F>> {
-        let stream = TcpStream::connect(self.connection.addr)?;
+    pub fn connect(self) -> SyslogResult<TCPDrain<T, TCPConnected, F>> {
+        let stream = if self.connection.lazy {
+            None
+        } else {
+            Some(open_stream(self.connection.addr,
+                             &self.connection.timeouts,
+                             &self.connection.metrics)?)
+        };
         Ok(TCPDrain::<T, TCPConnected, F> {
                formatter: self.formatter,
                connection: TCPConnected {
                    stream: Arc::new(Mutex::new(stream)),
                    addr: self.connection.addr,
+                   delimiter: self.connection.delimiter,
+                   timeouts: self.connection.timeouts,
+                   lazy: self.connection.lazy,
+                   metrics: self.connection.metrics,
                },
                _message_type: PhantomData,
            })
     }
 }
 
+fn open_stream(addr: SocketAddr,
+               timeouts: &StreamTimeouts,
+               metrics: &DrainMetrics)
+               -> SyslogResult<TcpStream> {
+    let stream = timeouts.open(addr).map_err(|err| SyslogError::connect(Transport::TCP, addr, err));
+    let stream = metrics.record_connection(stream, &addr)?;
+    metrics.set_state(ConnectionState::Connected, &addr);
+    Ok(stream)
+}
+
+impl<F> TCPDrain<DelimitedMessages, TCPDisconnected, F>
+    where F: StreamFormat
+{
+    /// Message delimiter
+    ///
+    /// Default: LF
+    pub fn delimiter(mut self, value: u8) -> Self {
+        self.connection.delimiter = value;
+        self
+    }
+}
+
 impl<T, F> TCPDrain<T, TCPConnected, F>
     where F: StreamFormat
 {
+    /// Delivery metrics snapshot
+    pub fn stats(&self) -> DrainStats {
+        self.connection.metrics.snapshot()
+    }
+
     /// Disconnect TCP stream, completing all operations
-    pub fn disconnect(self) -> io::Result<TCPDrain<T, TCPDisconnected, F>> {
-        self.connection
+    pub fn disconnect(self) -> SyslogResult<TCPDrain<T, TCPDisconnected, F>> {
+        let addr = self.connection.addr;
+        let stream = self.connection
             .stream
             .lock()
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Couldn't acquire lock"))
-            .and_then(|s| s.shutdown(Shutdown::Both))?;
+            .map_err(|_| SyslogError::poisoned(Transport::TCP, addr))?
+            .take();
+        if let Some(stream) = stream {
+            stream.shutdown(Shutdown::Both)
+                .map_err(|err| SyslogError::send(Transport::TCP, addr, err))?;
+        }
+        self.connection.metrics.set_state(ConnectionState::Disconnected, &addr);
         Ok(TCPDrain::<T, TCPDisconnected, F> {
                formatter: self.formatter,
-               connection: TCPDisconnected { addr: self.connection.addr },
+               connection: TCPDisconnected {
+                   addr: self.connection.addr,
+                   delimiter: self.connection.delimiter,
+                   timeouts: self.connection.timeouts,
+                   lazy: self.connection.lazy,
+                   metrics: self.connection.metrics,
+               },
                _message_type: PhantomData,
            })
     }
 }
 
-// RFC3164 messages over TCP don't require framed headers
-impl<F> Drain for TCPDrain<DelimitedMessages, TCPConnected, F>
+impl<T, F> TCPDrain<T, TCPConnected, F>
     where F: StreamFormat
 {
-    type Error = io::Error;
-
-    #[allow(dead_code)]
-    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> io::Result<()> {
-
-        // Should be thread safe - redo the buffering
+    fn format_message(&self,
+                      info: &Record,
+                      logger_values: &OwnedKeyValueList)
+                      -> SyslogResult<Vec<u8>> {
         let mut buf = Vec::<u8>::with_capacity(4096);
+        let result = self.formatter
+            .format(&mut buf, info, logger_values)
+            .map_err(SyslogError::FormatFailed);
+        self.connection.metrics.record(result)?;
+        Ok(buf)
+    }
 
-        self.formatter.format(&mut buf, info, logger_values)?;
-        self.connection
+    fn reconnect_stream(&self) -> SyslogResult<()> {
+        let addr = self.connection.addr;
+        let stream = open_stream(addr, &self.connection.timeouts, &self.connection.metrics)?;
+        let mut guard = self.connection
             .stream
             .lock()
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Couldn't acquire lock"))
-            .and_then(|mut s| s.write(buf.as_slice()))?;
+            .map_err(|_| SyslogError::poisoned(Transport::TCP, addr))?;
+        *guard = Some(stream);
+        self.connection.metrics.reconnected(&addr);
+        Ok(())
+    }
 
+    // Lazily connected stream is opened on the first write, broken stream
+    // is dropped when writing fails and opened again on the next write
+    fn write_message(&self, framing: Framing, message: &[u8]) -> SyslogResult<()> {
+        let addr = self.connection.addr;
+        let frame = framing.frame(message);
+        let mut guard = self.connection
+            .stream
+            .lock()
+            .map_err(|_| SyslogError::poisoned(Transport::TCP, addr))?;
+        let metrics = &self.connection.metrics;
+        let mut stream = match guard.take() {
+            Some(stream) => stream,
+            None => open_stream(addr, &self.connection.timeouts, metrics)?,
+        };
+        let result = stream.write_all(&frame);
+        if result.is_ok() {
+            *guard = Some(stream);
+        }
+        let result = result.map_err(|err| SyslogError::send(Transport::TCP, addr, err));
+        metrics.record_connection(result, &addr)?;
+        metrics.sent(frame.len(), &addr);
         Ok(())
     }
 }
 
+// RFC3164 messages over TCP don't require framed headers
+impl<F> SyslogDrain for TCPDrain<DelimitedMessages, TCPConnected, F>
+    where F: StreamFormat
+{
+    fn format(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        self.format_message(info, logger_values)
+    }
+
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        self.write_message(Framing::NonTransparent(self.connection.delimiter), message)
+    }
+
+    fn reconnect(&self) -> SyslogResult<()> {
+        self.reconnect_stream()
+    }
+
+    fn stats(&self) -> Option<DrainStats> {
+        Some(self.connection.metrics.snapshot())
+    }
+}
+
 // RFC5424 messages require framed delimition, first we need to send
 // the length of the message in octets
-impl<F> Drain for TCPDrain<FramedMessages, TCPConnected, F>
+impl<F> SyslogDrain for TCPDrain<FramedMessages, TCPConnected, F>
     where F: StreamFormat
 {
-    type Error = io::Error;
+    fn format(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        self.format_message(info, logger_values)
+    }
 
-    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> io::Result<()> {
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        self.write_message(Framing::Octet, message)
+    }
 
-        // Should be thread safe - redo the buffering
-        let mut buf = Cursor::new(Vec::<u8>::with_capacity(10));
+    fn reconnect(&self) -> SyslogResult<()> {
+        self.reconnect_stream()
+    }
 
-        self.formatter.format(&mut buf, info, logger_values)?;
-        let length = buf.position();
+    fn stats(&self) -> Option<DrainStats> {
+        Some(self.connection.metrics.snapshot())
+    }
+}
 
-        self.connection
-            .stream
-            .lock()
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Couldn't acquire lock"))
-            .and_then(|mut s| {
-                          // Space spearated frame length
-                          s.write_fmt(format_args!("{} ", length))?;
-                          s.write(buf.into_inner().as_slice())
-                      })?;
+impl<T, F> Drain for TCPDrain<T, TCPConnected, F>
+    where F: StreamFormat,
+          TCPDrain<T, TCPConnected, F>: SyslogDrain
+{
+    type Error = SyslogError;
 
-        Ok(())
+    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<()> {
+        let buf = self.format(info, logger_values)?;
+        self.send(buf.as_slice())
     }
 }
 
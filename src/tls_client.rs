@@ -6,8 +6,10 @@ use std::io::BufReader;
 use std::net::TcpStream;
 use std::str;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use rustls;
+use rustls::Session;
 use webpki_roots;
 
 /// This encapsulates the TCP-level connection, some connection
@@ -25,14 +27,19 @@ impl fmt::Debug for TlsClient {
 
 }
 
-/// We implement `io::Write` and pass through to the TLS session
+/// We implement `io::Write` and pass through to the TLS session,
+/// encrypted records are written to the socket right away
 impl io::Write for TlsClient {
     fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
-        self.tls_session.write(bytes)
+        let written = self.tls_session.write(bytes)?;
+        self.write_tls()?;
+        Ok(written)
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        self.tls_session.flush()
+        self.tls_session.flush()?;
+        self.write_tls()?;
+        self.socket.flush()
     }
 }
 
@@ -49,6 +56,44 @@ impl TlsClient {
             tls_session: rustls::ClientSession::new(&cfg, hostname),
         }
     }
+
+    /// Complete the TLS handshake, failing with `TimedOut` if it takes longer than timeout
+    pub fn handshake(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let read_timeout = self.socket.read_timeout()?;
+
+        while self.tls_session.is_handshaking() {
+            if let Some(deadline) = deadline {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "TLS handshake timed out"));
+                }
+                self.socket.set_read_timeout(Some(deadline - now))?;
+            }
+
+            self.write_tls()?;
+            if self.tls_session.is_handshaking() && self.tls_session.wants_read() {
+                if self.tls_session.read_tls(&mut self.socket)? == 0 {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                              "connection closed during TLS handshake"));
+                }
+                self.tls_session
+                    .process_new_packets()
+                    .map_err(|err| {
+                                 io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err))
+                             })?;
+            }
+        }
+
+        self.socket.set_read_timeout(read_timeout)
+    }
+
+    fn write_tls(&mut self) -> io::Result<()> {
+        while self.tls_session.wants_write() {
+            self.tls_session.write_tls(&mut self.socket)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Default)]
@@ -77,58 +122,69 @@ fn find_suite(name: &str) -> Option<&'static rustls::SupportedCipherSuite> {
 }
 
 /// Make a vector of ciphersuites named in `suites`
-pub fn lookup_suites(suites: &[String]) -> Vec<&'static rustls::SupportedCipherSuite> {
+pub fn lookup_suites(suites: &[String])
+                     -> Result<Vec<&'static rustls::SupportedCipherSuite>, String> {
     let mut out = Vec::new();
 
     for csname in suites {
         let scs = find_suite(csname);
         match scs {
             Some(s) => out.push(s),
-            None => panic!("cannot look up ciphersuite '{}'", csname),
+            None => return Err(format!("cannot look up ciphersuite '{}'", csname)),
         }
     }
 
-    out
+    Ok(out)
 }
 
-fn load_certs(filename: &str) -> Vec<rustls::Certificate> {
-    let certfile = fs::File::open(filename).expect("cannot open certificate file");
+pub fn load_certs(filename: &str) -> Result<Vec<rustls::Certificate>, String> {
+    let certfile = fs::File::open(filename)
+        .map_err(|err| format!("cannot open certificate file {}: {}", filename, err))?;
     let mut reader = BufReader::new(certfile);
-    rustls::internal::pemfile::certs(&mut reader).unwrap()
+    rustls::internal::pemfile::certs(&mut reader)
+        .map_err(|_| format!("cannot parse certificate file {}", filename))
 }
 
-fn load_private_key(filename: &str) -> rustls::PrivateKey {
-    let keyfile = fs::File::open(filename).expect("cannot open private key file");
+pub fn load_private_key(filename: &str) -> Result<rustls::PrivateKey, String> {
+    let keyfile = fs::File::open(filename)
+        .map_err(|err| format!("cannot open private key file {}: {}", filename, err))?;
     let mut reader = BufReader::new(keyfile);
-    let keys = rustls::internal::pemfile::rsa_private_keys(&mut reader).unwrap();
-    println!("{:?}", keys);
-    assert!(keys.len() == 1);
-    keys[0].clone()
+    let keys = rustls::internal::pemfile::rsa_private_keys(&mut reader)
+        .map_err(|_| format!("cannot parse private key file {}", filename))?;
+    if keys.len() != 1 {
+        return Err(format!("expected a single private key in {}, found {}",
+                           filename,
+                           keys.len()));
+    }
+    Ok(keys[0].clone())
 }
 
-fn load_key_and_cert(config: &mut rustls::ClientConfig, keyfile: &str, certsfile: &str) {
-    let certs = load_certs(certsfile);
-    let privkey = load_private_key(keyfile);
+fn load_key_and_cert(config: &mut rustls::ClientConfig,
+                     keyfile: &str,
+                     certsfile: &str)
+                     -> Result<(), String> {
+    let certs = load_certs(certsfile)?;
+    let privkey = load_private_key(keyfile)?;
 
     config.set_single_client_cert(certs, privkey);
+    Ok(())
 }
 
 /// Build a `ClientConfig` from our arguments
-pub fn make_config(args: &TLSSessionConfig) -> Arc<rustls::ClientConfig> {
+pub fn make_config(args: &TLSSessionConfig) -> Result<Arc<rustls::ClientConfig>, String> {
     let mut config = rustls::ClientConfig::new();
 
     if !args.suite.is_empty() {
-        config.ciphersuites = lookup_suites(&args.suite);
+        config.ciphersuites = lookup_suites(&args.suite)?;
     }
 
-    if args.cafile.is_some() {
-        let cafile = args.cafile.as_ref().unwrap();
-
-        let certfile = fs::File::open(&cafile).expect("Cannot open CA file");
+    if let Some(ref cafile) = args.cafile {
+        let certfile = fs::File::open(&cafile)
+            .map_err(|err| format!("cannot open CA file {}: {}", cafile, err))?;
         let mut reader = BufReader::new(certfile);
         config.root_store
             .add_pem_file(&mut reader)
-            .unwrap();
+            .map_err(|_| format!("cannot parse CA file {}", cafile))?;
     } else {
         config.root_store.add_trust_anchors(&webpki_roots::ROOTS);
     }
@@ -140,15 +196,12 @@ pub fn make_config(args: &TLSSessionConfig) -> Arc<rustls::ClientConfig> {
     config.set_protocols(&args.proto);
     config.set_mtu(&args.mtu);
 
-    if args.auth_key.is_some() || args.auth_certs.is_some() {
-        load_key_and_cert(&mut config,
-                          args.auth_key
-                              .as_ref()
-                              .expect("must provide auth-key with auth-certs"),
-                          args.auth_certs
-                              .as_ref()
-                              .expect("must provide auth-certs with auth-key"));
+    match (args.auth_key.as_ref(), args.auth_certs.as_ref()) {
+        (Some(key), Some(certs)) => load_key_and_cert(&mut config, key, certs)?,
+        (Some(_), None) => return Err("must provide auth-certs with auth-key".to_string()),
+        (None, Some(_)) => return Err("must provide auth-key with auth-certs".to_string()),
+        (None, None) => {}
     }
 
-    Arc::new(config)
+    Ok(Arc::new(config))
 }
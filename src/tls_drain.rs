@@ -1,29 +1,117 @@
+use drain::SyslogDrain;
+use error::{SyslogError, SyslogResult, Transport};
+use metrics::{ConnectionState, DrainMetrics, DrainStats, StateChangeHandler};
 use slog::{Drain, OwnedKeyValueList, Record};
 use slog_stream::Format as StreamFormat;
-use std::io;
-use std::io::{Write, Cursor};
+use std::io::Write;
 use std::marker::PhantomData;
-use std::net::{TcpStream, SocketAddr};
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tcp_drain::{DelimitedMessages, FramedMessages, Framing, StreamTimeouts};
 use tls_client::{TlsClient, TLSSessionConfig, make_config};
 
-/// Delimited messages
-pub struct DelimitedMessages;
+/// TLS session configuration
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TLSConfig {
+    /// CA certificates file (PEM) to verify the server with
+    ///
+    /// Default: `None`, Mozilla root certificates
+    pub ca_file: Option<String>,
+    /// Client certificate chain file (PEM), for client authentication
+    pub client_certs: Option<String>,
+    /// Client RSA private key file (PEM), for client authentication
+    pub client_key: Option<String>,
+    /// Cipher suite names, in the order of preference
+    ///
+    /// Default: empty, all the suites supported by rustls
+    pub cipher_suites: Vec<String>,
+    /// Server name to verify the certificate against
+    ///
+    /// Default: `None`, host part of the server address
+    pub server_name: Option<String>,
+    /// Timeout of the TLS handshake
+    ///
+    /// Default: `None`, wait for the server as long as it keeps the connection open
+    pub handshake_timeout: Option<Duration>,
+}
+
+impl TLSConfig {
+    /// TLS configuration with defaults
+    pub fn new() -> TLSConfig {
+        TLSConfig::default()
+    }
+
+    /// CA certificates file (PEM) to verify the server with
+    pub fn ca_file<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.ca_file = Some(value.into());
+        self
+    }
+
+    /// Client certificate chain and private key files (PEM), for client authentication
+    pub fn client_auth<C: Into<String>, K: Into<String>>(mut self, certs: C, key: K) -> Self {
+        self.client_certs = Some(certs.into());
+        self.client_key = Some(key.into());
+        self
+    }
+
+    /// Cipher suite names, in the order of preference
+    pub fn cipher_suites<I>(mut self, value: I) -> Self
+        where I: IntoIterator<Item = String>
+    {
+        self.cipher_suites = value.into_iter().collect();
+        self
+    }
+
+    /// Server name to verify the certificate against
+    pub fn server_name<VALUE: Into<String>>(mut self, value: VALUE) -> Self {
+        self.server_name = Some(value.into());
+        self
+    }
+
+    /// Timeout of the TLS handshake
+    pub fn handshake_timeout(mut self, value: Duration) -> Self {
+        self.handshake_timeout = Some(value);
+        self
+    }
 
-/// Framed messages
-pub struct FramedMessages;
+    fn session_config(&self) -> TLSSessionConfig {
+        TLSSessionConfig {
+            suite: self.cipher_suites.clone(),
+            proto: Vec::<String>::new(),
+            mtu: None,
+            cafile: self.ca_file.clone(),
+            no_tickets: false,
+            auth_key: self.client_key.clone(),
+            auth_certs: self.client_certs.clone(),
+        }
+    }
+}
 
 /// State: `TLSDisconnected`` for the TLS drain
 #[derive(Debug)]
 pub struct TLSDisconnected {
     addr: SocketAddr,
+    delimiter: u8,
+    tls: TLSConfig,
+    timeouts: StreamTimeouts,
+    lazy: bool,
+    metrics: DrainMetrics,
 }
 
 /// State: `TLSConnected` for the TLS drain
+///
+/// With lazy connect, the stream is opened when the first message is sent,
+/// a stream broken by a failed write is opened again on the next one.
 #[derive(Debug)]
 pub struct TLSConnected {
-    stream: Arc<Mutex<TlsClient>>,
+    stream: Arc<Mutex<Option<TlsClient>>>,
     addr: SocketAddr,
+    delimiter: u8,
+    tls: TLSConfig,
+    timeouts: StreamTimeouts,
+    lazy: bool,
+    metrics: DrainMetrics,
 }
 
 /// TLS drain
@@ -43,108 +131,277 @@ impl<T, F> TLSDrain<T, TLSDisconnected, F>
     pub fn new(addr: SocketAddr, formatter: F) -> TLSDrain<T, TLSDisconnected, F> {
         TLSDrain::<T, TLSDisconnected, F> {
             formatter: formatter,
-            connection: TLSDisconnected { addr: addr },
+            connection: TLSDisconnected {
+                addr: addr,
+                delimiter: b'\n',
+                tls: TLSConfig::default(),
+                timeouts: StreamTimeouts::default(),
+                lazy: false,
+                metrics: DrainMetrics::default(),
+            },
             _message_type: PhantomData,
         }
     }
 
-    /// Connect TLS stream
-    pub fn connect(self) -> io::Result<TLSDrain<T, TLSConnected, F>> {
+    /// TLS session configuration [TLSConfig](struct.TLSConfig.html)
+    ///
+    /// Default: Mozilla root certificates, server name is the server IP address
+    pub fn tls_config(mut self, value: TLSConfig) -> Self {
+        self.connection.tls = value;
+        self
+    }
 
-        let session_config = TLSSessionConfig {
-            suite: Vec::<String>::new(),
-            proto: Vec::<String>::new(),
-            mtu: None,
-            cafile: Some(String::from("/syslog-ng/cacert.pem")),
-            no_tickets: false,
-            auth_key: None,
-            auth_certs: None
-        };
+    /// Timeout of establishing the TCP connection, see `TLSConfig` for the handshake timeout
+    ///
+    /// Default: `None`, OS default
+    pub fn connect_timeout(mut self, value: Duration) -> Self {
+        self.connection.timeouts.connect = Some(value);
+        self
+    }
 
-        let config = make_config(&session_config);
+    /// Timeout of writing a message
+    ///
+    /// Default: `None`, writes block until the server accepts the message
+    pub fn write_timeout(mut self, value: Duration) -> Self {
+        self.connection.timeouts.write = Some(value);
+        self
+    }
 
-        let stream = TcpStream::connect(self.connection.addr)?;
-        let stream = TlsClient::new(stream, "syslog-ng", config);
+    /// Defer connecting until the first message is sent, like `openlog`
+    /// without `LOG_NDELAY`
+    ///
+    /// `connect` then never fails, connection errors are returned by `log`.
+    ///
+    /// Default: false
+    pub fn lazy_connect(mut self, value: bool) -> Self {
+        self.connection.lazy = value;
+        self
+    }
+
+    /// Handler called when the drain connects or disconnects
+    pub fn on_state_change<H>(mut self, handler: H) -> Self
+        where H: Fn(ConnectionState, &str) + Send + Sync + 'static
+    {
+        self.connection.metrics.set_handler(Arc::new(handler) as Arc<StateChangeHandler>);
+        self
+    }
+
+    /// Connect TLS stream, completing the handshake
+    pub fn connect(self) -> SyslogResult<TLSDrain<T, TLSConnected, F>> {
+        let stream = if self.connection.lazy {
+            None
+        } else {
+            Some(open_tls_stream(self.connection.addr,
+                                 &self.connection.tls,
+                                 &self.connection.timeouts,
+                                 &self.connection.metrics)?)
+        };
 
         Ok(TLSDrain::<T, TLSConnected, F> {
                formatter: self.formatter,
                connection: TLSConnected {
                    stream: Arc::new(Mutex::new(stream)),
                    addr: self.connection.addr,
+                   delimiter: self.connection.delimiter,
+                   tls: self.connection.tls,
+                   timeouts: self.connection.timeouts,
+                   lazy: self.connection.lazy,
+                   metrics: self.connection.metrics,
                },
                _message_type: PhantomData,
            })
     }
 }
 
+impl<F> TLSDrain<DelimitedMessages, TLSDisconnected, F>
+    where F: StreamFormat
+{
+    /// Message delimiter
+    ///
+    /// Default: LF
+    pub fn delimiter(mut self, value: u8) -> Self {
+        self.connection.delimiter = value;
+        self
+    }
+}
+
+fn open_tls_stream(addr: SocketAddr,
+                   tls: &TLSConfig,
+                   timeouts: &StreamTimeouts,
+                   metrics: &DrainMetrics)
+                   -> SyslogResult<TlsClient> {
+    let stream = connect_tls_stream(addr, tls, timeouts);
+    let stream = metrics.record_connection(stream, &addr)?;
+    metrics.set_state(ConnectionState::Connected, &addr);
+    Ok(stream)
+}
+
+fn connect_tls_stream(addr: SocketAddr,
+                      tls: &TLSConfig,
+                      timeouts: &StreamTimeouts)
+                      -> SyslogResult<TlsClient> {
+    let config = make_config(&tls.session_config()).map_err(|message| {
+                                                                SyslogError::Tls {
+                                                                    destination: addr.to_string(),
+                                                                    message: message,
+                                                                }
+                                                            })?;
+
+    let server_name = match tls.server_name {
+        Some(ref server_name) => server_name.clone(),
+        None => addr.ip().to_string(),
+    };
+
+    let stream = timeouts.open(addr)
+        .map_err(|err| SyslogError::connect(Transport::TLS, addr, err))?;
+    let mut client = TlsClient::new(stream, &server_name, config);
+    client.handshake(tls.handshake_timeout)
+        .map_err(|err| SyslogError::connect(Transport::TLS, addr, err))?;
+    Ok(client)
+}
+
 impl<T, F> TLSDrain<T, TLSConnected, F>
     where F: StreamFormat
 {
+    /// Delivery metrics snapshot
+    pub fn stats(&self) -> DrainStats {
+        self.connection.metrics.snapshot()
+    }
+
     /// Disconnect TLS stream, completing all operations
-    pub fn disconnect(self) -> io::Result<TLSDrain<T, TLSDisconnected, F>> {
+    pub fn disconnect(self) -> SyslogResult<TLSDrain<T, TLSDisconnected, F>> {
         //TODO: Fix
         // self.connection
         //     .stream
         //     .lock()
-        //     .map_err(|_| io::Error::new(io::ErrorKind::Other, "Couldn't acquire lock"))
-        //     .and_then(|mut s| s.shutdown())?;
+        //     .map_err(|_| SyslogError::poisoned(Transport::TLS, self.connection.addr))?
+        //     .shutdown()?;
+        let addr = self.connection.addr;
+        self.connection.metrics.set_state(ConnectionState::Disconnected, &addr);
         Ok(TLSDrain::<T, TLSDisconnected, F> {
                formatter: self.formatter,
-               connection: TLSDisconnected { addr: self.connection.addr },
+               connection: TLSDisconnected {
+                   addr: self.connection.addr,
+                   delimiter: self.connection.delimiter,
+                   tls: self.connection.tls,
+                   timeouts: self.connection.timeouts,
+                   lazy: self.connection.lazy,
+                   metrics: self.connection.metrics,
+               },
                _message_type: PhantomData,
            })
     }
 }
 
-// RFC3164 messages over TLS don't require framed headers
-impl<F> Drain for TLSDrain<DelimitedMessages, TLSConnected, F>
+impl<T, F> TLSDrain<T, TLSConnected, F>
     where F: StreamFormat
 {
-    type Error = io::Error;
-
-    #[allow(dead_code)]
-    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> io::Result<()> {
-
-        // Should be thread safe - redo the buffering
+    fn format_message(&self,
+                      info: &Record,
+                      logger_values: &OwnedKeyValueList)
+                      -> SyslogResult<Vec<u8>> {
         let mut buf = Vec::<u8>::with_capacity(4096);
+        let result = self.formatter
+            .format(&mut buf, info, logger_values)
+            .map_err(SyslogError::FormatFailed);
+        self.connection.metrics.record(result)?;
+        Ok(buf)
+    }
 
-        self.formatter.format(&mut buf, info, logger_values)?;
-        self.connection
+    fn reconnect_stream(&self) -> SyslogResult<()> {
+        let addr = self.connection.addr;
+        let stream = open_tls_stream(addr,
+                                     &self.connection.tls,
+                                     &self.connection.timeouts,
+                                     &self.connection.metrics)?;
+        let mut guard = self.connection
             .stream
             .lock()
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Couldn't acquire lock"))
-            .and_then(|mut s| s.write(buf.as_slice()))?;
+            .map_err(|_| SyslogError::poisoned(Transport::TLS, addr))?;
+        *guard = Some(stream);
+        self.connection.metrics.reconnected(&addr);
+        Ok(())
+    }
 
+    // Lazily connected stream is opened on the first write, broken stream
+    // is dropped when writing fails and opened again on the next write
+    fn write_message(&self, framing: Framing, message: &[u8]) -> SyslogResult<()> {
+        let addr = self.connection.addr;
+        let frame = framing.frame(message);
+        let mut guard = self.connection
+            .stream
+            .lock()
+            .map_err(|_| SyslogError::poisoned(Transport::TLS, addr))?;
+        let metrics = &self.connection.metrics;
+        let mut stream = match guard.take() {
+            Some(stream) => stream,
+            None => {
+                open_tls_stream(addr, &self.connection.tls, &self.connection.timeouts, metrics)?
+            }
+        };
+        let result = stream.write_all(&frame);
+        if result.is_ok() {
+            *guard = Some(stream);
+        }
+        let result = result.map_err(|err| SyslogError::send(Transport::TLS, addr, err));
+        metrics.record_connection(result, &addr)?;
+        metrics.sent(frame.len(), &addr);
         Ok(())
     }
 }
 
+// RFC3164 messages over TLS don't require framed headers
+impl<F> SyslogDrain for TLSDrain<DelimitedMessages, TLSConnected, F>
+    where F: StreamFormat
+{
+    fn format(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        self.format_message(info, logger_values)
+    }
+
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        self.write_message(Framing::NonTransparent(self.connection.delimiter), message)
+    }
+
+    fn reconnect(&self) -> SyslogResult<()> {
+        self.reconnect_stream()
+    }
+
+    fn stats(&self) -> Option<DrainStats> {
+        Some(self.connection.metrics.snapshot())
+    }
+}
+
 // RFC5424 messages require framed delimition, first we need to send
 // the length of the message in octets
-impl<F> Drain for TLSDrain<FramedMessages, TLSConnected, F>
+impl<F> SyslogDrain for TLSDrain<FramedMessages, TLSConnected, F>
     where F: StreamFormat
 {
-    type Error = io::Error;
+    fn format(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        self.format_message(info, logger_values)
+    }
 
-    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> io::Result<()> {
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        self.write_message(Framing::Octet, message)
+    }
 
-        // Should be thread safe - redo the buffering
-        let mut buf = Cursor::new(Vec::<u8>::with_capacity(10));
+    fn reconnect(&self) -> SyslogResult<()> {
+        self.reconnect_stream()
+    }
 
-        self.formatter.format(&mut buf, info, logger_values)?;
-        let length = buf.position();
+    fn stats(&self) -> Option<DrainStats> {
+        Some(self.connection.metrics.snapshot())
+    }
+}
 
-        self.connection
-            .stream
-            .lock()
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Couldn't acquire lock"))
-            .and_then(|mut s| {
-                          // Space spearated frame length
-                          s.write_fmt(format_args!("{} ", length))?;
-                          s.write(buf.into_inner().as_slice())
-                      })?;
+impl<T, F> Drain for TLSDrain<T, TLSConnected, F>
+    where F: StreamFormat,
+          TLSDrain<T, TLSConnected, F>: SyslogDrain
+{
+    type Error = SyslogError;
 
-        Ok(())
+    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<()> {
+        let buf = self.format(info, logger_values)?;
+        self.send(buf.as_slice())
     }
 }
 
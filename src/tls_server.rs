@@ -0,0 +1,65 @@
+use std::fmt;
+use std::io;
+use std::net::TcpStream;
+use std::sync::Arc;
+
+use rustls;
+use rustls::Session;
+use tls_client::{load_certs, load_private_key};
+
+/// Server side of a TLS connection: the accepted TCP connection
+/// and the TLS-level session, decrypted data is read from it
+pub struct TlsServer {
+    socket: TcpStream,
+    tls_session: rustls::ServerSession,
+}
+
+impl fmt::Debug for TlsServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TlsServer {{ socket: {:?} }}", self.socket)
+    }
+}
+
+/// Reads complete the handshake first, and return 0 once the client closes the connection
+impl io::Read for TlsServer {
+    fn read(&mut self, bytes: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.tls_session.read(bytes)?;
+            if read > 0 {
+                return Ok(read);
+            }
+
+            self.write_tls()?;
+            if self.tls_session.read_tls(&mut self.socket)? == 0 {
+                return Ok(0);
+            }
+            self.tls_session
+                .process_new_packets()
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err)))?;
+            self.write_tls()?;
+        }
+    }
+}
+
+impl TlsServer {
+    pub fn new(sock: TcpStream, cfg: &Arc<rustls::ServerConfig>) -> TlsServer {
+        TlsServer {
+            socket: sock,
+            tls_session: rustls::ServerSession::new(cfg),
+        }
+    }
+
+    fn write_tls(&mut self) -> io::Result<()> {
+        while self.tls_session.wants_write() {
+            self.tls_session.write_tls(&mut self.socket)?;
+        }
+        Ok(())
+    }
+}
+
+/// Build a `ServerConfig` with the certificate chain and the private key from PEM files
+pub fn make_server_config(certs: &str, key: &str) -> Result<Arc<rustls::ServerConfig>, String> {
+    let mut config = rustls::ServerConfig::new();
+    config.set_single_cert(load_certs(certs)?, load_private_key(key)?);
+    Ok(Arc::new(config))
+}
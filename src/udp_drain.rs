@@ -1,12 +1,22 @@
+use drain::SyslogDrain;
+use error::{SyslogError, SyslogResult, Transport};
+use libc;
+use metrics::{ConnectionState, DrainMetrics, DrainStats, StateChangeHandler};
 use slog::{Drain, OwnedKeyValueList, Record};
 use slog_stream::Format as StreamFormat;
-use std::io;
 use std::net::{UdpSocket, SocketAddr};
+use std::sync::Arc;
+
+/// Largest UDP payload over IPv4
+pub const UDP_MAX_MESSAGE_SIZE: usize = 65507;
 
 /// State: `UDPDisconnected` for the UDP drain
 #[derive(Debug)]
 pub struct UDPDisconnected {
     addr: SocketAddr,
+    max_message_size: usize,
+    truncate: bool,
+    metrics: DrainMetrics,
 }
 
 /// State: `UDPConnected` for the UDP drain
@@ -14,6 +24,9 @@ pub struct UDPDisconnected {
 pub struct UDPConnected {
     socket: UdpSocket,
     addr: SocketAddr,
+    max_message_size: usize,
+    truncate: bool,
+    metrics: DrainMetrics,
 }
 
 
@@ -33,18 +46,56 @@ impl<F> UDPDrain<UDPDisconnected, F>
     pub fn new(addr: SocketAddr, formatter: F) -> UDPDrain<UDPDisconnected, F> {
         UDPDrain::<UDPDisconnected, F> {
             formatter: formatter,
-            connection: UDPDisconnected { addr: addr },
+            connection: UDPDisconnected {
+                addr: addr,
+                max_message_size: UDP_MAX_MESSAGE_SIZE,
+                truncate: true,
+                metrics: DrainMetrics::default(),
+            },
         }
     }
 
+    /// Messages longer than this are truncated, or rejected unless `truncate`
+    ///
+    /// Default: 65507 bytes, the largest UDP payload over IPv4
+    pub fn max_message_size(mut self, value: usize) -> Self {
+        self.connection.max_message_size = value;
+        self
+    }
+
+    /// Truncate messages longer than `max_message_size`, instead of failing
+    /// with `SyslogError::MessageTooLarge`
+    ///
+    /// Default: `true`
+    pub fn truncate(mut self, value: bool) -> Self {
+        self.connection.truncate = value;
+        self
+    }
+
+    /// Handler called when the drain connects or disconnects
+    pub fn on_state_change<H>(mut self, handler: H) -> Self
+        where H: Fn(ConnectionState, &str) + Send + Sync + 'static
+    {
+        self.connection.metrics.set_handler(Arc::new(handler) as Arc<StateChangeHandler>);
+        self
+    }
+
     /// Connect UDP socket
-    pub fn connect(self) -> io::Result<UDPDrain<UDPConnected, F>> {
-        let socket = UdpSocket::bind("0.0.0.0:0")?;
+    pub fn connect(self) -> SyslogResult<UDPDrain<UDPConnected, F>> {
+        let addr = self.connection.addr;
+        let metrics = self.connection.metrics;
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .map_err(|err| SyslogError::connect(Transport::UDP, addr, err));
+        let socket = metrics.record_connection(socket, &addr)?;
+        metrics.set_state(ConnectionState::Connected, &addr);
         Ok(UDPDrain::<UDPConnected, F> {
                formatter: self.formatter,
                connection: UDPConnected {
                    socket: socket,
-                   addr: self.connection.addr,
+                   addr: addr,
+                   max_message_size: self.connection.max_message_size,
+                   truncate: self.connection.truncate,
+                   metrics: metrics,
                },
            })
     }
@@ -53,30 +104,79 @@ impl<F> UDPDrain<UDPDisconnected, F>
 impl<F> UDPDrain<UDPConnected, F>
     where F: StreamFormat
 {
+    /// Delivery metrics snapshot
+    pub fn stats(&self) -> DrainStats {
+        self.connection.metrics.snapshot()
+    }
+
     /// Disconnect UDP socket, completing all operations
-    pub fn disconnect(self) -> io::Result<UDPDrain<UDPDisconnected, F>> {
+    pub fn disconnect(self) -> SyslogResult<UDPDrain<UDPDisconnected, F>> {
+        let addr = self.connection.addr;
+        self.connection.metrics.set_state(ConnectionState::Disconnected, &addr);
         Ok(UDPDrain::<UDPDisconnected, F> {
                formatter: self.formatter,
-               connection: UDPDisconnected { addr: self.connection.addr },
+               connection: UDPDisconnected {
+                   addr: addr,
+                   max_message_size: self.connection.max_message_size,
+                   truncate: self.connection.truncate,
+                   metrics: self.connection.metrics,
+               },
            })
     }
 }
 
-impl<F> Drain for UDPDrain<UDPConnected, F>
+impl<F> SyslogDrain for UDPDrain<UDPConnected, F>
     where F: StreamFormat
 {
-    type Error = io::Error;
-
-    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> io::Result<()> {
-
-        // Should be thread safe - redo the buffering
+    fn format(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
         let mut buf = Vec::<u8>::with_capacity(4096);
+        let result = self.formatter
+            .format(&mut buf, info, logger_values)
+            .map_err(SyslogError::FormatFailed);
+        self.connection.metrics.record(result)?;
+        Ok(buf)
+    }
 
-        self.formatter.format(&mut buf, info, logger_values)?;
-        self.connection
+    // Oversized messages are truncated, as allowed by RFC5426, or rejected
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        let addr = self.connection.addr;
+        let metrics = &self.connection.metrics;
+        let limit = self.connection.max_message_size;
+        let message = if message.len() > limit {
+            if !self.connection.truncate {
+                let err = SyslogError::too_large(Transport::UDP, addr, message.len(), Some(limit));
+                return metrics.record(Err(err));
+            }
+            metrics.truncated();
+            &message[..limit]
+        } else {
+            message
+        };
+        let result = self.connection
             .socket
-            .send_to(buf.as_slice(), &self.connection.addr)?;
-
+            .send_to(message, &addr)
+            .map_err(|err| if err.raw_os_error() == Some(libc::EMSGSIZE) {
+                         SyslogError::too_large(Transport::UDP, addr, message.len(), None)
+                     } else {
+                         SyslogError::send(Transport::UDP, addr, err)
+                     });
+        let sent = metrics.record_connection(result, &addr)?;
+        metrics.sent(sent, &addr);
         Ok(())
     }
+
+    fn stats(&self) -> Option<DrainStats> {
+        Some(self.connection.metrics.snapshot())
+    }
+}
+
+impl<F> Drain for UDPDrain<UDPConnected, F>
+    where F: StreamFormat
+{
+    type Error = SyslogError;
+
+    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<()> {
+        let buf = self.format(info, logger_values)?;
+        self.send(buf.as_slice())
+    }
 }
@@ -1,24 +1,192 @@
+use drain::SyslogDrain;
+use error::{SyslogError, SyslogResult, Transport};
+use libc;
+use metrics::{ConnectionState, DrainMetrics, DrainStats, StateChangeHandler};
+use posix::locate_default_uds_socket;
 use slog::{Drain, OwnedKeyValueList, Record};
 use slog_stream::Format as StreamFormat;
 use std::io;
+use std::io::Write;
+use std::mem;
 use std::net::Shutdown;
-use std::os::unix::net::UnixDatagram;
-use std::path::PathBuf;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::net::{UnixDatagram, UnixStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+/// Unix domain socket type
+///
+/// Most of the syslog daemons listen on a datagram socket, but some
+/// (`/dev/log` on some systems, syslog-ng `unix-stream()` sources) use stream sockets.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum UDSSocketType {
+    /// Default: try datagram socket, fall back to stream socket,
+    /// if the daemon socket turns out to be a stream one
+    Auto,
+    /// `SOCK_DGRAM` socket
+    Datagram,
+    /// `SOCK_STREAM` socket, messages are delimited by LF
+    Stream,
+}
+
+impl Default for UDSSocketType {
+    fn default() -> UDSSocketType {
+        UDSSocketType::Auto
+    }
+}
+
+impl FromStr for UDSSocketType {
+    type Err = SyslogError;
+    fn from_str(s: &str) -> Result<UDSSocketType, SyslogError> {
+        let result = match &s.to_lowercase()[..] {
+            "auto" => UDSSocketType::Auto,
+            "datagram" | "dgram" => UDSSocketType::Datagram,
+            "stream" => UDSSocketType::Stream,
+            _ => return Err(SyslogError::ConfigInvalid(format!("Unknown socket type: {}", s))),
+        };
+        Ok(result)
+    }
+}
+
+/// Handling of failed sends for the UDS drain
+///
+/// Syslog daemon restart makes sends fail with `ECONNREFUSED` or `ENOENT`
+/// for a short while, full receive buffer fails them with `EAGAIN`.
+/// Such failures are retried with short sleeps, reconnecting the socket.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UDSRetryConfig {
+    /// Number of retries before the message is dropped
+    ///
+    /// Default: 3
+    pub retries: usize,
+    /// Sleep between retries
+    ///
+    /// Default: 10 milliseconds
+    pub retry_delay: Duration,
+    /// Use non-blocking socket, full receive buffer fails the send
+    /// instead of blocking the logging thread
+    ///
+    /// Default: `false`
+    pub nonblocking: bool,
+    /// Look for the default syslog socket again, when the socket path disappears
+    ///
+    /// Default: `false`
+    pub relocate: bool,
+    /// Report messages dropped after exhausting retries as errors, other failures
+    /// are always returned
+    ///
+    /// Default: `true`, set to `false` to only count them
+    pub fail_on_drop: bool,
+}
+
+impl Default for UDSRetryConfig {
+    fn default() -> UDSRetryConfig {
+        UDSRetryConfig {
+            retries: 3,
+            retry_delay: Duration::from_millis(10),
+            nonblocking: false,
+            relocate: false,
+            fail_on_drop: true,
+        }
+    }
+}
+
+impl UDSRetryConfig {
+    /// Number of retries before the message is dropped
+    pub fn retries(mut self, value: usize) -> Self {
+        self.retries = value;
+        self
+    }
+
+    /// Sleep between retries
+    pub fn retry_delay(mut self, value: Duration) -> Self {
+        self.retry_delay = value;
+        self
+    }
+
+    /// Use non-blocking socket
+    pub fn nonblocking(mut self, value: bool) -> Self {
+        self.nonblocking = value;
+        self
+    }
+
+    /// Look for the default syslog socket again, when the socket path disappears
+    pub fn relocate(mut self, value: bool) -> Self {
+        self.relocate = value;
+        self
+    }
+
+    /// Report dropped messages as errors
+    pub fn fail_on_drop(mut self, value: bool) -> Self {
+        self.fail_on_drop = value;
+        self
+    }
+}
 
 /// State: `UDSDisconnected`
 #[derive(Default, Debug)]
 pub struct UDSDisconnected {
     path_to_socket: PathBuf,
+    socket_type: UDSSocketType,
+    retry: UDSRetryConfig,
+    metrics: DrainMetrics,
+}
+
+#[derive(Debug)]
+enum UDSSocket {
+    Datagram(UnixDatagram),
+    Stream(UnixStream),
+}
+
+impl UDSSocket {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match *self {
+            UDSSocket::Datagram(ref s) => s.set_nonblocking(nonblocking),
+            UDSSocket::Stream(ref s) => s.set_nonblocking(nonblocking),
+        }
+    }
+
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        match *self {
+            UDSSocket::Datagram(ref s) => {
+                s.send(message)?;
+            }
+            UDSSocket::Stream(ref mut s) => {
+                // Stream sockets are LF delimited, replace datagram EOM
+                let mut end = message.len();
+                while end > 0 && (message[end - 1] == b'\0' || message[end - 1] == b'\n') {
+                    end -= 1;
+                }
+                s.write_all(&message[..end])?;
+                s.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct UDSSocketState {
+    socket: UDSSocket,
+    path_to_socket: PathBuf,
 }
 
 /// State: `UDSConnected` for the UDS drain
 #[derive(Debug)]
 pub struct UDSConnected {
-    socket: UnixDatagram,
-    path_to_socket: PathBuf,
+    state: Mutex<UDSSocketState>,
+    socket_type: UDSSocketType,
+    retry: UDSRetryConfig,
+    metrics: DrainMetrics,
 }
 
 /// Unix domain socket drain
+///
+/// Socket paths starting with `@` are Linux abstract namespace addresses.
 #[derive(Debug)]
 pub struct UDSDrain<C, F>
     where F: StreamFormat
@@ -27,6 +195,103 @@ pub struct UDSDrain<C, F>
     connection: C,
 }
 
+// Connect unix socket of a given type, supporting abstract namespace addresses
+fn connect_unix_socket(path: &Path, socket_type: libc::c_int) -> io::Result<RawFd> {
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    let bytes = path.as_os_str().as_bytes();
+    let (name, offset) = match bytes.first() {
+        Some(&b'@') => (&bytes[1..], 1),
+        _ => (bytes, 0),
+    };
+
+    if offset == 1 && !cfg!(target_os = "linux") {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "Abstract namespace sockets are supported only on Linux"));
+    }
+
+    // Leave space for terminating NUL of the filesystem path
+    if name.len() + offset >= addr.sun_path.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Socket path is too long"));
+    }
+
+    for (i, byte) in name.iter().enumerate() {
+        addr.sun_path[i + offset] = *byte as libc::c_char;
+    }
+
+    let path_offset = &addr.sun_path as *const _ as usize - &addr as *const _ as usize;
+    // Abstract names are not NUL terminated
+    let len = path_offset + offset + name.len() + (1 - offset);
+
+    unsafe {
+        let fd = libc::socket(libc::AF_UNIX, socket_type, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let result = libc::connect(fd,
+                                   &addr as *const _ as *const libc::sockaddr,
+                                   len as libc::socklen_t);
+        if result < 0 {
+            let err = io::Error::last_os_error();
+            libc::close(fd);
+            return Err(err);
+        }
+        Ok(fd)
+    }
+}
+
+// Open socket of the requested type, detecting the type for `Auto`
+fn open_socket(path: &Path, socket_type: UDSSocketType) -> io::Result<(UDSSocket, UDSSocketType)> {
+    match socket_type {
+        UDSSocketType::Datagram => {
+            let fd = connect_unix_socket(path, libc::SOCK_DGRAM)?;
+            Ok((UDSSocket::Datagram(unsafe { UnixDatagram::from_raw_fd(fd) }),
+                UDSSocketType::Datagram))
+        }
+        UDSSocketType::Stream => {
+            let fd = connect_unix_socket(path, libc::SOCK_STREAM)?;
+            Ok((UDSSocket::Stream(unsafe { UnixStream::from_raw_fd(fd) }), UDSSocketType::Stream))
+        }
+        UDSSocketType::Auto => {
+            match open_socket(path, UDSSocketType::Datagram) {
+                Err(ref err) if err.raw_os_error() == Some(libc::EPROTOTYPE) => {
+                    open_socket(path, UDSSocketType::Stream)
+                }
+                result => result,
+            }
+        }
+    }
+}
+
+// Open socket, applying retry config socket options
+fn open_configured_socket(path: &Path,
+                          socket_type: UDSSocketType,
+                          retry: &UDSRetryConfig)
+                          -> io::Result<(UDSSocket, UDSSocketType)> {
+    let (socket, socket_type) = open_socket(path, socket_type)?;
+    socket.set_nonblocking(retry.nonblocking)?;
+    Ok((socket, socket_type))
+}
+
+// Failures caused by daemon restart or a full receive buffer
+fn is_transient(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::ConnectionRefused |
+        io::ErrorKind::ConnectionReset |
+        io::ErrorKind::NotConnected |
+        io::ErrorKind::BrokenPipe |
+        io::ErrorKind::NotFound |
+        io::ErrorKind::WouldBlock => true,
+        _ => err.raw_os_error() == Some(libc::ENOBUFS),
+    }
+}
+
+// Full receive buffer doesn't require reconnect
+fn is_buffer_full(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::WouldBlock || err.raw_os_error() == Some(libc::ENOBUFS)
+}
+
 impl<F> UDSDrain<UDSDisconnected, F>
     where F: StreamFormat
 {
@@ -34,18 +299,59 @@ impl<F> UDSDrain<UDSDisconnected, F>
     pub fn new(path_to_socket: PathBuf, formatter: F) -> UDSDrain<UDSDisconnected, F> {
         UDSDrain::<UDSDisconnected, F> {
             formatter: formatter,
-            connection: UDSDisconnected { path_to_socket: path_to_socket },
+            connection: UDSDisconnected {
+                path_to_socket: path_to_socket,
+                socket_type: UDSSocketType::default(),
+                retry: UDSRetryConfig::default(),
+                metrics: DrainMetrics::default(),
+            },
         }
     }
 
+    /// Handling of failed sends [UDSRetryConfig](struct.UDSRetryConfig.html)
+    pub fn retry(mut self, retry: UDSRetryConfig) -> Self {
+        self.connection.retry = retry;
+        self
+    }
+
+    /// Socket type [UDSSocketType](enum.UDSSocketType.html)
+    ///
+    /// Default: `Auto`
+    pub fn socket_type(mut self, socket_type: UDSSocketType) -> Self {
+        self.connection.socket_type = socket_type;
+        self
+    }
+
+    /// Handler called when the drain connects or disconnects
+    pub fn on_state_change<H>(mut self, handler: H) -> Self
+        where H: Fn(ConnectionState, &str) + Send + Sync + 'static
+    {
+        self.connection.metrics.set_handler(Arc::new(handler) as Arc<StateChangeHandler>);
+        self
+    }
+
     /// Connect UDS socket
-    pub fn connect(self) -> io::Result<UDSDrain<UDSConnected, F>> {
-        let socket = UnixDatagram::unbound()?;
+    pub fn connect(self) -> SyslogResult<UDSDrain<UDSConnected, F>> {
+        let (socket, socket_type) = {
+            let path = self.connection.path_to_socket.display();
+            let opened = open_configured_socket(&self.connection.path_to_socket,
+                                                self.connection.socket_type,
+                                                &self.connection.retry)
+                .map_err(|err| SyslogError::connect(Transport::UDS, &path, err));
+            let opened = self.connection.metrics.record_connection(opened, &path)?;
+            self.connection.metrics.set_state(ConnectionState::Connected, &path);
+            opened
+        };
         Ok(UDSDrain::<UDSConnected, F> {
                formatter: self.formatter,
                connection: UDSConnected {
-                   socket: socket,
-                   path_to_socket: self.connection.path_to_socket,
+                   state: Mutex::new(UDSSocketState {
+                                         socket: socket,
+                                         path_to_socket: self.connection.path_to_socket,
+                                     }),
+                   socket_type: socket_type,
+                   retry: self.connection.retry,
+                   metrics: self.connection.metrics,
                },
            })
     }
@@ -54,33 +360,170 @@ impl<F> UDSDrain<UDSDisconnected, F>
 impl<F> UDSDrain<UDSConnected, F>
     where F: StreamFormat
 {
+    /// Actual socket type, either `Datagram` or `Stream`
+    pub fn connected_socket_type(&self) -> UDSSocketType {
+        self.connection.socket_type
+    }
+
+    /// Number of messages dropped after exhausting retries
+    pub fn dropped(&self) -> usize {
+        self.connection.metrics.snapshot().dropped
+    }
+
+    /// Delivery metrics snapshot
+    pub fn stats(&self) -> DrainStats {
+        self.connection.metrics.snapshot()
+    }
+
     /// Disconnect UDS socket, completing all operations
-    pub fn disconnect(self) -> io::Result<UDSDrain<UDSDisconnected, F>> {
+    pub fn disconnect(self) -> SyslogResult<UDSDrain<UDSDisconnected, F>> {
+        let state = self.connection
+            .state
+            .into_inner()
+            .map_err(|err| {
+                         SyslogError::poisoned(Transport::UDS,
+                                               err.into_inner().path_to_socket.display())
+                     })?;
+        let shutdown = match state.socket {
+            UDSSocket::Datagram(ref s) => s.shutdown(Shutdown::Both),
+            UDSSocket::Stream(ref s) => s.shutdown(Shutdown::Both),
+        };
+        shutdown.map_err(|err| {
+                              SyslogError::send(Transport::UDS, state.path_to_socket.display(), err)
+                          })?;
         self.connection
-            .socket
-            .shutdown(Shutdown::Both)?;
+            .metrics
+            .set_state(ConnectionState::Disconnected, &state.path_to_socket.display());
         Ok(UDSDrain::<UDSDisconnected, F> {
                formatter: self.formatter,
-               connection: UDSDisconnected { path_to_socket: self.connection.path_to_socket },
+               connection: UDSDisconnected {
+                   path_to_socket: state.path_to_socket,
+                   socket_type: self.connection.socket_type,
+                   retry: self.connection.retry,
+                   metrics: self.connection.metrics,
+               },
            })
     }
+
+    fn lock_state(&self) -> SyslogResult<MutexGuard<UDSSocketState>> {
+        self.connection
+            .state
+            .lock()
+            .map_err(|err| {
+                         SyslogError::poisoned(Transport::UDS,
+                                               err.into_inner().path_to_socket.display())
+                     })
+    }
+
+    // Reopen socket, looking for the default one, if the path has disappeared
+    fn reopen(&self, state: &mut UDSSocketState, relocate: bool) -> SyslogResult<()> {
+        let opened = open_configured_socket(&state.path_to_socket,
+                                            self.connection.socket_type,
+                                            &self.connection.retry);
+        let (socket, _) = match opened {
+            Err(ref err) if relocate && err.kind() == io::ErrorKind::NotFound => {
+                let path = locate_default_uds_socket()?;
+                let opened = open_configured_socket(&path,
+                                                    self.connection.socket_type,
+                                                    &self.connection.retry)
+                    .map_err(|err| SyslogError::connect(Transport::UDS, path.display(), err))?;
+                state.path_to_socket = path;
+                opened
+            }
+            Err(err) => {
+                let path = state.path_to_socket.display();
+                return Err(SyslogError::connect(Transport::UDS, path, err));
+            }
+            Ok(opened) => opened,
+        };
+        state.socket = socket;
+        self.connection.metrics.reconnected(&state.path_to_socket.display());
+        Ok(())
+    }
 }
 
-impl<F> Drain for UDSDrain<UDSConnected, F>
+impl<F> SyslogDrain for UDSDrain<UDSConnected, F>
     where F: StreamFormat
 {
-    type Error = io::Error;
+    fn format(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        let mut buf = Vec::<u8>::with_capacity(4096);
+        let result = self.formatter
+            .format(&mut buf, info, logger_values)
+            .map_err(SyslogError::FormatFailed);
+        self.connection.metrics.record(result)?;
+        Ok(buf)
+    }
 
-    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> io::Result<()> {
+    // Transient failures are retried with short sleeps, reconnecting the socket,
+    // message is dropped when retries are exhausted, other failures are returned
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        let retry = &self.connection.retry;
+        let metrics = &self.connection.metrics;
+        let mut attempt = 0;
+        let mut reopen = false;
+        loop {
+            // The lock is released before sleeping, other threads may send meanwhile
+            {
+                let mut state = self.lock_state()?;
+                if reopen {
+                    // Daemon may be still restarting, the next attempt will tell
+                    let _ = self.reopen(&mut state, retry.relocate);
+                }
 
-        // Should be thread safe - redo the buffering
-        let mut buf = Vec::<u8>::with_capacity(4096);
+                let err = match state.socket.send(message) {
+                    Ok(()) => {
+                        metrics.sent(message.len(), &state.path_to_socket.display());
+                        return Ok(());
+                    }
+                    Err(err) => err,
+                };
 
-        self.formatter.format(&mut buf, info, logger_values)?;
-        self.connection
-            .socket
-            .send_to(buf.as_slice(), &self.connection.path_to_socket)?;
+                let transient = is_transient(&err);
+                if attempt >= retry.retries || !transient {
+                    let path = state.path_to_socket.display();
+                    let err = if err.raw_os_error() == Some(libc::EMSGSIZE) {
+                        SyslogError::too_large(Transport::UDS, path, message.len(), None)
+                    } else {
+                        SyslogError::send(Transport::UDS, path, err)
+                    };
+                    metrics.error(&err);
+                    if !transient {
+                        return Err(err);
+                    }
+                    metrics.dropped();
+                    return if retry.fail_on_drop { Err(err) } else { Ok(()) };
+                }
 
-        Ok(())
+                reopen = !is_buffer_full(&err);
+                if reopen {
+                    metrics.set_state(ConnectionState::Disconnected,
+                                      &state.path_to_socket.display());
+                }
+            }
+            attempt += 1;
+
+            thread::sleep(retry.retry_delay);
+        }
+    }
+
+    fn reconnect(&self) -> SyslogResult<()> {
+        let mut state = self.lock_state()?;
+        let result = self.reopen(&mut state, self.connection.retry.relocate);
+        self.connection.metrics.record_connection(result, &state.path_to_socket.display())
+    }
+
+    fn stats(&self) -> Option<DrainStats> {
+        Some(self.connection.metrics.snapshot())
+    }
+}
+
+impl<F> Drain for UDSDrain<UDSConnected, F>
+    where F: StreamFormat
+{
+    type Error = SyslogError;
+
+    fn log(&self, info: &Record, logger_values: &OwnedKeyValueList) -> SyslogResult<()> {
+        let buf = self.format(info, logger_values)?;
+        self.send(buf.as_slice())
     }
 }
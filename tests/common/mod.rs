@@ -4,11 +4,14 @@ pub mod syslog_ng;
 pub use self::syslog_ng::{fetch_syslog_messages, filter_syslog_messages, reset_syslog_ng};
 use slog::{Logger, Record, OwnedKeyValueList, Drain, DrainExt};
 use slog_stream::Format as StreamFormat;
+use slog_syslog_ng::{SyslogDrain, SyslogError, SyslogResult, Transport, get_pid};
 // use slog_syslog_ng::*;
 
-use std::{io, result};
+use std::{env, fs, io, result};
 use std::ops::DerefMut;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 
 pub type SharedIoVec = Arc<Mutex<Vec<u8>>>;
@@ -89,6 +92,17 @@ pub fn emit_test_message_to_buffer<F>(formatter: F) -> TestIoBuffer
     return buffer;
 }
 
+// Log records of the fixture through the formatter,
+// `emit` gets the root logger, returns formatted output
+pub fn emit_to_string<F, E>(formatter: F, emit: E) -> String
+    where F: StreamFormat + 'static,
+          E: FnOnce(&Logger)
+{
+    let buffer = TestIoBuffer::new(1024);
+    emit(&Logger::root(TestDrain::new(buffer.io(), formatter).fuse(), o!()));
+    buffer.as_string()
+}
+
 // Formater fixture
 #[macro_export]
 macro_rules! formatter(
@@ -101,11 +115,14 @@ macro_rules! formatter(
 #[macro_export]
 macro_rules! logger_emit(
     ($drain: ident, $format: ident, $dest: expr, $event: expr) => {{
+        logger_emit!($drain, $format, $dest, $event, |drain| drain)
+    }};
+    ($drain: ident, $format: ident, $dest: expr, $event: expr, $configure: expr) => {{
 
         let buffer = TestIoBuffer::new(1024);
         let introspection_drain = TestDrain::new(buffer.io(), formatter!($format));
 
-        let test_drain = $drain::new($dest.clone(), formatter!($format))
+        let test_drain = $configure($drain::new($dest.clone(), formatter!($format)))
             .connect().expect("couldn't connect to socket");
 
         let logger = Logger::root(duplicate(introspection_drain, test_drain).fuse(),
@@ -241,8 +258,60 @@ macro_rules! tls_framed_tests {
                     stringify!(TLSDrainFramed),
                     stringify!($format),
                     $addr);
-                logger_emit!(TLSDrainFramed, $format, dest, message);
+                let tls = TLSConfig::new()
+                    .ca_file("/syslog-ng/cacert.pem")
+                    .server_name("syslog-ng");
+                logger_emit!(TLSDrainFramed, $format, dest, message, |drain: TLSDrainFramed<_, _>| {
+                    drain.tls_config(tls)
+                });
                 verify_syslog_ng_message!(message);
             }
         )*)
 }
+
+// Test transport, recording sent messages, which can be switched
+// to fail, to simulate unreachable syslog server
+#[derive(Debug)]
+pub struct TestSyslogDrain {
+    sent: Arc<Mutex<Vec<Vec<u8>>>>,
+    failing: Arc<AtomicBool>,
+}
+
+impl TestSyslogDrain {
+    pub fn new() -> TestSyslogDrain {
+        TestSyslogDrain {
+            sent: Arc::new(Mutex::new(Vec::new())),
+            failing: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn sent(&self) -> Arc<Mutex<Vec<Vec<u8>>>> {
+        self.sent.clone()
+    }
+
+    pub fn failing(&self) -> Arc<AtomicBool> {
+        self.failing.clone()
+    }
+}
+
+impl SyslogDrain for TestSyslogDrain {
+    fn format(&self, record: &Record, _: &OwnedKeyValueList) -> SyslogResult<Vec<u8>> {
+        Ok(format!("{}", record.msg()).into_bytes())
+    }
+
+    fn send(&self, message: &[u8]) -> SyslogResult<()> {
+        if self.failing.load(Ordering::SeqCst) {
+            let err = io::Error::new(io::ErrorKind::ConnectionRefused, "test drain is down");
+            return Err(SyslogError::send(Transport::TCP, "test", err));
+        }
+        self.sent.lock().unwrap().push(message.to_vec());
+        Ok(())
+    }
+}
+
+// Unique empty scratch directory for a test
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("slog-syslog-ng-{}-{}", name, get_pid()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
@@ -0,0 +1,34 @@
+// Tests forking the process, a separate binary without the test harness:
+// the harness runs tests on multiple threads, a child forked from such
+// a process may inherit locks held by threads which don't exist in it
+#[macro_use]
+extern crate slog;
+extern crate slog_stream;
+extern crate slog_syslog_ng;
+extern crate serde_json;
+extern crate libc;
+
+#[macro_use]
+mod common;
+
+mod tests {
+
+    use common::{TestDrain, TestIoBuffer};
+    use slog::{DrainExt, Logger};
+    use slog_syslog_ng::*;
+
+    use std::os::unix::net::UnixDatagram;
+    use std::panic;
+    use std::time::Duration;
+
+    include!("tests/fork.rs");
+
+}
+
+fn main() {
+    for &(name, test) in tests::TESTS {
+        print!("test {} ... ", name);
+        test();
+        println!("ok");
+    }
+}
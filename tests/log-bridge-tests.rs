@@ -0,0 +1,22 @@
+// Tests of the `log` crate bridge, a separate crate as `log` macros
+// clash with the slog ones and the global logger can be set only once
+#[macro_use]
+extern crate log;
+extern crate slog;
+extern crate slog_stream;
+extern crate slog_syslog_ng;
+
+#[cfg(test)]
+mod tests {
+
+    use slog::{Drain, OwnedKeyValueList, Record};
+    use slog_stream::Format as StreamFormat;
+    use slog_syslog_ng::*;
+
+    use std::io;
+    use std::ops::DerefMut;
+    use std::sync::{Arc, Mutex};
+
+    include!("tests/log_bridge.rs");
+
+}
@@ -8,6 +8,7 @@ extern crate slog_syslog_ng;
 extern crate slog;
 extern crate slog_stream;
 extern crate serde_json;
+extern crate libc;
 
 #[macro_use]
 mod common;
@@ -18,15 +19,38 @@ mod tests {
 
     // use common::*;
 
-    use common::emit_test_message_to_buffer;
+    use common::{TestDrain, TestIoBuffer, TestSyslogDrain, emit_test_message_to_buffer,
+                 emit_to_string, scratch_dir};
+    use slog::{DrainExt, Logger};
     use slog_syslog_ng::*;
 
-    use std::net::{SocketAddr, IpAddr, Ipv4Addr};
+    use std::fs;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{SocketAddr, IpAddr, Ipv4Addr, TcpListener, UdpSocket};
+    use std::os::unix::net::{UnixDatagram, UnixListener};
     use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
 
     include!("tests/helpers.rs");
     include!("tests/config.rs");
     include!("tests/serializers.rs");
+    include!("tests/spool.rs");
+    include!("tests/failover.rs");
+    include!("tests/fallback.rs");
+    include!("tests/multi.rs");
+    include!("tests/libc_drain.rs");
+    include!("tests/uds_drain.rs");
+    include!("tests/tcp_drain.rs");
+    include!("tests/metrics.rs");
+    include!("tests/rate_limit.rs");
+    include!("tests/redaction.rs");
+    include!("tests/fields.rs");
+    include!("tests/structured_data.rs");
+    include!("tests/hostname.rs");
+    include!("tests/parser.rs");
+    include!("tests/relay.rs");
 
 
 }
@@ -35,6 +35,7 @@ fn builder_invariants() {
 fn uds_config_default() {
     let config = syslog().uds();
     assert!(config.connection_config.socket.is_none());
+    assert_eq!(config.connection_config.socket_type, UDSSocketType::Auto);
     assert!(!config.async);
     assert_eq!(config.timestamp, TimestampFormat::RFC3164);
     assert_eq!(config.timezone, TimestampTZ::Local);
@@ -50,10 +51,16 @@ fn uds_config_with_path() {
     assert_eq!(config.mode, FormatMode::RFC5424);
 }
 
+#[test]
+fn uds_config_with_socket_type() {
+    let config = syslog().uds().socket("@syslog").socket_type(UDSSocketType::Stream);
+    assert_eq!(config.connection_config.socket_type, UDSSocketType::Stream);
+}
+
 #[test]
 fn udp_config_default() {
     let config = syslog().udp();
-    assert_eq!(config.connection_config.server, None);
+    assert!(config.connection_config.servers.is_empty());
     assert!(!config.async);
     assert_eq!(config.timestamp, TimestampFormat::RFC3164);
     assert_eq!(config.timezone, TimestampTZ::Local);
@@ -64,10 +71,329 @@ fn udp_config_default() {
 #[test]
 fn tcp_config_default() {
     let config = syslog().tcp();
-    assert_eq!(config.connection_config.server, None);
+    assert!(config.connection_config.servers.is_empty());
     assert!(!config.async);
     assert_eq!(config.timestamp, TimestampFormat::RFC3164);
     assert_eq!(config.timezone, TimestampTZ::Local);
     assert_eq!(config.serialization, SerializationFormat::Native);
     assert_eq!(config.facility, Facility::LOG_USER);
 }
+
+#[test]
+fn tcp_config_failover_servers() {
+    let config = syslog()
+        .tcp()
+        .server("primary:601")
+        .failover_server("secondary:601")
+        .probe_interval(Duration::from_secs(5));
+    assert_eq!(config.connection_config.servers,
+               vec!["primary:601", "secondary:601"]);
+    assert_eq!(config.connection_config.probe_interval, Duration::from_secs(5));
+}
+
+#[test]
+fn tcp_config_framing_and_tls() {
+    let tls = TLSConfig::new().ca_file("/etc/ssl/ca.pem").server_name("logs.internal");
+    let config = syslog()
+        .tcp()
+        .framing(Framing::NonTransparent(b'\0'))
+        .tls(tls.clone())
+        .lazy_connect(true)
+        .connect_timeout(Duration::from_secs(3))
+        .servers(vec!["primary:6514", "secondary:6514"])
+        .write_timeout(Duration::from_secs(1));
+    assert_eq!(config.connection_config.framing, Some(Framing::NonTransparent(b'\0')));
+    assert_eq!(config.connection_config.tls, Some(tls));
+    assert!(config.connection_config.lazy_connect);
+    assert_eq!(config.connection_config.timeouts,
+               StreamTimeouts {
+                   connect: Some(Duration::from_secs(3)),
+                   write: Some(Duration::from_secs(1)),
+               });
+}
+
+#[test]
+fn framing_frames_messages() {
+    assert_eq!(Framing::for_mode(&FormatMode::RFC5424), Framing::Octet);
+    assert_eq!(Framing::for_mode(&FormatMode::RFC3164), Framing::NonTransparent(b'\n'));
+    assert_eq!(Framing::Octet.frame(b"<13>1 - msg"), b"11 <13>1 - msg".to_vec());
+    assert_eq!(Framing::NonTransparent(b'\n').frame(b"<13>msg\n\0"), b"<13>msg\n".to_vec());
+    assert_eq!(Framing::NonTransparent(b'\0').frame(b"<13>msg"), b"<13>msg\0".to_vec());
+    assert_eq!("octet-counting".parse::<Framing>().unwrap(), Framing::Octet);
+    assert_eq!("nul".parse::<Framing>().unwrap(), Framing::NonTransparent(b'\0'));
+    assert!("crlf".parse::<Framing>().is_err());
+}
+
+#[test]
+fn udp_config_servers() {
+    let config = syslog().udp().servers(vec!["primary:514", "secondary:514"]);
+    assert_eq!(config.connection_config.servers,
+               vec!["primary:514", "secondary:514"]);
+}
+
+#[test]
+fn tcp_connect_skips_unreachable_server() {
+    let dead = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let live = TcpListener::bind("127.0.0.1:0").unwrap();
+    let drain = syslog()
+        .tcp()
+        .servers(vec![dead, live.local_addr().unwrap()])
+        .connect_timeout(Duration::from_secs(1))
+        .connect()
+        .unwrap();
+
+    let logger = Logger::root(drain.fuse(), o!());
+    info!(logger, "sent to the live server");
+
+    let (stream, _) = live.accept().unwrap();
+    let line = BufReader::new(stream).lines().next().unwrap().unwrap();
+    assert!(line.contains("sent to the live server"));
+}
+
+#[test]
+fn udp_connect_skips_unresolved_server() {
+    let live = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let servers = vec!["unresolved.invalid:514".to_string(),
+                       live.local_addr().unwrap().to_string()];
+    let drain = syslog().udp().servers(servers).connect().unwrap();
+
+    let logger = Logger::root(drain.fuse(), o!());
+    info!(logger, "sent to the resolved server");
+
+    let mut buf = [0u8; 1024];
+    let len = live.recv(&mut buf).unwrap();
+    assert!(String::from_utf8_lossy(&buf[..len]).contains("sent to the resolved server"));
+}
+
+#[test]
+fn tcp_connect_fails_without_reachable_server() {
+    let dead = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let also_dead = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let result = syslog()
+        .tcp()
+        .servers(vec![dead, also_dead])
+        .connect_timeout(Duration::from_secs(1))
+        .connect();
+    match result {
+        Err(SyslogError::ConnectFailed { transport: Transport::TCP, .. }) => {}
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+}
+
+#[test]
+fn validate_rejects_invalid_hostname() {
+    // Reachable server, connect fails only because of the hostname
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let config = syslog().tcp().server(server.local_addr().unwrap()).hostname("my host");
+    match config.validate() {
+        Err(SyslogError::ConfigInvalid(message)) => assert!(message.contains("hostname")),
+        result => panic!("unexpected result {:?}", result),
+    }
+    match config.connect() {
+        Err(SyslogError::ConfigInvalid(message)) => assert!(message.contains("hostname")),
+        result => panic!("unexpected result {:?}", result.map(|_| ())),
+    }
+
+    let long = ::std::iter::repeat("a").take(HOSTNAME_MAX_LEN + 1).collect::<String>();
+    assert!(syslog().udp().hostname(long).validate().is_err());
+}
+
+#[test]
+fn validate_rejects_oversized_spool_segment() {
+    let spool = SpoolConfig::new("/tmp/spool").max_size(1024).segment_size(4096);
+    assert!(syslog().tcp().spool(spool).validate().is_err());
+}
+
+#[test]
+fn validate_warns_about_questionable_settings() {
+    let warnings = syslog()
+        .uds()
+        .hostname("localhost")
+        .timestamp(TimestampFormat::ISO8601)
+        .validate()
+        .unwrap();
+    assert!(warnings.contains(&ConfigWarning::HostnameIgnored));
+    assert!(warnings.contains(&ConfigWarning::NativeSerializationFallback));
+    assert!(warnings.contains(&ConfigWarning::IsoTimestampInRfc3164));
+
+    let warnings = syslog().mode(FormatMode::RFC5424).tcp().validate().unwrap();
+    assert!(warnings.contains(&ConfigWarning::LocalTimezoneInRfc5424));
+    assert!(!warnings.contains(&ConfigWarning::NativeSerializationFallback));
+
+    let warnings = syslog()
+        .mode(FormatMode::RFC5424)
+        .timezone(TimestampTZ::UTC)
+        .udp()
+        .hostname("localhost")
+        .validate()
+        .unwrap();
+    assert!(!warnings.contains(&ConfigWarning::LocalTimezoneInRfc5424));
+    assert!(!warnings.contains(&ConfigWarning::HostnameIgnored));
+}
+
+#[test]
+fn validate_app_name_limits() {
+    assert!(validate_app_name("my-app", &FormatMode::RFC3164).is_ok());
+    assert!(validate_app_name("my:app", &FormatMode::RFC3164).is_err());
+    assert!(validate_app_name("my:app", &FormatMode::RFC5424).is_ok());
+    assert!(validate_app_name("my app", &FormatMode::RFC5424).is_err());
+
+    let name = ::std::iter::repeat("a").take(40).collect::<String>();
+    assert!(validate_app_name(&name, &FormatMode::RFC3164).is_err());
+    assert!(validate_app_name(&name, &FormatMode::RFC5424).is_ok());
+}
+
+#[test]
+fn validate_app_name_and_proc_id_settings() {
+    assert!(validate_proc_id("worker-3", &FormatMode::RFC3164).is_ok());
+    assert!(validate_proc_id("worker]3", &FormatMode::RFC3164).is_err());
+    assert!(validate_proc_id("worker 3", &FormatMode::RFC5424).is_err());
+    assert!(validate_proc_id("", &FormatMode::RFC5424).is_err());
+
+    let name = ::std::iter::repeat("a").take(33).collect::<String>();
+    assert!(syslog().udp().mode(FormatMode::RFC3164).app_name(name.as_str()).validate().is_err());
+    assert!(syslog().udp().mode(FormatMode::RFC5424).app_name(name.as_str()).validate().is_ok());
+    assert!(syslog()
+                .udp()
+                .proc_id(ProcId::Custom("worker 3".to_string()))
+                .validate()
+                .is_err());
+
+    assert!(get_process_name_from(ProcessNameSource::Argv0).is_some());
+    assert!(get_process_name_from(ProcessNameSource::Executable).is_some());
+}
+
+#[test]
+fn config_enums_from_str() {
+    assert_eq!("rfc5424".parse::<FormatMode>().unwrap(), FormatMode::RFC5424);
+    assert_eq!("BSD".parse::<FormatMode>().unwrap(), FormatMode::RFC3164);
+    assert_eq!("iso8601".parse::<TimestampFormat>().unwrap(),
+               TimestampFormat::ISO8601);
+    assert_eq!("utc".parse::<TimestampTZ>().unwrap(), TimestampTZ::UTC);
+    assert_eq!("cee".parse::<SerializationFormat>().unwrap(),
+               SerializationFormat::CEE);
+    assert!("rfc9999".parse::<FormatMode>().is_err());
+}
+
+#[test]
+fn settings_from_url() {
+    let settings = "tcp://logs.internal,backup.internal:1514?mode=rfc5424&facility=local3&tz=utc"
+        .parse::<SyslogSettings>()
+        .unwrap();
+    assert_eq!(settings.transport, Some(Transport::TCP));
+    assert_eq!(settings.servers,
+               vec!["logs.internal".to_string(), "backup.internal:1514".to_string()]);
+    assert_eq!(settings.mode, FormatMode::RFC5424);
+    assert_eq!(settings.facility, Facility::LOG_LOCAL3);
+    assert_eq!(settings.timezone, TimestampTZ::UTC);
+
+    match settings.into_config().unwrap() {
+        AnySyslogConfig::TCP(config) => {
+            assert_eq!(config.connection_config.servers,
+                       vec!["logs.internal:514".to_string(), "backup.internal:1514".to_string()]);
+            assert_eq!(config.mode, FormatMode::RFC5424);
+        }
+        config => panic!("unexpected config {:?}", config),
+    }
+
+    match "unix://@syslog?socket_type=stream".parse::<AnySyslogConfig>().unwrap() {
+        AnySyslogConfig::UDS(config) => {
+            assert_eq!(config.connection_config.socket, Some(PathBuf::from("@syslog")));
+            assert_eq!(config.connection_config.socket_type, UDSSocketType::Stream);
+        }
+        config => panic!("unexpected config {:?}", config),
+    }
+
+    match "tls://logs.internal?ca_file=/etc/ssl/ca.pem&framing=lf"
+        .parse::<AnySyslogConfig>()
+        .unwrap() {
+        AnySyslogConfig::TCP(config) => {
+            assert_eq!(config.connection_config.servers,
+                       vec!["logs.internal:6514".to_string()]);
+            assert_eq!(config.connection_config.framing, Some(Framing::NonTransparent(b'\n')));
+            assert_eq!(config.connection_config.tls,
+                       Some(TLSConfig::new().ca_file("/etc/ssl/ca.pem")));
+        }
+        config => panic!("unexpected config {:?}", config),
+    }
+
+    match "tcp+tls://logs.internal:6514?mode=rfc5424&facility=local3&tz=utc"
+        .parse::<AnySyslogConfig>()
+        .unwrap() {
+        AnySyslogConfig::TCP(config) => {
+            assert_eq!(config.connection_config.servers,
+                       vec!["logs.internal:6514".to_string()]);
+            assert_eq!(config.connection_config.tls, Some(TLSConfig::new()));
+            assert_eq!(config.mode, FormatMode::RFC5424);
+            assert_eq!(config.facility, Facility::LOG_LOCAL3);
+        }
+        config => panic!("unexpected config {:?}", config),
+    }
+
+    match "udp://::1,[::1],[::1]:1514".parse::<AnySyslogConfig>().unwrap() {
+        AnySyslogConfig::UDP(config) => {
+            assert_eq!(config.connection_config.servers,
+                       vec!["[::1]:514".to_string(),
+                            "[::1]:514".to_string(),
+                            "[::1]:1514".to_string()]);
+        }
+        config => panic!("unexpected config {:?}", config),
+    }
+
+    assert!("logs.internal:514".parse::<SyslogSettings>().is_err());
+    assert!("udp://logs.internal?colour=blue".parse::<SyslogSettings>().is_err());
+}
+
+#[test]
+fn settings_from_env_vars() {
+    let vars = vec![("SYSLOG_URL".to_string(), "udp://127.0.0.1?mode=rfc5424".to_string()),
+                    ("SYSLOG_FACILITY".to_string(), "daemon".to_string()),
+                    ("SYSLOG_MODE".to_string(), "rfc3164".to_string()),
+                    ("SYSLOG_NG_CONF".to_string(), "/etc/syslog-ng.conf".to_string()),
+                    ("HOME".to_string(), "/root".to_string())];
+    let settings = SyslogSettings::from_vars(vars).unwrap();
+    assert_eq!(settings.transport, Some(Transport::UDP));
+    assert_eq!(settings.servers, vec!["127.0.0.1".to_string()]);
+    assert_eq!(settings.facility, Facility::LOG_DAEMON);
+    assert_eq!(settings.mode, FormatMode::RFC3164);
+}
+
+#[test]
+fn settings_from_serde() {
+    let settings: SyslogSettings = ::serde_json::from_str(r#"{
+        "transport": "udp",
+        "servers": ["10.0.0.1", "10.0.0.2:5514"],
+        "serialization": "cee",
+        "async": true,
+        "probe_interval": 10
+    }"#)
+        .unwrap();
+    assert_eq!(settings.servers,
+               vec!["10.0.0.1".to_string(), "10.0.0.2:5514".to_string()]);
+    assert_eq!(settings.serialization, SerializationFormat::CEE);
+    assert!(settings.async);
+    assert_eq!(settings.probe_interval, Some(Duration::from_secs(10)));
+
+    let result = ::serde_json::from_str::<SyslogSettings>(r#"{"mode": "rfc9999"}"#);
+    assert!(result.is_err());
+}
+
+#[test]
+fn config_from_serde() {
+    let config: AnySyslogConfig = ::serde_json::from_str(r#"{
+        "transport": "tcp",
+        "servers": ["10.0.0.1"],
+        "mode": "rfc5424"
+    }"#)
+        .unwrap();
+    match config {
+        AnySyslogConfig::TCP(config) => {
+            assert_eq!(config.connection_config.servers, vec!["10.0.0.1:514".to_string()]);
+            assert_eq!(config.mode, FormatMode::RFC5424);
+        }
+        config => panic!("unexpected config {:?}", config),
+    }
+
+    let result = ::serde_json::from_str::<AnySyslogConfig>(r#"{"servers": ["10.0.0.1"]}"#);
+    assert!(result.is_err());
+}
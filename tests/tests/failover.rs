@@ -0,0 +1,108 @@
+#[test]
+fn failover_uses_secondary_when_primary_fails() {
+    let primary = TestSyslogDrain::new();
+    let secondary = TestSyslogDrain::new();
+    let primary_sent = primary.sent();
+    let primary_failing = primary.failing();
+    let secondary_sent = secondary.sent();
+
+    let drain = FailoverDrain::new(vec![Box::new(primary), Box::new(secondary)])
+        .probe_interval(Duration::from_secs(3600));
+
+    primary_failing.store(true, Ordering::SeqCst);
+    drain.send(b"one").unwrap();
+    assert_eq!(drain.active(), 1);
+    assert_eq!(drain.health(), vec![false, true]);
+
+    // Primary is not probed before probe interval elapses
+    primary_failing.store(false, Ordering::SeqCst);
+    drain.send(b"two").unwrap();
+
+    assert!(primary_sent.lock().unwrap().is_empty());
+    assert_eq!(*secondary_sent.lock().unwrap(),
+               vec![b"one".to_vec(), b"two".to_vec()]);
+}
+
+#[test]
+fn failover_fails_back_to_primary() {
+    let primary = TestSyslogDrain::new();
+    let secondary = TestSyslogDrain::new();
+    let primary_sent = primary.sent();
+    let primary_failing = primary.failing();
+
+    let drain = FailoverDrain::new(vec![Box::new(primary), Box::new(secondary)])
+        .probe_interval(Duration::from_secs(0));
+
+    primary_failing.store(true, Ordering::SeqCst);
+    drain.send(b"one").unwrap();
+    assert_eq!(drain.active(), 1);
+
+    primary_failing.store(false, Ordering::SeqCst);
+    drain.send(b"two").unwrap();
+    assert_eq!(drain.active(), 0);
+    assert_eq!(drain.health(), vec![true, true]);
+    assert_eq!(*primary_sent.lock().unwrap(), vec![b"two".to_vec()]);
+}
+
+#[test]
+fn failover_fails_when_all_destinations_fail() {
+    let primary = TestSyslogDrain::new();
+    let secondary = TestSyslogDrain::new();
+    primary.failing().store(true, Ordering::SeqCst);
+    secondary.failing().store(true, Ordering::SeqCst);
+
+    let drain = FailoverDrain::new(vec![Box::new(primary), Box::new(secondary)]);
+    assert!(drain.send(b"lost").is_err());
+}
+
+#[test]
+fn failover_skips_destination_marked_unhealthy() {
+    let primary = TestSyslogDrain::new();
+    let secondary = TestSyslogDrain::new();
+    let primary_sent = primary.sent();
+
+    let drain = FailoverDrain::new(vec![Box::new(primary), Box::new(secondary)])
+        .probe_interval(Duration::from_secs(60))
+        .unhealthy(0);
+    drain.send(b"one").unwrap();
+    assert_eq!(drain.active(), 1);
+    assert_eq!(drain.health(), vec![false, true]);
+    assert!(primary_sent.lock().unwrap().is_empty());
+}
+
+// Destination which never reconnects
+struct UnreachableDrain;
+
+impl SyslogDrain for UnreachableDrain {
+    fn format(&self, record: &::slog::Record, _: &::slog::OwnedKeyValueList)
+              -> SyslogResult<Vec<u8>> {
+        Ok(format!("{}", record.msg()).into_bytes())
+    }
+
+    fn send(&self, _: &[u8]) -> SyslogResult<()> {
+        Err(SyslogError::NoDestination)
+    }
+
+    fn reconnect(&self) -> SyslogResult<()> {
+        let err = ::std::io::Error::new(::std::io::ErrorKind::ConnectionRefused, "unreachable");
+        Err(SyslogError::connect(Transport::TCP, "unreachable", err))
+    }
+}
+
+#[test]
+fn failover_reconnect_needs_one_reachable_destination() {
+    let destinations: Vec<BoxedSyslogDrain> = vec![Box::new(UnreachableDrain),
+                                                   Box::new(UnreachableDrain)];
+    let drain = FailoverDrain::new(destinations);
+    match drain.reconnect() {
+        Err(SyslogError::ConnectFailed { .. }) => {}
+        other => panic!("Expected ConnectFailed, got {:?}", other),
+    }
+    assert_eq!(drain.health(), vec![false, false]);
+
+    let destinations: Vec<BoxedSyslogDrain> = vec![Box::new(UnreachableDrain),
+                                                   Box::new(TestSyslogDrain::new())];
+    let drain = FailoverDrain::new(destinations);
+    drain.reconnect().unwrap();
+    assert_eq!(drain.health(), vec![false, true]);
+}
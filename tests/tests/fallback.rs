@@ -0,0 +1,51 @@
+#[test]
+fn fallback_drain_writes_undeliverable_records_to_file() {
+    let path = scratch_dir("fallback").with_extension("log");
+    let _ = fs::remove_file(&path);
+
+    let syslog = TestSyslogDrain::new();
+    let sent = syslog.sent();
+    let failing = syslog.failing();
+    let errors = Arc::new(AtomicUsize::new(0));
+    let reported = errors.clone();
+
+    let drain = FallbackDrain::new(syslog)
+        .on_error(move |_| {
+                      reported.fetch_add(1, Ordering::SeqCst);
+                  })
+        .fallback(FallbackSink::File(path.clone()))
+        .diagnostics_interval(None);
+
+    drain.send(b"delivered\n").unwrap();
+    failing.store(true, Ordering::SeqCst);
+    drain.send(b"first undelivered\n\0").unwrap();
+    drain.send(b"second undelivered").unwrap();
+
+    assert_eq!(*sent.lock().unwrap(), vec![b"delivered\n".to_vec()]);
+    assert_eq!(errors.load(Ordering::SeqCst), 2);
+    assert_eq!(drain.failures(), 2);
+
+    let mut written = String::new();
+    fs::File::open(&path).unwrap().read_to_string(&mut written).unwrap();
+    assert_eq!(written, "first undelivered\nsecond undelivered\n");
+}
+
+#[test]
+fn fallback_drain_never_fails() {
+    let syslog = TestSyslogDrain::new();
+    syslog.failing().store(true, Ordering::SeqCst);
+
+    let drain = FallbackDrain::new(syslog).diagnostics_interval(Some(Duration::from_secs(3600)));
+    let logger = Logger::root(drain.fuse(), o!());
+    info!(logger, "lost message");
+    info!(logger, "another lost message");
+}
+
+#[test]
+fn fallback_sink_from_str() {
+    assert_eq!("stderr".parse::<FallbackSink>().unwrap(), FallbackSink::Stderr);
+    assert_eq!("console".parse::<FallbackSink>().unwrap(), FallbackSink::Console);
+    assert_eq!("/var/log/app-syslog.log".parse::<FallbackSink>().unwrap(),
+               FallbackSink::File(PathBuf::from("/var/log/app-syslog.log")));
+    assert!("".parse::<FallbackSink>().is_err());
+}
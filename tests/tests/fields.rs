@@ -0,0 +1,55 @@
+// Log a record with a key shadowing a logger key through the formatter
+fn emit_shadowing_message<F>(formatter: F) -> String
+    where F: ::slog_stream::Format + 'static
+{
+    emit_to_string(formatter, |root| {
+        let logger = root.new(o!("service" => "billing", "request" => "r1"))
+            .new(o!("request" => "r2", "internal" => "x"));
+        info!(logger, "Charged"; "amount" => 10, "request" => "r3");
+    })
+}
+
+#[test]
+fn fields_emit_all_values_by_default() {
+    let message = emit_shadowing_message(formatter!(Rfc3164ShortKsv));
+    assert!(message.contains("Charged request=r3 amount=10 internal=x request=r2 \
+                              request=r1 service=billing"));
+}
+
+#[test]
+fn fields_prefix_rename_drop_and_dedup() {
+    let fields = FieldTransforms::new()
+        .prefix("app.")
+        .rename("amount", "amount_usd")
+        .drop_key("internal")
+        .dedup(true);
+    let options = FormatOptions::default().fields(fields);
+    let message = emit_shadowing_message(formatter!(Rfc3164ShortKsv).options(options));
+    assert!(message.contains("Charged app.request=r3 app.amount_usd=10 app.service=billing"));
+}
+
+#[test]
+fn fields_logger_first_order() {
+    let fields = FieldTransforms::new().dedup(true).order(FieldOrder::LoggerFirst);
+    let options = FormatOptions::default().fields(fields);
+    let message = emit_shadowing_message(formatter!(Rfc3164ShortKsv).options(options));
+    assert!(message.contains("Charged internal=x service=billing request=r3 amount=10"));
+}
+
+#[test]
+fn fields_in_rfc5424_structured_data() {
+    let fields = FieldTransforms::new().dedup(true).rename("service", "svc");
+    let options = FormatOptions::default().fields(fields);
+    let message = emit_shadowing_message(formatter!(Rfc5424NativeTsIsoUtc).options(options));
+    assert!(message.contains(" request=\"r3\" amount=\"10\"][logger@"));
+    assert!(message.contains(" internal=\"x\" svc=\"billing\"]"));
+}
+
+#[test]
+fn fields_redaction_uses_original_key() {
+    let fields = FieldTransforms::new().rename("request", "req");
+    let redaction = RedactionPolicy::new().deny_key("request");
+    let options = FormatOptions::default().fields(fields).redaction(redaction);
+    let message = emit_shadowing_message(formatter!(Rfc3164ShortKsv).options(options));
+    assert!(message.contains("Charged req=[REDACTED] amount=10"));
+}
@@ -0,0 +1,55 @@
+// Tests run by `main`, without the test harness
+pub const TESTS: &'static [(&'static str, fn())] =
+    &[("fork_rfc3164_reports_child_pid", fork_rfc3164_reports_child_pid),
+      ("fork_rfc5424_reports_child_pid", fork_rfc5424_reports_child_pid)];
+
+// Emit the test message from a forked child, returning the child pid and the message
+fn emit_in_child<F>(formatter: F) -> (i32, String)
+    where F: ::slog_stream::Format + 'static
+{
+    let (parent_socket, child_socket) = UnixDatagram::pair().unwrap();
+    parent_socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    // Everything the child uses is built before forking
+    let buffer = TestIoBuffer::new(1024);
+    let logger = Logger::root(TestDrain::new(buffer.io(), formatter).fuse(),
+                              o!("lk1" => "lv1", "lk2" => "lv2"));
+    let child = unsafe { ::libc::fork() };
+    assert!(child >= 0);
+    if child == 0 {
+        // Child must not unwind into the parent's code
+        let sent = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            info!(logger, "Test message 1"; "mk1" => "mv1", "mk2" => "mv2");
+            child_socket.send(&buffer.as_vec()).is_ok()
+        }));
+        let status = match sent {
+            Ok(true) => 0,
+            _ => 1,
+        };
+        unsafe { ::libc::_exit(status) };
+    }
+
+    let mut message = vec![0; 1024];
+    let received = parent_socket.recv(&mut message);
+    if received.is_err() {
+        unsafe { ::libc::kill(child, ::libc::SIGKILL) };
+    }
+    let mut status = 0;
+    unsafe { ::libc::waitpid(child, &mut status, 0) };
+    let len = received.expect("child didn't send the message in time");
+    assert!(::libc::WIFEXITED(status) && ::libc::WEXITSTATUS(status) == 0,
+            "child failed with status {}",
+            status);
+    (child, String::from_utf8_lossy(&message[..len]).into_owned())
+}
+
+pub fn fork_rfc3164_reports_child_pid() {
+    let (child, message) = emit_in_child(formatter!(Rfc3164ShortKsv));
+    assert!(message.contains(&format!("test[{}]: Test message 1", child)));
+    assert!(!message.contains("[12345]"));
+}
+
+pub fn fork_rfc5424_reports_child_pid() {
+    let (child, message) = emit_in_child(formatter!(Rfc5424NativeTsIsoUtc));
+    assert!(message.contains(&format!(" test {} INFO ", child)));
+}
@@ -0,0 +1,101 @@
+#[test]
+fn hostname_sanitized() {
+    assert_eq!(sanitize_hostname("my host.example.com."), "myhost.example.com");
+    assert_eq!(sanitize_hostname("höst\t1"), "hst1");
+    let long = ::std::iter::repeat("a").take(300).collect::<String>();
+    assert_eq!(sanitize_hostname(&long).len(), HOSTNAME_MAX_LEN);
+}
+
+#[test]
+fn hostname_sources() {
+    let system = get_host_name().unwrap();
+    let short = get_host_name_from(HostnameSource::Short).unwrap();
+    assert!(!short.contains('.'));
+    assert!(system.starts_with(&short) || short.parse::<IpAddr>().is_ok());
+    assert!(get_host_name_from(HostnameSource::Fqdn).is_some());
+    assert!(validate_hostname(&get_host_name_from(HostnameSource::System).unwrap()).is_ok());
+
+    // No name to fall back from, only the primary address
+    assert_eq!(get_host_name_from(HostnameSource::Ip),
+               get_primary_address().map(|ip| ip.to_string()));
+}
+
+#[test]
+fn hostname_source_settings() {
+    assert_eq!("FQDN".parse::<HostnameSource>().unwrap(), HostnameSource::Fqdn);
+    assert!("domain".parse::<HostnameSource>().is_err());
+
+    let settings = "udp://localhost:514?hostname_source=short".parse::<SyslogSettings>().unwrap();
+    assert_eq!(settings.hostname_source, HostnameSource::Short);
+    assert_eq!(syslog().hostname_source(HostnameSource::Ip).hostname_source,
+               HostnameSource::Ip);
+}
+
+#[test]
+fn rfc3164_full_always_has_hostname() {
+    let buffer = emit_test_message_to_buffer(formatter!(Rfc3164KsvTsIsoUtc));
+    let address = get_primary_address().map(|ip| ip.to_string())
+        .unwrap_or_else(|| HOSTNAME_FALLBACK.to_owned());
+    assert!(buffer.as_string().contains(&format!(" {} test[12345]: Test message 1", address)));
+
+    let fields = HeaderFields::new(Some("web-1".to_owned()),
+                                   Some("test".to_owned()),
+                                   12345,
+                                   Facility::LOG_USER);
+    let buffer = emit_test_message_to_buffer(Rfc3164KsvTsIsoUtc::from_fields(fields));
+    assert!(buffer.as_string().contains(" web-1 test[12345]: Test message 1"));
+}
+
+#[test]
+fn configured_pid_is_kept_without_fork() {
+    let buffer = emit_test_message_to_buffer(formatter!(Rfc3164ShortKsv));
+    assert!(buffer.as_string().contains("test[12345]:"));
+}
+
+// Log twice through the same formatter, calling `between` in between
+fn emit_twice<F, B>(formatter: F, between: B) -> (String, String)
+    where F: ::slog_stream::Format + 'static,
+          B: FnOnce()
+{
+    let buffer = TestIoBuffer::new(1024);
+    let logger = Logger::root(TestDrain::new(buffer.io(), formatter).fuse(), o!());
+    info!(logger, "First");
+    let first = buffer.as_string();
+    between();
+    info!(logger, "Second");
+    let second = buffer.as_string()[first.len()..].to_string();
+    (first, second)
+}
+
+fn stale_hostname_formatter(refresh: HostnameRefresh) -> Rfc5424NativeTsIsoUtc {
+    let fields = HeaderFields::new(Some("stale-host".to_owned()),
+                                   Some("test".to_owned()),
+                                   12345,
+                                   Facility::LOG_USER)
+        .hostname_refresh(refresh);
+    Rfc5424NativeTsIsoUtc::from_fields(fields)
+}
+
+#[test]
+fn hostname_refreshed_on_demand() {
+    let hostname = get_host_name().unwrap();
+    let (_, second) = emit_twice(stale_hostname_formatter(HostnameRefresh::OnDemand),
+                                 refresh_hostname);
+    assert!(second.contains(&format!(" {} test ", hostname)));
+}
+
+#[test]
+fn hostname_refreshed_periodically() {
+    let hostname = get_host_name().unwrap();
+    let refresh = HostnameRefresh::Every(Duration::from_millis(10));
+    let (_, second) = emit_twice(stale_hostname_formatter(refresh),
+                                 || ::std::thread::sleep(Duration::from_millis(20)));
+    assert!(second.contains(&format!(" {} test ", hostname)));
+}
+
+#[test]
+fn hostname_never_refreshed() {
+    let (_, second) = emit_twice(stale_hostname_formatter(HostnameRefresh::Never),
+                                 refresh_hostname);
+    assert!(second.contains(" stale-host test "));
+}
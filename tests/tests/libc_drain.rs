@@ -0,0 +1,21 @@
+#[test]
+fn libc_syslog_drain_connect_disconnect() {
+    let drain = LibcSyslogDrain::new("slog-syslog-ng-test", Facility::LOG_LOCAL0)
+        .log_pid(true)
+        .log_ndelay(false)
+        .mask_up_to(Severity::LOG_ERR)
+        .connect()
+        .expect("openlog failed");
+    assert!(drain.disconnect().is_ok());
+}
+
+#[test]
+fn libc_syslog_drain_logs() {
+    // syslog(3) reports no errors, logging must just not fail
+    let drain = LibcSyslogDrain::new("slog-syslog-ng-test", Facility::LOG_LOCAL0)
+        .mask_up_to(Severity::LOG_DEBUG)
+        .connect()
+        .expect("openlog failed");
+    let logger = Logger::root(drain.fuse(), o!("lk1" => "lv1"));
+    info!(logger, "libc syslog test message"; "mk1" => "mv1");
+}
@@ -0,0 +1,55 @@
+// Drain formatting records into a shared buffer
+struct BufferDrain<F> {
+    buffer: Arc<Mutex<Vec<u8>>>,
+    formatter: F,
+}
+
+impl<F> Drain for BufferDrain<F>
+    where F: StreamFormat
+{
+    type Error = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKeyValueList) -> io::Result<()> {
+        let mut buffer = self.buffer.lock().unwrap();
+        self.formatter.format(buffer.deref_mut(), record, values)
+    }
+}
+
+fn take(buffer: &Arc<Mutex<Vec<u8>>>) -> String {
+    let mut buffer = buffer.lock().unwrap();
+    let message = String::from_utf8(buffer.clone()).unwrap();
+    buffer.clear();
+    message
+}
+
+#[test]
+fn log_records_are_sent_through_the_drain() {
+    assert_eq!(Severity::from(::log::LogLevel::Error), Severity::LOG_ERR);
+    assert_eq!(Severity::from(::log::LogLevel::Trace), Severity::LOG_DEBUG);
+
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let options = FormatOptions::default()
+        .sd_element(SdElement::new("log@32473").prefix("log."));
+    let drain = BufferDrain {
+        buffer: buffer.clone(),
+        formatter: Rfc5424NativeTsIsoUtc::new(None,
+                                              Some("test".to_owned()),
+                                              12345,
+                                              Facility::LOG_USER)
+            .options(options),
+    };
+    SyslogLog::new(drain).max_level(::log::LogLevelFilter::Info).init().unwrap();
+
+    warn!(target: "billing", "Charged {}", 10);
+    let message = take(&buffer);
+    assert!(message.starts_with("<12>1 "), message);
+    // No empty record and logger elements, their SD-IDs don't depend on the line
+    assert!(message.contains(" 12345 WARN [log@32473 target=\"billing\" "), message);
+    assert!(!message.contains("@0"), message);
+    assert!(message.contains(" module_path=\"log_bridge_tests::tests\""), message);
+    assert!(message.contains("log_bridge.rs\" line=\""), message);
+    assert!(message.contains("] Charged 10"), message);
+
+    debug!("Not sent");
+    assert_eq!(take(&buffer), "");
+}
@@ -0,0 +1,94 @@
+#[test]
+fn udp_drain_counts_sent_and_truncated_messages() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let states = Arc::new(Mutex::new(Vec::new()));
+    let reported = states.clone();
+
+    let drain = UDPDrain::new(server.local_addr().unwrap(), formatter!(Rfc3164ShortKsv))
+        .max_message_size(16)
+        .on_state_change(move |state, _| reported.lock().unwrap().push(state))
+        .connect()
+        .unwrap();
+    drain.send(b"<14>short\n").unwrap();
+    drain.send(b"<14>message longer than sixteen bytes\n").unwrap();
+
+    let mut buf = [0u8; 1024];
+    assert_eq!(server.recv(&mut buf).unwrap(), 10);
+    assert_eq!(server.recv(&mut buf).unwrap(), 16);
+
+    let stats = drain.stats();
+    assert_eq!(stats.records_sent, 2);
+    assert_eq!(stats.bytes_sent, 26);
+    assert_eq!(stats.truncated, 1);
+    assert_eq!(stats.errors.total(), 0);
+    assert!(stats.connected);
+    assert_eq!(*states.lock().unwrap(), vec![ConnectionState::Connected]);
+    assert_eq!(SyslogDrain::stats(&drain), Some(stats));
+}
+
+#[test]
+fn udp_drain_rejects_too_large_messages_without_truncation() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let drain = UDPDrain::new(server.local_addr().unwrap(), formatter!(Rfc3164ShortKsv))
+        .max_message_size(16)
+        .truncate(false)
+        .connect()
+        .unwrap();
+
+    match drain.send(b"<14>message longer than sixteen bytes\n") {
+        Err(SyslogError::MessageTooLarge { transport: Transport::UDP, size, limit, .. }) => {
+            assert_eq!((size, limit), (38, Some(16)))
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    let stats = drain.stats();
+    assert_eq!(stats.records_sent, 0);
+    assert_eq!(stats.truncated, 0);
+    assert_eq!(stats.errors.too_large, 1);
+}
+
+#[test]
+fn tcp_drain_reports_connection_state_changes() {
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let states = Arc::new(Mutex::new(Vec::new()));
+    let reported = states.clone();
+
+    let drain = TCPDrainFramed::new(addr, formatter!(Rfc5424KsvTsIsoUtc))
+        .lazy_connect(true)
+        .on_state_change(move |state, destination| {
+                             reported.lock().unwrap().push((state, destination.to_string()))
+                         })
+        .connect()
+        .unwrap();
+    assert!(drain.send(b"<14>1 - unreachable").is_err());
+    assert_eq!(drain.stats().errors.connect, 1);
+    assert!(states.lock().unwrap().is_empty());
+
+    let _listener = TcpListener::bind(addr).unwrap();
+    drain.send(b"<14>1 - connected").unwrap();
+
+    let stats = drain.stats();
+    assert_eq!(stats.records_sent, 1);
+    assert_eq!(stats.bytes_sent, b"17 <14>1 - connected".len());
+    assert!(stats.connected);
+    assert_eq!(*states.lock().unwrap(),
+               vec![(ConnectionState::Connected, addr.to_string())]);
+}
+
+#[test]
+fn drain_stats_merge() {
+    let drains: Vec<BoxedSyslogDrain> = vec![Box::new(TestSyslogDrain::new())];
+    assert_eq!(FailoverDrain::new(drains).stats(), None);
+
+    let mut stats = DrainStats { records_sent: 2, bytes_sent: 20, ..DrainStats::default() };
+    stats.merge(&DrainStats {
+                    records_sent: 1,
+                    bytes_sent: 5,
+                    connected: true,
+                    ..DrainStats::default()
+                });
+    assert_eq!(stats.records_sent, 3);
+    assert_eq!(stats.bytes_sent, 25);
+    assert!(stats.connected);
+}
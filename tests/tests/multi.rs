@@ -0,0 +1,116 @@
+#[test]
+fn multi_round_robin_alternates_destinations() {
+    let first = TestSyslogDrain::new();
+    let second = TestSyslogDrain::new();
+    let first_sent = first.sent();
+    let second_sent = second.sent();
+
+    let drain = MultiDrain::new(vec![Box::new(first), Box::new(second)],
+                                Distribution::RoundRobin);
+    for message in &[b"one", b"two", b"six"] {
+        drain.send(&message[..]).unwrap();
+    }
+
+    assert_eq!(*first_sent.lock().unwrap(), vec![b"one".to_vec(), b"six".to_vec()]);
+    assert_eq!(*second_sent.lock().unwrap(), vec![b"two".to_vec()]);
+}
+
+#[test]
+fn multi_round_robin_skips_failed_destination() {
+    let first = TestSyslogDrain::new();
+    let second = TestSyslogDrain::new();
+    let second_sent = second.sent();
+    first.failing().store(true, Ordering::SeqCst);
+
+    let drain = MultiDrain::new(vec![Box::new(first), Box::new(second)],
+                                Distribution::RoundRobin);
+    drain.send(b"one").unwrap();
+    drain.send(b"two").unwrap();
+
+    assert_eq!(second_sent.lock().unwrap().len(), 2);
+    assert_eq!(drain.errors(), vec![1, 0]);
+}
+
+#[test]
+fn multi_hash_by_key_is_sticky() {
+    let first = TestSyslogDrain::new();
+    let second = TestSyslogDrain::new();
+    let first_sent = first.sent();
+    let second_sent = second.sent();
+
+    let drain = MultiDrain::new(vec![Box::new(first), Box::new(second)],
+                                Distribution::HashByKey("tenant".to_owned()));
+    let logger = Logger::root(drain.fuse(), o!());
+    for _ in 0..4 {
+        info!(logger, "tenant message"; "tenant" => "acme");
+    }
+
+    let first_count = first_sent.lock().unwrap().len();
+    let second_count = second_sent.lock().unwrap().len();
+    assert!(first_count == 4 && second_count == 0 || first_count == 0 && second_count == 4);
+}
+
+#[test]
+fn multi_broadcast_fails_only_when_all_fail() {
+    let first = TestSyslogDrain::new();
+    let second = TestSyslogDrain::new();
+    let second_sent = second.sent();
+    let first_failing = first.failing();
+    let second_failing = second.failing();
+
+    let errors = Arc::new(Mutex::new(Vec::<usize>::new()));
+    let reported = errors.clone();
+    let drain = MultiDrain::new(vec![Box::new(first), Box::new(second)],
+                                Distribution::Broadcast)
+        .on_error(move |index, _| reported.lock().unwrap().push(index));
+
+    first_failing.store(true, Ordering::SeqCst);
+    drain.send(b"copy").unwrap();
+    assert_eq!(*second_sent.lock().unwrap(), vec![b"copy".to_vec()]);
+    assert_eq!(*errors.lock().unwrap(), vec![0]);
+
+    second_failing.store(true, Ordering::SeqCst);
+    assert!(drain.send(b"lost").is_err());
+    assert_eq!(*errors.lock().unwrap(), vec![0, 0, 1]);
+}
+
+#[test]
+fn multi_broadcast_copies_filtered_records() {
+    let all = TestSyslogDrain::new();
+    let security = TestSyslogDrain::new();
+    let all_sent = all.sent();
+    let security_sent = security.sent();
+
+    let drain = MultiDrain::new(vec![Box::new(all), Box::new(security)],
+                                Distribution::Broadcast)
+        .filter(1, RecordFilter::KeyValue("category".to_owned(), "security".to_owned()));
+    let logger = Logger::root(drain.fuse(), o!());
+    info!(logger, "login failed"; "category" => "security");
+    info!(logger, "page served"; "category" => "access");
+
+    assert_eq!(all_sent.lock().unwrap().len(), 2);
+    assert_eq!(*security_sent.lock().unwrap(), vec![b"login failed".to_vec()]);
+}
+
+#[test]
+fn multi_filters_by_severity_and_key() {
+    let critical = TestSyslogDrain::new();
+    let tenants = TestSyslogDrain::new();
+    let critical_sent = critical.sent();
+    let tenants_sent = tenants.sent();
+
+    let drain = MultiDrain::new(vec![Box::new(critical), Box::new(tenants)],
+                                Distribution::Broadcast)
+        .filter(0, RecordFilter::MinSeverity(Severity::LOG_ERR))
+        .filter(1, RecordFilter::HasKey("tenant".to_owned()));
+    // Formatted messages only go to destinations without a filter
+    drain.send(b"raw").unwrap();
+
+    let logger = Logger::root(drain.fuse(), o!());
+    error!(logger, "disk full");
+    warn!(logger, "slow request"; "tenant" => "acme");
+    debug!(logger, "dropped");
+
+    assert_eq!(*critical_sent.lock().unwrap(), vec![b"disk full".to_vec()]);
+    assert_eq!(*tenants_sent.lock().unwrap(), vec![b"slow request".to_vec()]);
+}
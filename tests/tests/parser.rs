@@ -0,0 +1,102 @@
+#[test]
+fn parse_rfc5424_message() {
+    let message = SyslogMessage::parse(b"<165>1 2003-10-11T22:14:15.003Z mymachine evntslog 42 \
+                                         ID47 [exampleSDID@32473 iut=\"3\" eventID=\"10\\]11\"]\
+                                         [origin ip=\"10.0.0.1\"] An application event\n\0")
+        .unwrap();
+    assert_eq!(message.mode, FormatMode::RFC5424);
+    assert_eq!(message.priority, 165);
+    assert_eq!(message.timestamp, Some("2003-10-11T22:14:15.003Z".to_string()));
+    assert_eq!(message.hostname, Some("mymachine".to_string()));
+    assert_eq!(message.app_name, Some("evntslog".to_string()));
+    assert_eq!(message.proc_id, Some("42".to_string()));
+    assert_eq!(message.msg_id, Some("ID47".to_string()));
+    assert_eq!(message.structured_data,
+               vec![("exampleSDID@32473".to_string(),
+                     vec![("iut".to_string(), "3".to_string()),
+                          ("eventID".to_string(), "10]11".to_string())]),
+                    ("origin".to_string(), vec![("ip".to_string(), "10.0.0.1".to_string())])]);
+    assert_eq!(message.message, "An application event");
+
+    let message = SyslogMessage::parse(b"<34>1 - - su - - -").unwrap();
+    assert_eq!(message.hostname, None);
+    assert_eq!(message.app_name, Some("su".to_string()));
+    assert!(message.structured_data.is_empty());
+    assert_eq!(message.message, "");
+}
+
+#[test]
+fn parse_rfc3164_message() {
+    let message = SyslogMessage::parse(b"<34>Oct 11 22:14:15 mymachine su[230]: 'su root' failed")
+        .unwrap();
+    assert_eq!(message.mode, FormatMode::RFC3164);
+    assert_eq!(message.priority, 34);
+    assert_eq!(message.timestamp, Some("Oct 11 22:14:15".to_string()));
+    assert_eq!(message.hostname, Some("mymachine".to_string()));
+    assert_eq!(message.app_name, Some("su".to_string()));
+    assert_eq!(message.proc_id, Some("230".to_string()));
+    assert_eq!(message.message, "'su root' failed");
+
+    // Short header of the unix domain socket drain
+    let message = SyslogMessage::parse(b"<14> test[12345]: Test message 1 mk1=mv1").unwrap();
+    assert_eq!(message.timestamp, None);
+    assert_eq!(message.hostname, None);
+    assert_eq!(message.app_name, Some("test".to_string()));
+    assert_eq!(message.message, "Test message 1 mk1=mv1");
+}
+
+#[test]
+fn parse_own_messages() {
+    for buffer in vec![emit_test_message_to_buffer(formatter!(Rfc3164KsvTsIsoUtc)),
+                       emit_test_message_to_buffer(formatter!(Rfc3164KsvTs3164Local))] {
+        let message = SyslogMessage::parse(&buffer.as_vec()).unwrap();
+        assert!(message.timestamp.is_some());
+        assert_eq!(message.hostname, None);
+        assert_eq!(message.app_name, Some("test".to_string()));
+        assert_eq!(message.proc_id, Some("12345".to_string()));
+        assert_eq!(message.message, "Test message 1 mk2=mv2 mk1=mv1 lk2=lv2 lk1=lv1");
+    }
+
+    let buffer = emit_test_message_to_buffer(formatter!(Rfc5424NativeTsIsoUtc));
+    let message = SyslogMessage::parse(&buffer.as_vec()).unwrap();
+    assert_eq!(message.msg_id, Some("INFO".to_string()));
+    assert_eq!(message.structured_data.len(), 2);
+    assert_eq!(message.message, "Test message 1");
+}
+
+#[test]
+fn parse_invalid_messages() {
+    assert!(SyslogMessage::parse(b"no priority").is_err());
+    assert!(SyslogMessage::parse(b"<192>Oct 11 22:14:15 host tag: text").is_err());
+    assert!(SyslogMessage::parse(b"<14>1 - - - - - [unterminated").is_err());
+
+    let message = SyslogMessage::unparsed(b"no priority\n");
+    assert_eq!(message.priority, 13);
+    assert_eq!(message.message, "no priority");
+}
+
+#[test]
+fn convert_rfc3164_to_rfc5424() {
+    let message = SyslogMessage::parse(b"<14>Oct 11 22:14:15 web-1 app[7]: Charged order=o1 n=2")
+        .unwrap()
+        .ksv_to_structured_data("msg@32473");
+    assert_eq!(message.message, "Charged");
+    let converted = String::from_utf8(message.to_rfc5424()).unwrap();
+    assert!(converted.starts_with("<14>1 "));
+    assert!(converted.ends_with(" web-1 app 7 - [msg@32473 order=\"o1\" n=\"2\"] Charged"));
+    assert!(!converted.contains("Oct 11"));
+}
+
+#[test]
+fn convert_rfc5424_to_rfc3164() {
+    let message = SyslogMessage::parse(b"<165>1 2003-10-11T22:14:15.003Z host app - - \
+                                         [id@32473 k=\"a b\" n=\"1\"] Event")
+        .unwrap();
+    let converted = String::from_utf8(message.to_rfc3164()).unwrap();
+    assert!(converted.starts_with("<165>Oct 1"));
+    assert!(converted.ends_with(" host app: Event k=\"a b\" n=1"));
+
+    let reparsed = SyslogMessage::parse(converted.as_bytes()).unwrap();
+    assert_eq!(reparsed.hostname, Some("host".to_string()));
+    assert_eq!(reparsed.app_name, Some("app".to_string()));
+}
@@ -0,0 +1,184 @@
+// Drain sharing its limiter with the test, for the counters
+struct SharedLimiter(Arc<RateLimiter>, TestSyslogDrain);
+
+impl ::slog::Drain for SharedLimiter {
+    type Error = SyslogError;
+
+    fn log(&self, info: &::slog::Record, values: &::slog::OwnedKeyValueList) -> SyslogResult<()> {
+        self.0.log(&self.1, info, values)
+    }
+}
+
+#[test]
+fn rate_limit_drops_messages_over_the_limit() {
+    let syslog = TestSyslogDrain::new();
+    let sent = syslog.sent();
+
+    let config = RateLimitConfig::new().limit(RateLimit::new(2, Duration::from_secs(3600)));
+    let limiter = Arc::new(RateLimiter::new(config));
+    let logger = Logger::root(SharedLimiter(limiter.clone(), syslog).fuse(), o!());
+
+    info!(logger, "one");
+    info!(logger, "two");
+    info!(logger, "three");
+    crit!(logger, "critical");
+
+    assert_eq!(*sent.lock().unwrap(),
+               vec![b"one".to_vec(), b"two".to_vec(), b"critical".to_vec()]);
+    assert_eq!(limiter.limited(), 1);
+}
+
+#[test]
+fn rate_limit_severity_limit_overrides_limit() {
+    let config = RateLimitConfig::new()
+        .limit(RateLimit::new(10, Duration::from_secs(1)))
+        .severity_limit(Severity::LOG_DEBUG, RateLimit::new(1, Duration::from_secs(60)));
+    assert_eq!(config.limits.len(), 6);
+    assert_eq!(config.limits.last(),
+               Some(&(Severity::LOG_DEBUG, RateLimit::new(1, Duration::from_secs(60)))));
+}
+
+#[test]
+fn rate_limit_collapses_duplicates() {
+    let syslog = TestSyslogDrain::new();
+    let sent = syslog.sent();
+
+    let config = RateLimitConfig::new().suppress_duplicates(Duration::from_secs(3600));
+    let limiter = Arc::new(RateLimiter::new(config));
+    let logger = Logger::root(SharedLimiter(limiter.clone(), syslog).fuse(), o!());
+
+    for _ in 0..4 {
+        info!(logger, "disk full"; "device" => "sda");
+    }
+    info!(logger, "disk full"; "device" => "sdb");
+
+    assert_eq!(*sent.lock().unwrap(),
+               vec![b"disk full".to_vec(),
+                    b"last message repeated 3 times".to_vec(),
+                    b"disk full".to_vec()]);
+    assert_eq!(limiter.repeated(), 3);
+}
+
+#[test]
+fn rate_limit_flushes_repeats_on_drop() {
+    let syslog = TestSyslogDrain::new();
+    let sent = syslog.sent();
+
+    {
+        let config = RateLimitConfig::new().suppress_duplicates(Duration::from_secs(3600));
+        let logger = Logger::root(RateLimitDrain::new(syslog, config).fuse(), o!());
+        info!(logger, "same");
+        info!(logger, "same");
+    }
+
+    assert_eq!(*sent.lock().unwrap(),
+               vec![b"same".to_vec(), b"last message repeated 1 time".to_vec()]);
+}
+
+#[test]
+fn rate_limit_in_settings() {
+    let mut settings = SyslogSettings::default();
+    settings.set("rate_limit", "100").unwrap();
+    settings.set("suppress_duplicates", "30").unwrap();
+    assert!(settings.set("rate_limit", "fast").is_err());
+
+    let expected = RateLimitConfig::new()
+        .limit(RateLimit::new(100, Duration::from_secs(1)))
+        .suppress_duplicates(Duration::from_secs(30));
+    match settings.into_config().unwrap() {
+        AnySyslogConfig::Default(config) => assert_eq!(config.rate_limit, Some(expected)),
+        config => panic!("unexpected config {:?}", config),
+    }
+}
+
+#[test]
+fn rate_limit_never_collapses_critical_duplicates() {
+    let syslog = TestSyslogDrain::new();
+    let sent = syslog.sent();
+
+    let config = RateLimitConfig::new().suppress_duplicates(Duration::from_secs(3600));
+    let limiter = Arc::new(RateLimiter::new(config));
+    let logger = Logger::root(SharedLimiter(limiter.clone(), syslog).fuse(), o!());
+
+    crit!(logger, "disk failed");
+    crit!(logger, "disk failed");
+
+    assert_eq!(*sent.lock().unwrap(),
+               vec![b"disk failed".to_vec(), b"disk failed".to_vec()]);
+    assert_eq!(limiter.repeated(), 0);
+}
+
+#[test]
+fn rate_limit_dropped_record_keeps_duplicates_collapsed() {
+    let syslog = TestSyslogDrain::new();
+    let sent = syslog.sent();
+
+    let config = RateLimitConfig::new()
+        .severity_limit(Severity::LOG_DEBUG, RateLimit::new(0, Duration::from_secs(3600)))
+        .suppress_duplicates(Duration::from_secs(3600));
+    let limiter = Arc::new(RateLimiter::new(config));
+    let logger = Logger::root(SharedLimiter(limiter.clone(), syslog).fuse(), o!());
+
+    info!(logger, "same");
+    debug!(logger, "over the limit");
+    info!(logger, "same");
+
+    assert_eq!(*sent.lock().unwrap(), vec![b"same".to_vec()]);
+    assert_eq!(limiter.limited(), 1);
+    assert_eq!(limiter.repeated(), 1);
+}
+
+#[test]
+fn rate_limit_sends_repeats_after_interval() {
+    let syslog = TestSyslogDrain::new();
+    let sent = syslog.sent();
+
+    let config = RateLimitConfig::new().suppress_duplicates(Duration::from_millis(50));
+    let drain = ConfiguredDrain::new(Box::new(syslog)).rate_limit(config);
+    let logger = Logger::root(drain.fuse(), o!());
+    info!(logger, "same");
+    info!(logger, "same");
+    info!(logger, "same");
+
+    // No other record arrives, the timer sends the summary
+    ::std::thread::sleep(Duration::from_millis(300));
+    assert_eq!(*sent.lock().unwrap(),
+               vec![b"same".to_vec(), b"last message repeated 2 times".to_vec()]);
+}
+
+// Drain sharing the configured drain with the test, to flush it
+struct SharedConfigured(Arc<ConfiguredDrain>);
+
+impl ::slog::Drain for SharedConfigured {
+    type Error = SyslogError;
+
+    fn log(&self, info: &::slog::Record, values: &::slog::OwnedKeyValueList) -> SyslogResult<()> {
+        ::slog::Drain::log(&*self.0, info, values)
+    }
+}
+
+#[test]
+fn configured_drain_flushes_repeats_explicitly() {
+    let syslog = TestSyslogDrain::new();
+    let sent = syslog.sent();
+
+    let config = RateLimitConfig::new().suppress_duplicates(Duration::from_secs(3600));
+    let drain = Arc::new(ConfiguredDrain::new(Box::new(syslog)).rate_limit(config));
+    let logger = Logger::root(SharedConfigured(drain.clone()).fuse(), o!());
+    for _ in 0..3 {
+        info!(logger, "same");
+    }
+    drain.flush().unwrap();
+    assert_eq!(*sent.lock().unwrap(),
+               vec![b"same".to_vec(), b"last message repeated 2 times".to_vec()]);
+
+    info!(logger, "same");
+    info!(logger, "same");
+    drop(logger);
+    let drain = Arc::try_unwrap(drain).unwrap().into_inner();
+    assert_eq!(sent.lock().unwrap().last(),
+               Some(&b"last message repeated 1 time".to_vec()));
+    assert_eq!(sent.lock().unwrap().len(), 4);
+    drain.send(b"inner").unwrap();
+    assert_eq!(sent.lock().unwrap().len(), 5);
+}
@@ -0,0 +1,69 @@
+// Log a record with sensitive values through the formatter
+fn emit_sensitive_message<F>(formatter: F) -> String
+    where F: ::slog_stream::Format + 'static
+{
+    emit_to_string(formatter, |root| {
+        let logger = root.new(o!("user" => "alice", "api_token" => "t0k3n"));
+        info!(logger, "Payment";
+              "card" => "paid with 4111 1111 1111 1111", "password" => "hunter2");
+    })
+}
+
+fn sensitive_policy() -> RedactionPolicy {
+    RedactionPolicy::new()
+        .deny_key("Password")
+        .deny_key_pattern("(?i)token")
+        .unwrap()
+        .mask_values(r"\b(?:\d[ -]?){13,16}\b")
+        .unwrap()
+}
+
+#[test]
+fn redaction_masks_ksv_values() {
+    let options = FormatOptions::default().redaction(sensitive_policy());
+    let message = emit_sensitive_message(formatter!(Rfc3164ShortKsv).options(options));
+
+    assert!(message.contains("Payment password=[REDACTED] card=paid with [REDACTED] \
+                              api_token=[REDACTED] user=alice"));
+    assert!(!message.contains("hunter2"));
+    assert!(!message.contains("t0k3n"));
+    assert!(!message.contains("4111"));
+}
+
+#[test]
+fn redaction_masks_rfc5424_structured_data() {
+    let options = FormatOptions::default().redaction(sensitive_policy().mask("***"));
+    let message = emit_sensitive_message(formatter!(Rfc5424NativeTsIsoUtc).options(options));
+
+    assert!(message.contains("password=\"***\" card=\"paid with ***\"]"));
+    assert!(message.contains("api_token=\"***\" user=\"alice\"]"));
+}
+
+#[test]
+fn redaction_hashes_values() {
+    let policy = RedactionPolicy::new().deny_key("password").redaction(Redaction::Hash);
+    let options = FormatOptions::default().redaction(policy);
+    let first = emit_sensitive_message(formatter!(Rfc3164ShortKsv).options(options.clone()));
+    let second = emit_sensitive_message(formatter!(Rfc3164ShortKsv).options(options));
+
+    // FNV-1a of "hunter2"
+    assert!(first.contains("password=4db147a54908e973 "));
+    assert_eq!(first, second);
+}
+
+#[test]
+fn redaction_allow_list_emits_only_named_keys() {
+    let policy = RedactionPolicy::new().allow_key("user").allow_key("card");
+    let options = FormatOptions::default().redaction(policy);
+    let message = emit_sensitive_message(formatter!(Rfc3164ShortKsv).options(options));
+
+    assert!(message.contains("Payment card=paid with 4111 1111 1111 1111 user=alice"));
+    assert!(!message.contains("password"));
+    assert!(!message.contains("api_token"));
+}
+
+#[test]
+fn redaction_invalid_pattern() {
+    assert!(RedactionPolicy::new().deny_key_pattern("(token").is_err());
+    assert!(RedactionPolicy::new().mask_values("[0-9").is_err());
+}
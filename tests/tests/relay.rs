@@ -0,0 +1,103 @@
+fn test_relay(config: RelayConfig) -> (Arc<Relay<TestSyslogDrain>>, Arc<Mutex<Vec<Vec<u8>>>>) {
+    let drain = TestSyslogDrain::new();
+    let sent = drain.sent();
+    (Arc::new(Relay::new(drain, config)), sent)
+}
+
+fn sent_strings(sent: &Arc<Mutex<Vec<Vec<u8>>>>) -> Vec<String> {
+    sent.lock()
+        .unwrap()
+        .iter()
+        .map(|message| String::from_utf8_lossy(message).into_owned())
+        .collect()
+}
+
+#[test]
+fn relay_forwards_as_received() {
+    let (relay, sent) = test_relay(RelayConfig::new());
+    relay.relay(b"<14>Oct 11 22:14:15 web-1 app: text\n").unwrap();
+    relay.relay(b"not syslog").unwrap();
+    relay.relay(b"\n").unwrap();
+    assert_eq!(sent_strings(&sent),
+               vec!["<14>Oct 11 22:14:15 web-1 app: text\n\0".to_string(),
+                    "not syslog\n\0".to_string()]);
+    assert_eq!(relay.received(), 2);
+    assert_eq!(relay.forwarded(), 2);
+}
+
+#[test]
+fn relay_converts_messages() {
+    let config = RelayConfig::new().convert(FormatMode::RFC5424).ksv_sd_id("msg@32473");
+    let (relay, sent) = test_relay(config);
+    relay.relay(b"<14>Oct 11 22:14:15 web-1 app[7]: Charged order=o1").unwrap();
+    relay.relay(b"not syslog").unwrap();
+
+    let sent = sent_strings(&sent);
+    assert!(sent[0].ends_with(" web-1 app 7 - [msg@32473 order=\"o1\"] Charged\n\0"));
+    assert!(sent[1].starts_with("<13>1 "));
+    assert!(sent[1].ends_with(" - - - - - not syslog\n\0"));
+    assert_eq!(relay.unparsed(), 1);
+}
+
+#[test]
+fn relay_stream_framing() {
+    let (relay, sent) = test_relay(RelayConfig::new());
+    let stream = b"<14>a: one\n<14>a: two\x0017 <14>a: three\nfour\n<14>a: five" as &[u8];
+    relay.relay_stream(stream).unwrap();
+    assert_eq!(sent_strings(&sent),
+               vec!["<14>a: one\n\0".to_string(),
+                    "<14>a: two\n\0".to_string(),
+                    "<14>a: three\nfour\n\0".to_string(),
+                    "<14>a: five\n\0".to_string()]);
+
+    let (relay, _) = test_relay(RelayConfig::new());
+    assert!(relay.relay_stream(b"99999999 <14>a: too large" as &[u8]).is_err());
+
+    // Frames starting with digits not followed by SP aren't octet counted
+    let (relay, sent) = test_relay(RelayConfig::new());
+    relay.relay_stream(b"2017-03-01 backup done\n10 <14>a: six" as &[u8]).unwrap();
+    assert_eq!(sent_strings(&sent),
+               vec!["2017-03-01 backup done\n\0".to_string(), "<14>a: six\n\0".to_string()]);
+}
+
+#[test]
+fn relay_udp_and_tcp_listeners() {
+    let (relay, sent) = test_relay(RelayConfig::new());
+
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let udp_addr = udp.local_addr().unwrap();
+    listen_udp(relay.clone(), udp);
+    let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+    let tcp_addr = tcp.local_addr().unwrap();
+    listen_tcp(relay.clone(), tcp);
+
+    UdpSocket::bind("127.0.0.1:0").unwrap().send_to(b"<14>udp: one", udp_addr).unwrap();
+    ::std::net::TcpStream::connect(tcp_addr).unwrap().write_all(b"<14>tcp: two\n").unwrap();
+    ::std::thread::sleep(Duration::from_millis(200));
+
+    let mut sent = sent_strings(&sent);
+    sent.sort();
+    assert_eq!(sent, vec!["<14>tcp: two\n\0".to_string(), "<14>udp: one\n\0".to_string()]);
+}
+
+#[test]
+fn relay_tcp_listener_closes_idle_and_excess_connections() {
+    let config = RelayConfig::new().read_timeout(Duration::from_millis(200)).max_connections(1);
+    let (relay, _) = test_relay(config);
+    let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+    let tcp_addr = tcp.local_addr().unwrap();
+    listen_tcp(relay.clone(), tcp);
+
+    let mut idle = ::std::net::TcpStream::connect(tcp_addr).unwrap();
+    ::std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(relay.connections(), 1);
+
+    let mut excess = ::std::net::TcpStream::connect(tcp_addr).unwrap();
+    excess.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(excess.read(&mut [0; 16]).unwrap(), 0);
+
+    idle.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(idle.read(&mut [0; 16]).unwrap(), 0);
+    ::std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(relay.connections(), 0);
+}
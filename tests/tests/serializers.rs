@@ -122,3 +122,43 @@ fn formatter_rfc5424_native_tsiso_utc() {
     assert!(buffer.as_string().contains("lk2=\"lv2\" lk1=\"lv1\"]"));
     assert!(buffer.as_string().contains("]["));
 }
+
+// PROCID
+fn header_fields(proc_id: ProcId) -> HeaderFields {
+    HeaderFields::new(None, Some("test".to_owned()), 12345, Facility::LOG_USER).proc_id(proc_id)
+}
+
+#[test]
+fn rfc3164_custom_and_omitted_proc_id() {
+    let formatter = Rfc3164ShortKsv::from_fields(header_fields(ProcId::Omit));
+    assert!(emit_test_message_to_buffer(formatter).as_string().contains(" test: Test message 1"));
+
+    let formatter = Rfc3164ShortKsv::from_fields(header_fields(ProcId::Custom("worker-3".into())));
+    let buffer = emit_test_message_to_buffer(formatter);
+    assert!(buffer.as_string().contains(" test[worker-3]: Test message 1"));
+}
+
+#[test]
+fn rfc5424_custom_and_omitted_proc_id() {
+    let formatter = Rfc5424NativeTsIsoUtc::from_fields(header_fields(ProcId::Omit));
+    assert!(emit_test_message_to_buffer(formatter).as_string().contains(" test - INFO "));
+
+    let formatter =
+        Rfc5424NativeTsIsoUtc::from_fields(header_fields(ProcId::Custom("worker-3".into())));
+    let buffer = emit_test_message_to_buffer(formatter);
+    assert!(buffer.as_string().contains(" test worker-3 INFO "));
+}
+
+#[test]
+fn severity_overrides_record_level() {
+    let formatter = Rfc3164ShortKsv::from_fields(header_fields(ProcId::Pid));
+    assert!(emit_test_message_to_buffer(formatter).as_string().starts_with("<14>"));
+
+    let fields = header_fields(ProcId::Pid).severity(Severity::LOG_NOTICE);
+    let buffer = emit_test_message_to_buffer(Rfc3164ShortKsv::from_fields(fields));
+    assert!(buffer.as_string().starts_with("<13>"));
+
+    let fields = header_fields(ProcId::Pid).severity(Severity::LOG_ALERT);
+    let buffer = emit_test_message_to_buffer(Rfc5424NativeTsIsoUtc::from_fields(fields));
+    assert!(buffer.as_string().starts_with("<9>1 "));
+}
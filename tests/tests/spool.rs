@@ -0,0 +1,240 @@
+#[test]
+fn spool_replays_in_order() {
+    let spool = Spool::open(SpoolConfig::new(scratch_dir("spool-order"))).unwrap();
+    spool.push(b"first").unwrap();
+    spool.push(b"second").unwrap();
+    assert!(!spool.is_empty());
+
+    let mut replayed = Vec::<Vec<u8>>::new();
+    let count = spool.replay(|m| {
+                                 replayed.push(m.to_vec());
+                                 Ok(())
+                             })
+        .unwrap();
+
+    assert_eq!(count, 2);
+    assert_eq!(replayed, vec![b"first".to_vec(), b"second".to_vec()]);
+    assert!(spool.is_empty());
+    assert_eq!(spool.stats(),
+               SpoolStats {
+                   spooled: 2,
+                   replayed: 2,
+                   discarded: 0,
+               });
+}
+
+#[test]
+fn spool_survives_reopen() {
+    let dir = scratch_dir("spool-reopen");
+    {
+        let spool = Spool::open(SpoolConfig::new(dir.clone())).unwrap();
+        spool.push(b"persisted").unwrap();
+    }
+
+    let spool = Spool::open(SpoolConfig::new(dir)).unwrap();
+    let mut replayed = Vec::<Vec<u8>>::new();
+    spool.replay(|m| {
+                     replayed.push(m.to_vec());
+                     Ok(())
+                 })
+        .unwrap();
+    assert_eq!(replayed, vec![b"persisted".to_vec()]);
+}
+
+#[test]
+fn spool_keeps_message_on_failed_replay() {
+    let spool = Spool::open(SpoolConfig::new(scratch_dir("spool-failed"))).unwrap();
+    spool.push(b"retry me").unwrap();
+
+    let result = spool.replay(|_| Err(SyslogError::NoDestination));
+    assert!(result.is_err());
+    assert!(!spool.is_empty());
+
+    assert_eq!(spool.replay(|_| Ok(())).unwrap(), 1);
+}
+
+#[test]
+fn spool_detects_corruption() {
+    let dir = scratch_dir("spool-corrupt");
+    {
+        let spool = Spool::open(SpoolConfig::new(dir.clone())).unwrap();
+        spool.push(b"intact message").unwrap();
+    }
+
+    // Flip last byte of the only segment
+    let segment = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+    let mut bytes = Vec::new();
+    fs::File::open(&segment).unwrap().read_to_end(&mut bytes).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    fs::File::create(&segment).unwrap().write_all(&bytes).unwrap();
+
+    let spool = Spool::open(SpoolConfig::new(dir)).unwrap();
+    assert_eq!(spool.replay(|_| Ok(())).unwrap(), 0);
+    assert_eq!(spool.stats().discarded, 1);
+    assert!(spool.is_empty());
+}
+
+#[test]
+fn spool_skips_corrupted_entry_and_counts_it() {
+    let dir = scratch_dir("spool-corrupt-middle");
+    {
+        let spool = Spool::open(SpoolConfig::new(dir.clone())).unwrap();
+        spool.push(b"first").unwrap();
+        spool.push(b"second").unwrap();
+        spool.push(b"third").unwrap();
+    }
+
+    // Flip a byte of the second entry payload
+    let segment = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+    let mut bytes = Vec::new();
+    fs::File::open(&segment).unwrap().read_to_end(&mut bytes).unwrap();
+    bytes[12 + 5 + 12] ^= 0xff;
+    fs::File::create(&segment).unwrap().write_all(&bytes).unwrap();
+
+    let spool = Spool::open(SpoolConfig::new(dir)).unwrap();
+    let mut replayed = Vec::<Vec<u8>>::new();
+    spool.replay(|m| {
+                     replayed.push(m.to_vec());
+                     Ok(())
+                 })
+        .unwrap();
+    assert_eq!(replayed, vec![b"first".to_vec(), b"third".to_vec()]);
+    assert_eq!(spool.stats().discarded, 1);
+}
+
+#[test]
+fn spool_resyncs_at_entry_marker_after_garbage() {
+    let dir = scratch_dir("spool-garbage");
+    {
+        let spool = Spool::open(SpoolConfig::new(dir.clone())).unwrap();
+        spool.push(b"first").unwrap();
+        spool.push(b"second").unwrap();
+    }
+
+    // Garbage, including an entry marker with a bad checksum, between the entries
+    let segment = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+    let mut bytes = Vec::new();
+    fs::File::open(&segment).unwrap().read_to_end(&mut bytes).unwrap();
+    let mut garbage = vec![0x55; 64 * 1024];
+    garbage.extend_from_slice(&[0xFE, b'S', b'P', b'L', 0, 0, 0, 3, 0, 0, 0, 0]);
+    garbage.extend_from_slice(b"xyz");
+    let second = 12 + 5;
+    let bytes = [&bytes[..second], &garbage[..], &bytes[second..]].concat();
+    fs::File::create(&segment).unwrap().write_all(&bytes).unwrap();
+
+    let spool = Spool::open(SpoolConfig::new(dir)).unwrap();
+    let mut replayed = Vec::<Vec<u8>>::new();
+    spool.replay(|m| {
+                     replayed.push(m.to_vec());
+                     Ok(())
+                 })
+        .unwrap();
+    assert_eq!(replayed, vec![b"first".to_vec(), b"second".to_vec()]);
+    assert_eq!(spool.stats().discarded, 1);
+}
+
+#[test]
+fn spool_appends_after_torn_tail_to_new_segment() {
+    let dir = scratch_dir("spool-torn");
+    {
+        let spool = Spool::open(SpoolConfig::new(dir.clone())).unwrap();
+        spool.push(b"complete").unwrap();
+    }
+
+    // Half written entry at the end of the segment
+    let segment = fs::read_dir(&dir).unwrap().next().unwrap().unwrap().path();
+    fs::OpenOptions::new().append(true).open(&segment).unwrap().write_all(&[0, 0, 0]).unwrap();
+
+    let spool = Spool::open(SpoolConfig::new(dir)).unwrap();
+    spool.push(b"after restart").unwrap();
+    let mut replayed = Vec::<Vec<u8>>::new();
+    spool.replay(|m| {
+                     replayed.push(m.to_vec());
+                     Ok(())
+                 })
+        .unwrap();
+    assert_eq!(replayed, vec![b"complete".to_vec(), b"after restart".to_vec()]);
+    assert_eq!(spool.stats().discarded, 1);
+}
+
+#[test]
+fn spool_enforces_size_cap() {
+    let config = SpoolConfig::new(scratch_dir("spool-cap")).segment_size(16).max_size(32);
+    let spool = Spool::open(config).unwrap();
+    for _ in 0..10 {
+        spool.push(b"0123456789").unwrap();
+    }
+
+    let count = spool.replay(|_| Ok(())).unwrap();
+    assert!(count < 10);
+    assert_eq!(spool.stats().discarded, 10 - count);
+}
+
+#[test]
+fn spool_drain_spools_while_transport_is_down() {
+    let transport = TestSyslogDrain::new();
+    let sent = transport.sent();
+    let failing = transport.failing();
+
+    let config = SpoolConfig::new(scratch_dir("spool-drain")).retry_interval(Duration::from_secs(0));
+    let drain = SpoolDrain::new(transport, config).unwrap();
+
+    failing.store(true, Ordering::SeqCst);
+    drain.send(b"one").unwrap();
+    drain.send(b"two").unwrap();
+    assert!(sent.lock().unwrap().is_empty());
+
+    failing.store(false, Ordering::SeqCst);
+    drain.send(b"three").unwrap();
+    assert_eq!(*sent.lock().unwrap(),
+               vec![b"one".to_vec(), b"two".to_vec(), b"three".to_vec()]);
+    assert_eq!(drain.stats().replayed, 2);
+}
+
+#[test]
+fn spool_drain_overflows_memory_buffer_to_disk() {
+    let dir = scratch_dir("spool-memory");
+    let transport = TestSyslogDrain::new();
+    let sent = transport.sent();
+    let failing = transport.failing();
+
+    let config = SpoolConfig::new(dir.clone())
+        .retry_interval(Duration::from_secs(0))
+        .memory_buffer(2);
+    let drain = SpoolDrain::new(transport, config).unwrap();
+
+    failing.store(true, Ordering::SeqCst);
+    for message in &[&b"one"[..], b"two", b"three"] {
+        drain.send(message).unwrap();
+    }
+    // Only the oldest message overflowed to disk
+    assert_eq!(drain.stats().spooled, 1);
+
+    failing.store(false, Ordering::SeqCst);
+    drain.send(b"four").unwrap();
+    assert_eq!(*sent.lock().unwrap(),
+               vec![b"one".to_vec(), b"two".to_vec(), b"three".to_vec(), b"four".to_vec()]);
+}
+
+#[test]
+fn spool_drain_writes_memory_buffer_on_drop() {
+    let dir = scratch_dir("spool-memory-drop");
+    let transport = TestSyslogDrain::new();
+    transport.failing().store(true, Ordering::SeqCst);
+    {
+        let config = SpoolConfig::new(dir.clone()).memory_buffer(10);
+        let drain = SpoolDrain::new(transport, config).unwrap();
+        drain.send(b"kept").unwrap();
+        assert_eq!(drain.stats().spooled, 0);
+    }
+
+    let spool = Spool::open(SpoolConfig::new(dir)).unwrap();
+    let mut replayed = Vec::<Vec<u8>>::new();
+    spool.replay(|m| {
+                     replayed.push(m.to_vec());
+                     Ok(())
+                 })
+        .unwrap();
+    assert_eq!(replayed, vec![b"kept".to_vec()]);
+}
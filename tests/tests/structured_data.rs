@@ -0,0 +1,179 @@
+// Log a record with origin and application keys through the formatter
+fn emit_grouped_message<F>(formatter: F) -> String
+    where F: ::slog_stream::Format + 'static
+{
+    emit_to_string(formatter, |root| {
+        let logger = root.new(o!("origin.software" => "billing", "origin.ip" => "10.0.0.1"));
+        info!(logger, "Charged"; "order" => "o1", "amount" => 10);
+    })
+}
+
+#[test]
+fn structured_data_elements_by_prefix_and_key() {
+    let options = FormatOptions::default()
+        .sd_element(SdElement::origin())
+        .sd_element(SdElement::new("order@32473").key("order").param("amount", "total"));
+    let message = emit_grouped_message(formatter!(Rfc5424NativeTsIsoUtc).options(options));
+
+    assert!(message.contains("][logger@"));
+    assert!(message.contains("[origin ip=\"10.0.0.1\" software=\"billing\"]"));
+    assert!(message.contains("[order@32473 total=\"10\" order=\"o1\"]"));
+    assert!(!message.contains("origin.ip"));
+    assert!(!message.contains(" amount="));
+}
+
+#[test]
+fn structured_data_omits_elements_without_keys() {
+    let options = FormatOptions::default().sd_element(SdElement::meta());
+    let message = emit_grouped_message(formatter!(Rfc5424NativeTsIsoUtc).options(options));
+    assert!(!message.contains("[meta"));
+}
+
+#[test]
+fn structured_data_keys_stay_in_ksv() {
+    let options = FormatOptions::default().sd_element(SdElement::origin());
+    let message = emit_grouped_message(formatter!(Rfc3164ShortKsv).options(options));
+    assert!(message.contains("origin.ip=10.0.0.1 origin.software=billing"));
+}
+
+#[test]
+fn structured_data_invalid_param_names_stay_in_msg() {
+    let options = FormatOptions::default().sd_element(SdElement::origin());
+    let message = emit_to_string(formatter!(Rfc5424NativeTsIsoUtc).options(options), |root| {
+        info!(root, "Charged"; "origin.ip" => "10.0.0.1", "origin.client id" => "c1");
+    });
+
+    assert!(message.contains("[origin ip=\"10.0.0.1\"]"));
+    assert!(message.contains("origin.client id=\"c1\""));
+    assert_eq!(SdElement::origin().param_name("origin.client id"), None);
+    assert_eq!(SdElement::origin().param_name("origin."), None);
+    assert_eq!(SdElement::origin().param_name("origin.ip"), Some("ip"));
+}
+
+#[test]
+fn structured_data_validation() {
+    assert!(SdElement::origin().validate().is_ok());
+    assert!(SdElement::new("order@32473").key("order").validate().is_ok());
+    assert!(SdElement::new("order").validate().is_err());
+    assert!(SdElement::new("order@pen").validate().is_err());
+    assert!(SdElement::new("order@32473").param("order", "order id").validate().is_err());
+    match validate_sd_name("a=b") {
+        Err(SyslogError::ConfigInvalid(message)) => assert!(message.contains("a=b")),
+        result => panic!("unexpected result {:?}", result),
+    }
+
+    assert!(syslog().tcp().sd_element(SdElement::new("order")).validate().is_err());
+    let warnings = syslog().tcp().sd_element(SdElement::origin()).validate().unwrap();
+    assert!(warnings.contains(&ConfigWarning::StructuredDataIgnored));
+}
+
+#[test]
+fn structured_data_auto_elements() {
+    let auto_sd = AutoStructuredData::all()
+        .origin_ip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)))
+        .software("billing", "1.2.3");
+    let formatter = formatter!(Rfc5424NativeTsIsoUtc)
+        .options(FormatOptions::default().auto_sd(auto_sd));
+    let messages = emit_to_string(formatter, |logger| {
+        info!(logger, "first");
+        info!(logger, "second");
+    });
+
+    assert!(messages.contains("[timeQuality tzKnown=\"1\""));
+    assert!(messages.contains("[origin ip=\"10.0.0.2\" software=\"billing\" \
+                               swVersion=\"1.2.3\"]"));
+    assert!(messages.contains("[meta sequenceId=\"1\""));
+    assert!(messages.contains("[meta sequenceId=\"2\""));
+}
+
+#[test]
+fn structured_data_auto_elements_replace_nilvalue() {
+    let formatter = formatter!(Rfc5424KsvTsIsoUtc)
+        .options(FormatOptions::default().auto_sd(AutoStructuredData::new().meta(true)));
+    let buffer = emit_test_message_to_buffer(formatter);
+    assert!(buffer.as_string().contains(" INFO [meta sequenceId=\"1\""));
+
+    let buffer = emit_test_message_to_buffer(formatter!(Rfc5424KsvTsIsoUtc));
+    assert!(buffer.as_string().contains(" INFO - Test message 1"));
+}
+
+#[test]
+fn structured_data_auto_elements_validation() {
+    let long = "v".repeat(SD_SW_VERSION_MAX_LEN + 1);
+    assert!(AutoStructuredData::all().software("app", long).validate().is_err());
+    let warnings = syslog().tcp().auto_sd(AutoStructuredData::all()).validate().unwrap();
+    assert!(warnings.contains(&ConfigWarning::StructuredDataIgnored));
+}
+
+#[test]
+fn structured_data_source_location() {
+    let options = FormatOptions::default().source_location(SourceLocation::new("source@32473"));
+    let message = emit_grouped_message(formatter!(Rfc5424NativeTsIsoUtc).options(options));
+    assert!(message.contains("[source@32473 file=\"tests/tests/structured_data.rs\" line=\"8\" \
+                              module=\"runtime_tests::tests\"]"),
+            message);
+
+    let source = SourceLocation::new("source@32473").prefix("src.").trim_prefix("tests");
+    let options = FormatOptions::default().source_location(source);
+    let message = emit_grouped_message(formatter!(Rfc3164ShortKsv).options(options));
+    assert!(message.contains(" src.file=tests/structured_data.rs src.line=8 \
+                              src.module=runtime_tests::tests"),
+            message);
+}
+
+#[test]
+fn structured_data_source_path_trimming() {
+    assert_eq!(trim_source_path("/home/ci/app/src/db.rs", Some("/home/ci/app")),
+               "src/db.rs");
+    assert_eq!(trim_source_path("/home/ci/.cargo/registry/src/github.com-1ecc6299db9ec823/\
+                                 slog-1.5.2/src/lib.rs",
+                                None),
+               "slog-1.5.2/src/lib.rs");
+    assert_eq!(trim_source_path("./src/main.rs", None), "src/main.rs");
+    assert_eq!(trim_source_path("src/main.rs", Some("/elsewhere")), "src/main.rs");
+    assert_eq!(trim_source_path("/home/ci/app/src/db.rs", Some("/home/ci/app/")),
+               "src/db.rs");
+    assert_eq!(trim_source_path("/home/ci/app2/src/db.rs", Some("/home/ci/app")),
+               "/home/ci/app2/src/db.rs");
+
+    assert!(SourceLocation::new("source@32473").validate().is_ok());
+    assert!(SourceLocation::new("source").validate().is_err());
+}
+
+#[test]
+fn structured_data_record_and_logger_sd_ids() {
+    let options = FormatOptions::default().record_sd_id("app@32473").logger_sd_id("ctx@32473");
+    let message = emit_grouped_message(formatter!(Rfc5424NativeTsIsoUtc).options(options));
+    assert!(message.contains(" INFO [app@32473 amount=\"10\" order=\"o1\"]\
+                              [ctx@32473 origin.ip=\"10.0.0.1\" origin.software=\"billing\"] "),
+            message);
+
+    // Elements without values are omitted, NILVALUE without any
+    let message = emit_to_string(formatter!(Rfc5424NativeTsIsoUtc), |root| info!(root, "Bare"));
+    assert!(message.contains(" INFO - Bare"), message);
+
+    assert!(syslog().tcp().record_sd_id("app").validate().is_err());
+    assert!(syslog().tcp().logger_sd_id("ctx@32473").validate().is_ok());
+}
+
+#[test]
+fn structured_data_source_location_follows_field_rules() {
+    let source = SourceLocation::new("source@32473");
+    let options = FormatOptions::default()
+        .source_location(source.clone())
+        .fields(FieldTransforms::new().order(FieldOrder::LoggerFirst))
+        .redaction(RedactionPolicy::new().deny_key("source.module"));
+    let message = emit_grouped_message(formatter!(Rfc3164ShortKsv).options(options));
+    assert!(message.contains(" origin.software=billing amount=10 order=o1 \
+                              source.file=tests/tests/structured_data.rs source.line=8 \
+                              source.module=[REDACTED]"),
+            message);
+
+    let options = FormatOptions::default()
+        .source_location(source)
+        .fields(FieldTransforms::new().drop_key("source.file"))
+        .redaction(RedactionPolicy::new().allow_key("order").allow_key("source.line"));
+    let message = emit_grouped_message(formatter!(Rfc5424NativeTsIsoUtc).options(options));
+    assert!(message.contains(" INFO [msg@32473 order=\"o1\"][source@32473 line=\"8\"] "),
+            message);
+}
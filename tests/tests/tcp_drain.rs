@@ -0,0 +1,62 @@
+#[test]
+fn tcp_drain_lazy_connect_defers_connecting() {
+    // Reserve a free port, nothing listens on it until the listener is bound again
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+
+    let drain = TCPDrainDelimited::new(addr, formatter!(Rfc3164ShortKsv))
+        .lazy_connect(true)
+        .connect_timeout(Duration::from_secs(1))
+        .write_timeout(Duration::from_secs(1))
+        .connect()
+        .unwrap();
+
+    let err = drain.send(b"<14>unreachable\n").unwrap_err();
+    assert_eq!(err.transport(), Some(Transport::TCP));
+
+    let listener = TcpListener::bind(addr).unwrap();
+    let logger = Logger::root(drain.fuse(), o!());
+    info!(logger, "lazily connected");
+
+    let (stream, _) = listener.accept().unwrap();
+    let line = BufReader::new(stream).lines().next().unwrap().unwrap();
+    assert!(line.contains("lazily connected"));
+}
+
+#[test]
+fn tcp_drain_connect_fails_without_lazy_connect() {
+    let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let err = TCPDrainFramed::new(addr, formatter!(Rfc5424KsvTsIsoUtc))
+        .connect_timeout(Duration::from_secs(1))
+        .connect()
+        .unwrap_err();
+    match err {
+        SyslogError::ConnectFailed { transport, .. } => assert_eq!(transport, Transport::TCP),
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn tcp_drain_reconnects_after_failed_write() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let drain = TCPDrainDelimited::new(listener.local_addr().unwrap(),
+                                       formatter!(Rfc3164ShortKsv))
+        .connect()
+        .unwrap();
+
+    // Server drops the connection, writes fail once the reset arrives
+    drop(listener.accept().unwrap());
+    let mut failed = false;
+    for _ in 0..100 {
+        if drain.send(b"<14>dropped").is_err() {
+            failed = true;
+            break;
+        }
+        ::std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(failed);
+
+    drain.send(b"<14>reconnected").unwrap();
+    let (stream, _) = listener.accept().unwrap();
+    let line = BufReader::new(stream).lines().next().unwrap().unwrap();
+    assert_eq!(line, "<14>reconnected");
+}
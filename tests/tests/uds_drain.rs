@@ -0,0 +1,129 @@
+#[test]
+fn uds_drain_detects_datagram_socket() {
+    let path = scratch_dir("uds-dgram").with_extension("sock");
+    let _ = fs::remove_file(&path);
+    let server = UnixDatagram::bind(&path).unwrap();
+
+    let drain = UDSDrain::new(path.clone(), formatter!(Rfc3164ShortKsv)).connect().unwrap();
+    assert_eq!(drain.connected_socket_type(), UDSSocketType::Datagram);
+
+    let logger = Logger::root(drain.fuse(), o!());
+    info!(logger, "datagram message");
+
+    let mut buf = [0u8; 1024];
+    let len = server.recv(&mut buf).unwrap();
+    let received = String::from_utf8_lossy(&buf[..len]).into_owned();
+    assert!(received.contains("datagram message"));
+    assert!(received.ends_with("\n\0"));
+}
+
+#[test]
+fn uds_drain_falls_back_to_stream_socket() {
+    let path = scratch_dir("uds-stream").with_extension("sock");
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let drain = UDSDrain::new(path.clone(), formatter!(Rfc3164ShortKsv)).connect().unwrap();
+    assert_eq!(drain.connected_socket_type(), UDSSocketType::Stream);
+
+    let logger = Logger::root(drain.fuse(), o!());
+    info!(logger, "first stream message");
+    info!(logger, "second stream message");
+
+    let (stream, _) = listener.accept().unwrap();
+    let lines = BufReader::new(stream)
+        .lines()
+        .take(2)
+        .map(|l| l.unwrap())
+        .collect::<Vec<_>>();
+    assert!(lines[0].contains("first stream message"));
+    assert!(lines[1].contains("second stream message"));
+    assert!(!lines[0].contains("\0"));
+}
+
+#[test]
+fn uds_drain_missing_socket_fails_to_connect() {
+    let path = scratch_dir("uds-missing").with_extension("sock");
+    let err = UDSDrain::new(path.clone(), formatter!(Rfc3164ShortKsv)).connect().unwrap_err();
+    match err {
+        SyslogError::ConnectFailed { transport, ref destination, .. } => {
+            assert_eq!(transport, Transport::UDS);
+            assert_eq!(destination, &path.display().to_string());
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn uds_drain_survives_daemon_restart() {
+    let path = scratch_dir("uds-restart").with_extension("sock");
+    let _ = fs::remove_file(&path);
+    let server = UnixDatagram::bind(&path).unwrap();
+
+    let retry = UDSRetryConfig::default().retries(2).retry_delay(Duration::from_millis(1));
+    let drain = UDSDrain::new(path.clone(), formatter!(Rfc3164ShortKsv))
+        .socket_type(UDSSocketType::Datagram)
+        .retry(retry)
+        .connect()
+        .unwrap();
+
+    // Daemon restarts, binding a new socket at the same path
+    drop(server);
+    fs::remove_file(&path).unwrap();
+    let server = UnixDatagram::bind(&path).unwrap();
+
+    drain.send(b"after restart").unwrap();
+    let mut buf = [0u8; 1024];
+    let len = server.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"after restart");
+    assert_eq!(drain.dropped(), 0);
+}
+
+#[test]
+fn uds_drain_counts_dropped_messages() {
+    let path = scratch_dir("uds-dropped").with_extension("sock");
+    let _ = fs::remove_file(&path);
+    let server = UnixDatagram::bind(&path).unwrap();
+
+    let retry = UDSRetryConfig::default()
+        .retries(1)
+        .retry_delay(Duration::from_millis(1))
+        .fail_on_drop(false);
+    let drain = UDSDrain::new(path.clone(), formatter!(Rfc3164ShortKsv))
+        .retry(retry)
+        .connect()
+        .unwrap();
+
+    // Daemon is gone for good
+    drop(server);
+    fs::remove_file(&path).unwrap();
+
+    assert!(drain.send(b"lost").is_ok());
+    assert_eq!(drain.dropped(), 1);
+}
+
+#[test]
+fn uds_drain_returns_non_transient_errors() {
+    let path = scratch_dir("uds-non-transient").with_extension("sock");
+    let _ = fs::remove_file(&path);
+    let _server = UnixDatagram::bind(&path).unwrap();
+
+    let retry = UDSRetryConfig::default()
+        .retry_delay(Duration::from_millis(1))
+        .fail_on_drop(false);
+    let drain = UDSDrain::new(path.clone(), formatter!(Rfc3164ShortKsv))
+        .socket_type(UDSSocketType::Datagram)
+        .retry(retry)
+        .connect()
+        .unwrap();
+
+    // Larger than any datagram socket buffer, retrying won't help
+    match drain.send(&vec![b'x'; 4 * 1024 * 1024]) {
+        Err(SyslogError::MessageTooLarge { transport: Transport::UDS, size, .. }) => {
+            assert_eq!(size, 4 * 1024 * 1024)
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+    assert_eq!(drain.dropped(), 0);
+    assert_eq!(drain.stats().errors.too_large, 1);
+}